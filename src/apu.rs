@@ -0,0 +1,311 @@
+pub mod dmc;
+pub mod envelope;
+pub mod length_counter;
+pub mod noise;
+pub mod pulse;
+pub mod triangle;
+
+use self::dmc::Dmc;
+use self::noise::Noise;
+use self::pulse::Pulse;
+use self::triangle::Triangle;
+use crate::mapper::Mapper;
+use crate::types::{Address, Byte};
+
+// $4015 bits.
+const STATUS_PULSE_1: Byte = 0x01;
+const STATUS_PULSE_2: Byte = 0x02;
+const STATUS_TRIANGLE: Byte = 0x04;
+const STATUS_NOISE: Byte = 0x08;
+const STATUS_DMC: Byte = 0x10;
+const STATUS_FRAME_IRQ: Byte = 0x40;
+const STATUS_DMC_IRQ: Byte = 0x80;
+
+// $4017 bits.
+const FRAME_FIVE_STEP: Byte = 0x80;
+const FRAME_IRQ_INHIBIT: Byte = 0x40;
+
+// Frame counter steps in CPU cycles after its reset (NTSC), twice the APU-cycle figures.
+// Each sequence ends one cycle before its period with a quarter and half frame. The
+// 4-step sequence raises the frame IRQ on its last three cycles; the 5-step sequence
+// never does.
+const QUARTER_FRAME_STEPS: [u32; 3] = [7457, 14913, 22371];
+const HALF_FRAME_STEP: u32 = 14913;
+const FOUR_STEP_PERIOD: u32 = 29830;
+const FIVE_STEP_PERIOD: u32 = 37282;
+
+pub struct Apu {
+    pulses: [Pulse; 2],
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+    is_five_step: bool,
+    is_irq_inhibited: bool,
+    frame_irq: bool,
+    frame_cycle: u32,
+    // A $4017 write resets the frame counter 3 or 4 CPU cycles later.
+    reset_delay: Byte,
+    is_odd_cycle: bool,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Apu {
+            pulses: [Pulse::new(true), Pulse::new(false)],
+            triangle: Triangle::default(),
+            noise: Noise::default(),
+            dmc: Dmc::default(),
+            is_five_step: false,
+            is_irq_inhibited: false,
+            frame_irq: false,
+            frame_cycle: 0,
+            reset_delay: 0,
+            is_odd_cycle: false,
+        }
+    }
+
+    // CPU reads of $4000-$401F. Only $4015 is readable; the rest is approximated as 0.
+    pub fn read(&mut self, register: Address) -> Byte {
+        match register {
+            0x15 => {
+                let status = [
+                    (self.pulses[0].is_active(), STATUS_PULSE_1),
+                    (self.pulses[1].is_active(), STATUS_PULSE_2),
+                    (self.triangle.is_active(), STATUS_TRIANGLE),
+                    (self.noise.is_active(), STATUS_NOISE),
+                    (self.dmc.is_active(), STATUS_DMC),
+                    (self.frame_irq, STATUS_FRAME_IRQ),
+                    (self.dmc.irq(), STATUS_DMC_IRQ),
+                ]
+                .iter()
+                .filter(|(is_set, _)| *is_set)
+                .fold(0, |status, (_, bit)| status | bit);
+                // Reading the status acknowledges the frame IRQ.
+                self.frame_irq = false;
+                status
+            }
+            _ => 0,
+        }
+    }
+
+    // CPU writes to $4000-$401F.
+    pub fn write(&mut self, register: Address, data: Byte) {
+        match register {
+            0x00..=0x03 => self.pulses[0].write(register, data),
+            0x04..=0x07 => self.pulses[1].write(register - 0x04, data),
+            0x08..=0x0B => self.triangle.write(register - 0x08, data),
+            0x0C..=0x0F => self.noise.write(register - 0x0C, data),
+            0x10..=0x13 => self.dmc.write(register - 0x10, data),
+            0x15 => {
+                self.pulses[0].set_enabled(data & STATUS_PULSE_1 != 0);
+                self.pulses[1].set_enabled(data & STATUS_PULSE_2 != 0);
+                self.triangle.set_enabled(data & STATUS_TRIANGLE != 0);
+                self.noise.set_enabled(data & STATUS_NOISE != 0);
+                self.dmc.set_enabled(data & STATUS_DMC != 0);
+            }
+            0x17 => {
+                self.is_five_step = data & FRAME_FIVE_STEP != 0;
+                self.is_irq_inhibited = data & FRAME_IRQ_INHIBIT != 0;
+                if self.is_irq_inhibited {
+                    self.frame_irq = false;
+                }
+                self.reset_delay = if self.is_odd_cycle { 4 } else { 3 };
+            }
+            _ => (),
+        }
+    }
+
+    pub fn irq(&self) -> bool {
+        self.frame_irq || self.dmc.irq()
+    }
+
+    // The address of the DMC sample byte to fetch, once the sample buffer has emptied.
+    // The console reads it over the CPU bus, halting the CPU, and hands it back through
+    // `load_dmc_sample`.
+    pub fn get_dmc_request(&self) -> Option<Address> {
+        self.dmc.get_request()
+    }
+
+    pub fn load_dmc_sample(&mut self, data: Byte) {
+        self.dmc.load_sample(data);
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulses.iter_mut().for_each(Pulse::clock_quarter_frame);
+        self.triangle.clock_quarter_frame();
+        self.noise.clock_quarter_frame();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulses.iter_mut().for_each(Pulse::clock_half_frame);
+        self.triangle.clock_half_frame();
+        self.noise.clock_half_frame();
+    }
+
+    fn step_frame_counter(&mut self) {
+        if self.reset_delay > 0 {
+            self.reset_delay -= 1;
+            if self.reset_delay == 0 {
+                self.frame_cycle = 0;
+                // Entering the 5-step mode clocks every unit immediately.
+                if self.is_five_step {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+                return;
+            }
+        }
+        self.frame_cycle += 1;
+        let period = if self.is_five_step {
+            FIVE_STEP_PERIOD
+        } else {
+            FOUR_STEP_PERIOD
+        };
+        if QUARTER_FRAME_STEPS.contains(&self.frame_cycle) {
+            self.clock_quarter_frame();
+        }
+        if self.frame_cycle == HALF_FRAME_STEP {
+            self.clock_half_frame();
+        }
+        if self.frame_cycle == period - 1 {
+            self.clock_quarter_frame();
+            self.clock_half_frame();
+        }
+        if !self.is_five_step && !self.is_irq_inhibited && self.frame_cycle >= period - 2 {
+            self.frame_irq = true;
+        }
+        if self.frame_cycle == period {
+            self.frame_cycle = 0;
+        }
+    }
+
+    // Advances one CPU cycle.
+    pub fn step(&mut self) {
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if self.is_odd_cycle {
+            self.pulses.iter_mut().for_each(Pulse::clock_timer);
+        }
+        self.step_frame_counter();
+        self.is_odd_cycle = !self.is_odd_cycle;
+    }
+
+    // The mixed output of the channels and the cartridge's expansion audio, from 0.0
+    // to about 1.0, using the nonlinear mixer formulas.
+    pub fn output(&self, mapper: &dyn Mapper) -> f32 {
+        let pulse = (self.pulses[0].output() + self.pulses[1].output()) as f32;
+        let pulse = if pulse == 0.0 {
+            0.0
+        } else {
+            95.88 / (8128.0 / pulse + 100.0)
+        };
+        let tnd = self.triangle.output() as f32 / 8227.0
+            + self.noise.output() as f32 / 12241.0
+            + self.dmc.output() as f32 / 22638.0;
+        let tnd = if tnd == 0.0 {
+            0.0
+        } else {
+            159.79 / (1.0 / tnd + 100.0)
+        };
+        pulse + tnd + mapper.audio_output()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapper::nrom::Nrom;
+    use crate::mapper::test::cartridge;
+
+    fn run(apu: &mut Apu, cycles: u32) {
+        for _ in 0..cycles {
+            apu.step();
+        }
+    }
+
+    #[test]
+    fn test_four_step_frame_irq() {
+        let mut apu = Apu::new();
+        // The IRQ is first raised on CPU cycle 29828 of the sequence.
+        run(&mut apu, 29827);
+        assert!(!apu.irq());
+        run(&mut apu, 1);
+        assert!(apu.irq());
+        assert_eq!(apu.read(0x15), STATUS_FRAME_IRQ);
+        assert!(!apu.irq());
+
+        // Setting the inhibit bit acknowledges a pending IRQ and blocks new ones.
+        run(&mut apu, 29830);
+        assert!(apu.irq());
+        apu.write(0x17, FRAME_IRQ_INHIBIT);
+        assert!(!apu.irq());
+        run(&mut apu, 29830 * 2);
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn test_length_counters_and_status() {
+        let mut apu = Apu::new();
+        apu.write(0x15, 0x0F);
+        // Length index 1 (254) for pulse 1, index 0 (10) for the others.
+        apu.write(0x03, 0x08);
+        apu.write(0x07, 0x00);
+        apu.write(0x0B, 0x00);
+        apu.write(0x0F, 0x00);
+        assert_eq!(apu.read(0x15), 0x0F);
+        // Half frames land on CPU cycles 14913 and 29829 of each 29830-cycle frame, so
+        // the tenth is on cycle 4 * 29830 + 29829.
+        run(&mut apu, 4 * 29830 + 29828);
+        assert_eq!(apu.read(0x15) & 0x0F, 0x0F);
+        run(&mut apu, 1);
+        assert_eq!(apu.read(0x15) & 0x0F, STATUS_PULSE_1);
+
+        apu.write(0x15, 0x00);
+        assert_eq!(apu.read(0x15) & 0x0F, 0);
+        // Disabled channels do not load.
+        apu.write(0x03, 0x08);
+        assert_eq!(apu.read(0x15) & 0x0F, 0);
+    }
+
+    #[test]
+    fn test_five_step_mode_clocks_on_write() {
+        let mut apu = Apu::new();
+        apu.write(0x15, STATUS_NOISE);
+        apu.write(0x0F, 0x18);
+        // Index 3 loads 2; the write clocks a half frame once the reset happens.
+        apu.write(0x17, FRAME_FIVE_STEP);
+        run(&mut apu, 3);
+        assert_eq!(apu.read(0x15), STATUS_NOISE);
+        // The next half frame is on CPU cycle 14913.
+        run(&mut apu, 14912);
+        assert_eq!(apu.read(0x15), STATUS_NOISE);
+        run(&mut apu, 1);
+        assert_eq!(apu.read(0x15), 0);
+        // The 5-step sequence never raises the IRQ.
+        run(&mut apu, 37282 * 2);
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn test_mixer() {
+        let mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut apu = Apu::new();
+        // The idle triangle sits at level 15.
+        let triangle = 159.79 / (8227.0 / 15.0 + 100.0);
+        assert!((apu.output(&mapper) - triangle).abs() < 1e-6);
+        apu.write(0x15, STATUS_PULSE_1);
+        // Constant volume 15, duty 75%, which outputs from step 0.
+        apu.write(0x00, 0xFF);
+        apu.write(0x02, 0x80);
+        apu.write(0x03, 0x08);
+        let pulse = 95.88 / (8128.0 / 15.0 + 100.0);
+        assert!((apu.output(&mapper) - triangle - pulse).abs() < 1e-6);
+    }
+}
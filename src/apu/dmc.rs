@@ -0,0 +1,214 @@
+use crate::types::{Address, Byte};
+
+// Output timer periods in CPU cycles (NTSC).
+const RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+// The delta modulation channel ($4010-$4013). Its memory reader fetches sample bytes
+// over the CPU bus, which the console performs when `get_request` asks, and the output
+// unit moves a 7-bit level up or down by 2 for each bit of the sample.
+pub struct Dmc {
+    is_irq_enabled: bool,
+    is_loop: bool,
+    timer_period: u16,
+    timer: u16,
+    sample_address: Address,
+    sample_length: u16,
+    // The memory reader.
+    current_address: Address,
+    bytes_remaining: u16,
+    sample_buffer: Option<Byte>,
+    // The output unit.
+    shift_register: Byte,
+    bits_remaining: Byte,
+    is_silent: bool,
+    output_level: Byte,
+    irq: bool,
+}
+
+impl Default for Dmc {
+    fn default() -> Self {
+        Dmc {
+            is_irq_enabled: false,
+            is_loop: false,
+            timer_period: RATE_TABLE[0],
+            timer: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            is_silent: true,
+            output_level: 0,
+            irq: false,
+        }
+    }
+}
+
+impl Dmc {
+    pub fn write(&mut self, register: Address, data: Byte) {
+        match register {
+            0 => {
+                self.is_irq_enabled = data & 0x80 == 0x80;
+                self.is_loop = data & 0x40 == 0x40;
+                self.timer_period = RATE_TABLE[(data & 0x0F) as usize];
+                if !self.is_irq_enabled {
+                    self.irq = false;
+                }
+            }
+            1 => self.output_level = data & 0x7F,
+            2 => self.sample_address = 0xC000 | (data as Address) << 6,
+            _ => self.sample_length = (data as u16) << 4 | 0x01,
+        }
+    }
+
+    // Bit 4 of $4015. Enabling restarts the sample only once the previous one has ended.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    // The address of the next sample byte, when the buffer is empty and the sample has
+    // bytes left.
+    pub fn get_request(&self) -> Option<Address> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    pub fn load_sample(&mut self, data: Byte) {
+        self.sample_buffer = Some(data);
+        // The address wraps from $FFFF to $8000.
+        self.current_address = self.current_address.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.is_loop {
+                self.restart();
+            } else if self.is_irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    // Clocked every CPU cycle, as the rate table counts CPU cycles.
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period - 1;
+        if !self.is_silent {
+            if self.shift_register & 0x01 == 0x01 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(data) => {
+                    self.is_silent = false;
+                    self.shift_register = data;
+                }
+                None => self.is_silent = true,
+            }
+        }
+    }
+
+    pub fn output(&self) -> Byte {
+        self.output_level
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sample_registers_and_reader() {
+        let mut dmc = Dmc::default();
+        // A 65-byte sample at $FFC0 wraps from $FFFF to $8000.
+        dmc.write(2, 0xFF);
+        dmc.write(3, 0x04);
+        dmc.set_enabled(true);
+        assert_eq!(dmc.get_request(), Some(0xFFC0));
+        for _ in 0..0x40 {
+            dmc.load_sample(0);
+            dmc.sample_buffer = None;
+        }
+        assert_eq!(dmc.get_request(), Some(0x8000));
+        dmc.load_sample(0);
+        assert!(!dmc.is_active());
+        dmc.sample_buffer = None;
+        assert_eq!(dmc.get_request(), None);
+    }
+
+    #[test]
+    fn test_loop_and_irq() {
+        let mut dmc = Dmc::default();
+        dmc.write(0, 0x80);
+        dmc.write(3, 0x00);
+        dmc.set_enabled(true);
+        dmc.load_sample(0);
+        assert!(dmc.irq() && !dmc.is_active());
+        // Clearing the enable bit acknowledges the IRQ.
+        dmc.write(0, 0x00);
+        assert!(!dmc.irq());
+
+        dmc.write(0, 0xC0);
+        dmc.set_enabled(true);
+        dmc.load_sample(0);
+        assert!(!dmc.irq() && dmc.is_active());
+        assert_eq!(dmc.get_request(), None);
+        dmc.sample_buffer = None;
+        assert_eq!(dmc.get_request(), Some(0xC000));
+    }
+
+    #[test]
+    fn test_output_unit() {
+        let mut dmc = Dmc::default();
+        // The fastest rate, 54 cycles per bit.
+        dmc.write(0, 0x0F);
+        dmc.write(1, 0x40);
+        dmc.write(3, 0x00);
+        dmc.set_enabled(true);
+        dmc.load_sample(0b0000_0111);
+        // The first output cycle is silent and takes the buffered byte at its end.
+        for _ in 0..1 + 54 * 7 {
+            dmc.clock_timer();
+        }
+        assert_eq!(dmc.shift_register, 0b0000_0111);
+        assert!(dmc.get_request().is_none());
+        for _ in 0..54 * 8 {
+            dmc.clock_timer();
+        }
+        assert_eq!(dmc.output(), 0x40 + 6 - 10);
+    }
+}
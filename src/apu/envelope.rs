@@ -0,0 +1,78 @@
+use crate::types::Byte;
+
+// The volume unit of the pulse and noise channels: either a constant volume, or a
+// decay from 15 to 0 whose rate the same four bits set, optionally looping.
+#[derive(Default)]
+pub struct Envelope {
+    is_start: bool,
+    is_loop: bool,
+    is_constant: bool,
+    volume: Byte,
+    divider: Byte,
+    decay: Byte,
+}
+
+impl Envelope {
+    // The low six bits of $4000/$4004/$400C. Bit 5 also halts the length counter.
+    pub fn write(&mut self, data: Byte) {
+        self.is_loop = data & 0x20 == 0x20;
+        self.is_constant = data & 0x10 == 0x10;
+        self.volume = data & 0x0F;
+    }
+
+    // Writes to the fourth register of a channel restart the decay on the next clock.
+    pub fn restart(&mut self) {
+        self.is_start = true;
+    }
+
+    // Clocked by the frame counter's quarter frames.
+    pub fn clock(&mut self) {
+        if self.is_start {
+            self.is_start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.is_loop {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn get_volume(&self) -> Byte {
+        if self.is_constant {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decay_and_loop() {
+        let mut envelope = Envelope::default();
+        // Decay one step every second clock, looping.
+        envelope.write(0x21);
+        envelope.restart();
+        envelope.clock();
+        assert_eq!(envelope.get_volume(), 15);
+        for _ in 0..30 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.get_volume(), 0);
+        envelope.clock();
+        envelope.clock();
+        assert_eq!(envelope.get_volume(), 15);
+
+        envelope.write(0x17);
+        assert_eq!(envelope.get_volume(), 7);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::types::Byte;
+
+const LENGTH_TABLE: [Byte; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+// Silences a channel after a number of half frames, unless halted. Disabling the
+// channel through $4015 clears it and keeps it from being loaded.
+#[derive(Default)]
+pub struct LengthCounter {
+    is_enabled: bool,
+    is_halted: bool,
+    count: Byte,
+}
+
+impl LengthCounter {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.is_enabled = enabled;
+        if !enabled {
+            self.count = 0;
+        }
+    }
+
+    pub fn set_halted(&mut self, halted: bool) {
+        self.is_halted = halted;
+    }
+
+    // Loads from the 5-bit index in bits 3-7 of a channel's fourth register.
+    pub fn load(&mut self, data: Byte) {
+        if self.is_enabled {
+            self.count = LENGTH_TABLE[(data >> 3) as usize];
+        }
+    }
+
+    // Clocked by the frame counter's half frames.
+    pub fn clock(&mut self) {
+        if !self.is_halted && self.count > 0 {
+            self.count -= 1;
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.count > 0
+    }
+}
@@ -0,0 +1,134 @@
+use super::envelope::Envelope;
+use super::length_counter::LengthCounter;
+use crate::types::{Address, Byte};
+
+// Timer periods in CPU cycles (NTSC).
+const PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+// The noise channel ($400C-$400F): a 15-bit LFSR whose feedback taps bit 1, or bit 6 in
+// the short mode, which gives a 93-step sequence.
+pub struct Noise {
+    is_short_mode: bool,
+    timer_period: u16,
+    timer: u16,
+    shift_register: u16,
+    envelope: Envelope,
+    length: LengthCounter,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise {
+            is_short_mode: false,
+            timer_period: PERIOD_TABLE[0],
+            timer: 0,
+            shift_register: 1,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+        }
+    }
+}
+
+impl Noise {
+    pub fn write(&mut self, register: Address, data: Byte) {
+        match register {
+            0 => {
+                self.length.set_halted(data & 0x20 == 0x20);
+                self.envelope.write(data);
+            }
+            1 => (),
+            2 => {
+                self.is_short_mode = data & 0x80 == 0x80;
+                self.timer_period = PERIOD_TABLE[(data & 0x0F) as usize];
+            }
+            _ => {
+                self.length.load(data);
+                self.envelope.restart();
+            }
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length.set_enabled(enabled);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.length.is_active()
+    }
+
+    // Clocked every CPU cycle, as the period table counts CPU cycles.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            self.clock_shift_register();
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_shift_register(&mut self) {
+        let tap = if self.is_short_mode { 6 } else { 1 };
+        let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 0x01;
+        self.shift_register = self.shift_register >> 1 | feedback << 14;
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length.clock();
+    }
+
+    pub fn output(&self) -> Byte {
+        if !self.length.is_active() || self.shift_register & 0x01 == 0x01 {
+            0
+        } else {
+            self.envelope.get_volume()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Steps until the shift register returns to its seed.
+    fn sequence_length(noise: &mut Noise) -> usize {
+        let seed = noise.shift_register;
+        (1..)
+            .find(|_| {
+                noise.clock_shift_register();
+                noise.shift_register == seed
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lfsr_modes() {
+        let mut noise = Noise::default();
+        assert_eq!(sequence_length(&mut noise), 32767);
+        noise.write(2, 0x80);
+        assert_eq!(sequence_length(&mut noise), 93);
+    }
+
+    #[test]
+    fn test_output_follows_bit_zero() {
+        let mut noise = Noise::default();
+        noise.set_enabled(true);
+        noise.write(0, 0x1A);
+        noise.write(3, 0x08);
+        // Seed 1 outputs silence; after one shift bit 0 is clear.
+        assert_eq!(noise.output(), 0);
+        noise.clock_timer();
+        assert_eq!(noise.shift_register, 0x4000);
+        assert_eq!(noise.output(), 10);
+        // Period index 0 shifts every 4 CPU cycles.
+        for _ in 0..4 {
+            noise.clock_timer();
+        }
+        assert_eq!(noise.shift_register, 0x2000);
+    }
+}
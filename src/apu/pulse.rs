@@ -0,0 +1,235 @@
+use super::envelope::Envelope;
+use super::length_counter::LengthCounter;
+use crate::types::{Address, Byte};
+
+const DUTY_TABLE: [[Byte; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+// A pulse channel ($4000-$4003 or $4004-$4007): an 8-step duty sequence clocked every
+// APU cycle, with envelope, length counter and sweep.
+pub struct Pulse {
+    // The MMC5 pulses have no sweep unit, so they ignore the second register and are
+    // never muted by it.
+    has_sweep: bool,
+    // Pulse 1 negates its sweep change with the ones' complement, so it subtracts one
+    // more than pulse 2.
+    is_ones_complement: bool,
+    duty: usize,
+    step: usize,
+    timer_period: u16,
+    timer: u16,
+    envelope: Envelope,
+    length: LengthCounter,
+    sweep_enabled: bool,
+    sweep_period: Byte,
+    sweep_negate: bool,
+    sweep_shift: Byte,
+    sweep_divider: Byte,
+    sweep_reload: bool,
+}
+
+impl Pulse {
+    pub fn new(is_ones_complement: bool) -> Self {
+        Pulse {
+            has_sweep: true,
+            is_ones_complement,
+            duty: 0,
+            step: 0,
+            timer_period: 0,
+            timer: 0,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_divider: 0,
+            sweep_reload: false,
+        }
+    }
+
+    pub fn without_sweep() -> Self {
+        Pulse {
+            has_sweep: false,
+            ..Pulse::new(false)
+        }
+    }
+
+    pub fn write(&mut self, register: Address, data: Byte) {
+        match register {
+            0 => {
+                self.duty = (data >> 6) as usize;
+                self.length.set_halted(data & 0x20 == 0x20);
+                self.envelope.write(data);
+            }
+            1 if self.has_sweep => {
+                self.sweep_enabled = data & 0x80 == 0x80;
+                self.sweep_period = (data >> 4) & 0x07;
+                self.sweep_negate = data & 0x08 == 0x08;
+                self.sweep_shift = data & 0x07;
+                self.sweep_reload = true;
+            }
+            1 => (),
+            2 => self.timer_period = (self.timer_period & 0x0700) | data as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0x07) << 8);
+                self.length.load(data);
+                self.step = 0;
+                self.envelope.restart();
+            }
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length.set_enabled(enabled);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.length.is_active()
+    }
+
+    // Clocked every other CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.step = (self.step + 1) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length.clock();
+        if self.sweep_divider == 0
+            && self.sweep_enabled
+            && self.sweep_shift > 0
+            && !self.is_sweep_muting()
+        {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if !self.sweep_negate {
+            self.timer_period + change
+        } else if self.is_ones_complement {
+            self.timer_period.saturating_sub(change + 1)
+        } else {
+            self.timer_period.saturating_sub(change)
+        }
+    }
+
+    // The sweep unit mutes the channel whenever its target overflows 11 bits, even
+    // with the sweep disabled, and so does a period below 8.
+    fn is_sweep_muting(&self) -> bool {
+        self.has_sweep && (self.timer_period < 8 || self.sweep_target() > 0x07FF)
+    }
+
+    pub fn output(&self) -> Byte {
+        if !self.length.is_active()
+            || self.is_sweep_muting()
+            || DUTY_TABLE[self.duty][self.step] == 0
+        {
+            0
+        } else {
+            self.envelope.get_volume()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pulse(is_ones_complement: bool) -> Pulse {
+        let mut pulse = Pulse::new(is_ones_complement);
+        pulse.set_enabled(true);
+        pulse.write(0, 0xBF);
+        pulse.write(2, 0x00);
+        pulse.write(3, 0x01);
+        pulse
+    }
+
+    #[test]
+    fn test_sweep_negation_differs_between_channels() {
+        // Period $100, shift 2, negated, divider period 0.
+        let mut first = pulse(true);
+        let mut second = pulse(false);
+        first.write(1, 0x8A);
+        second.write(1, 0x8A);
+        first.clock_half_frame();
+        second.clock_half_frame();
+        assert_eq!(first.timer_period, 0x100 - 0x40 - 1);
+        assert_eq!(second.timer_period, 0x100 - 0x40);
+    }
+
+    #[test]
+    fn test_sweep_muting() {
+        let mut pulse = pulse(false);
+        // Shift 0 still mutes once the target overflows, without changing the period.
+        pulse.write(2, 0xFF);
+        pulse.write(3, 0x07);
+        pulse.write(1, 0x00);
+        pulse.clock_half_frame();
+        assert_eq!(pulse.timer_period, 0x7FF);
+        assert!(pulse.is_sweep_muting());
+        pulse.write(1, 0x08);
+        assert!(!pulse.is_sweep_muting());
+
+        pulse.write(2, 0x07);
+        pulse.write(3, 0x00);
+        assert!(pulse.is_sweep_muting());
+    }
+
+    #[test]
+    fn test_without_sweep() {
+        let mut pulse = Pulse::without_sweep();
+        pulse.set_enabled(true);
+        pulse.write(0, 0xBF);
+        pulse.write(1, 0x8A);
+        pulse.write(2, 0x04);
+        pulse.write(3, 0x01);
+        pulse.clock_half_frame();
+        // The period stays, and a period below 8 still sounds.
+        assert_eq!(pulse.timer_period, 0x104);
+        pulse.write(3, 0x00);
+        assert!(!pulse.is_sweep_muting());
+    }
+
+    #[test]
+    fn test_duty_sequence_and_length() {
+        let mut pulse = pulse(false);
+        // 25% duty, constant volume 15, length index 0 (10 half frames).
+        pulse.write(0, 0x5F);
+        pulse.write(2, 0x08);
+        pulse.write(3, 0x00);
+        let mut outputs = Vec::new();
+        for _ in 0..8 {
+            outputs.push(pulse.output());
+            for _ in 0..9 {
+                pulse.clock_timer();
+            }
+        }
+        assert_eq!(outputs, [0, 15, 15, 0, 0, 0, 0, 0]);
+
+        for _ in 0..10 {
+            pulse.clock_half_frame();
+        }
+        assert!(!pulse.is_active());
+    }
+}
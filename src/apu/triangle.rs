@@ -0,0 +1,125 @@
+use super::length_counter::LengthCounter;
+use crate::types::{Address, Byte};
+
+const SEQUENCE: [Byte; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+// The triangle channel ($4008-$400B): a 32-step sequence clocked every CPU cycle, which
+// only advances while both the length counter and the linear counter are non-zero.
+#[derive(Default)]
+pub struct Triangle {
+    step: usize,
+    timer_period: u16,
+    timer: u16,
+    length: LengthCounter,
+    // The control flag halts the length counter and keeps the linear counter reloading.
+    is_control: bool,
+    linear_reload_value: Byte,
+    linear_counter: Byte,
+    linear_reload: bool,
+}
+
+impl Triangle {
+    pub fn write(&mut self, register: Address, data: Byte) {
+        match register {
+            0 => {
+                self.is_control = data & 0x80 == 0x80;
+                self.length.set_halted(self.is_control);
+                self.linear_reload_value = data & 0x7F;
+            }
+            1 => (),
+            2 => self.timer_period = (self.timer_period & 0x0700) | data as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0x07) << 8);
+                self.length.load(data);
+                self.linear_reload = true;
+            }
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length.set_enabled(enabled);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.length.is_active()
+    }
+
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length.is_active() && self.linear_counter > 0 {
+                self.step = (self.step + 1) & 0x1F;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.is_control {
+            self.linear_reload = false;
+        }
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length.clock();
+    }
+
+    // A stopped triangle holds its last level rather than dropping to 0.
+    pub fn output(&self) -> Byte {
+        SEQUENCE[self.step]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_linear_counter_gates_the_sequence() {
+        let mut triangle = Triangle::default();
+        triangle.set_enabled(true);
+        // Linear counter 2, period 0, so every timer clock is a step.
+        triangle.write(0, 0x02);
+        triangle.write(2, 0x00);
+        triangle.write(3, 0x08);
+        // Nothing moves until a quarter frame loads the linear counter.
+        triangle.clock_timer();
+        assert_eq!(triangle.output(), 15);
+        triangle.clock_quarter_frame();
+        triangle.clock_timer();
+        triangle.clock_timer();
+        assert_eq!(triangle.output(), 13);
+
+        triangle.clock_quarter_frame();
+        triangle.clock_quarter_frame();
+        triangle.clock_timer();
+        assert_eq!(triangle.output(), 13);
+        assert!(triangle.is_active());
+    }
+
+    #[test]
+    fn test_control_flag_keeps_reloading() {
+        let mut triangle = Triangle::default();
+        triangle.set_enabled(true);
+        triangle.write(0, 0x81);
+        triangle.write(3, 0x08);
+        for _ in 0..5 {
+            triangle.clock_quarter_frame();
+        }
+        assert_eq!(triangle.linear_counter, 1);
+        // Clearing the flag lets the next quarter frame drop the reload.
+        triangle.write(0, 0x01);
+        triangle.clock_quarter_frame();
+        triangle.clock_quarter_frame();
+        assert_eq!(triangle.linear_counter, 0);
+    }
+}
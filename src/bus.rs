@@ -1,5 +1,8 @@
+use crate::apu::Apu;
+use crate::dma::Dma;
+use crate::mapper::Mapper;
+use crate::ppu::Ppu;
 use crate::ram::Ram;
-use crate::rom::Rom;
 
 pub trait CpuBus {
     fn read_word(&mut self, addr: u16) -> u16;
@@ -7,19 +10,100 @@ pub trait CpuBus {
     fn read(&mut self, addr: u16) -> u8;
 
     fn write(&mut self, addr: u16, data: u8);
+
+    // Runs an OAM DMA requested during the last instruction and returns the cycles the
+    // CPU was halted for. `elapsed` is the length of that instruction.
+    fn run_dma(&mut self, _elapsed: u16) -> u16 {
+        0
+    }
+}
+
+// The CPU's most recent bus access. OAM DMA and DMC fetches do not count, as they
+// happen while the CPU is halted.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CpuAccess {
+    #[default]
+    None,
+    Read(u16),
+    Write(u16),
 }
 
 pub struct Bus<'a> {
-    program_rom: &'a Rom,
+    mapper: &'a mut dyn Mapper,
     work_ram: &'a mut Ram,
+    ppu: &'a mut Ppu,
+    apu: &'a mut Apu,
+    dma: &'a mut Dma,
+    last_access: &'a mut CpuAccess,
 }
 
 impl<'a> Bus<'a> {
-    pub fn new(program_rom: &'a Rom, work_ram: &'a mut Ram) -> Bus<'a> {
+    pub fn new(
+        mapper: &'a mut dyn Mapper,
+        work_ram: &'a mut Ram,
+        ppu: &'a mut Ppu,
+        apu: &'a mut Apu,
+        dma: &'a mut Dma,
+        last_access: &'a mut CpuAccess,
+    ) -> Bus<'a> {
         Self {
-            program_rom,
+            mapper,
             work_ram,
+            ppu,
+            apu,
+            dma,
+            last_access,
+        }
+    }
+
+    // Fetches a DMC sample byte for the APU and returns the cycles the CPU is halted
+    // for: 4, or 2 when an OAM DMA already holds it. A halt on the last cycle of an
+    // instruction interrupts its final read, which the CPU then performs again, so
+    // registers with read side effects see two reads. A final write is not repeated.
+    pub fn fetch_dmc_sample(&mut self, addr: u16, is_last_cycle: bool, is_in_oam_dma: bool) -> u16 {
+        if is_in_oam_dma {
+            let data = self.read_memory(addr);
+            self.apu.load_dmc_sample(data);
+            return 2;
+        }
+        if let CpuAccess::Read(last_read) = *self.last_access {
+            let is_register_read = match last_read {
+                0x2000..=0x3FFF => last_read & 0x0007 == 0x0007,
+                0x4016 | 0x4017 => true,
+                _ => false,
+            };
+            if is_last_cycle && is_register_read {
+                self.read_memory(last_read);
+            }
         }
+        let data = self.read_memory(addr);
+        self.apu.load_dmc_sample(data);
+        4
+    }
+
+    fn read_memory(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1FFF => self.work_ram.read(addr & 0x07FF),
+            0x2000..=0x3FFF => self.ppu.read_register(addr, &mut *self.mapper),
+            // 0x4016 => self.keypad.read(),
+            // 0x4017 => 0, // TODO: 2player
+            0x4000..=0x401F => self.apu.read(addr - 0x4000),
+            0x4020..=0xFFFF => self.mapper.cpu_read(addr),
+        }
+    }
+
+    fn write_memory(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.work_ram.write(addr & 0x07FF, data),
+            0x2000..=0x3FFF => {
+                self.mapper.on_ppu_register_write(addr, data);
+                self.ppu.write_register(addr, data, &mut *self.mapper);
+            }
+            0x4014 => self.dma.write(data),
+            // 0x4016 => self.keypad.write(data),
+            0x4000..=0x401F => self.apu.write(addr - 0x4000, data),
+            0x4020..=0xFFFF => self.mapper.cpu_write(addr, data),
+        };
     }
 }
 
@@ -27,50 +111,28 @@ impl<'a> CpuBus for Bus<'a> {
     fn read_word(&mut self, addr: u16) -> u16 {
         let lower = self.read(addr) as u16;
         let upper = self.read(addr + 1) as u16;
-        (upper << 8 | lower) as u16
+        upper << 8 | lower
     }
 
     fn read(&mut self, addr: u16) -> u8 {
-        match addr {
-            0x0000...0x1FFF => self.work_ram.read(addr & 0x07FF),
-            // 0x2000...0x3FFF => self.ppu.read(addr - 0x2000),
-            // 0x4016 => self.keypad.read(),
-            // 0x4017 => 0, // TODO: 2player
-            // 0x4000...0x401F => self.apu.read(addr - 0x4000),
-            0x6000...0x7FFF => {
-                println!(
-                    "Not implemented. This area is battery backup ram area 0x{:x}",
-                    addr
-                );
-                0
-            }
-            0x8000...0xBFFF => self.program_rom.read(addr - 0x8000),
-            0xC000...0xFFFF if self.program_rom.size() <= 0x4000 => {
-                self.program_rom.read(addr - 0xC000)
-            }
-            0xC000...0xFFFF => self.program_rom.read(addr - 0x8000),
-            _ => panic!("[READ] There is an illegal address (0x{:x}) access.", addr),
-        }
+        *self.last_access = CpuAccess::Read(addr);
+        self.read_memory(addr)
     }
 
     fn write(&mut self, addr: u16, data: u8) {
-        match addr {
-            0x0000...0x1FFF => self.work_ram.write(addr & 0x07FF, data),
-            // 0x2000...0x3FFF => self.ppu.write(addr - 0x2000, data),
-            // 0x4014 => self.dma.write(data),
-            // 0x4016 => self.keypad.write(data),
-            // 0x4000...0x401F => self.apu.write(addr - 0x4000, data),
-            0x6000...0x7FFF => {
-                println!(
-                    "Not implemented. This area is battery backup ram area 0x{:x}",
-                    addr
-                );
-            }
-            // 0x8000...0xFFFF => {
-            //     println!("switch bank to {}", data);
-            //     self.mmc.set_bank(data);
-            // }
-            _ => panic!("[WRITE] There is an illegal address (0x{:x}) access.", addr),
+        *self.last_access = CpuAccess::Write(addr);
+        self.write_memory(addr, data);
+    }
+
+    fn run_dma(&mut self, elapsed: u16) -> u16 {
+        let page = match self.dma.take_page() {
+            Some(page) => (page as u16) << 8,
+            None => return 0,
         };
+        for offset in 0..0x100 {
+            let data = self.read_memory(page | offset);
+            self.write_memory(0x2004, data);
+        }
+        self.dma.stall_cycles(elapsed)
     }
 }
@@ -1,40 +1,625 @@
+use std::error;
+use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::io::BufReader;
+
+const PROGRAM_ROM_UNIT: usize = 0x4000;
+const CHARACTER_ROM_UNIT: usize = 0x2000;
+const PROGRAM_RAM_UNIT: usize = 0x2000;
+const TRAINER_OFFSET: usize = 0x1000;
+const TRAINER_SIZE: usize = 0x0200;
+// The largest PRG ROM the plain NES 2.0 size form can express. Exponent-form sizes
+// beyond it are rejected rather than allocated.
+const MAX_ROM_SIZE: usize = 0x0EFF * PROGRAM_ROM_UNIT;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeaderFormat {
+    // iNES images from before flags 7 existed, or whose bytes 7-15 hold garbage
+    // such as a "DiskDude!" signature. Only flags 6 is trusted.
+    Archaic,
+    INes,
+    Nes2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem { ppu: u8, hardware: u8 },
+    Playchoice10,
+    Extended(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    SingleScreenLower,
+    SingleScreenUpper,
+    FourScreen,
+}
 
 pub struct Cartridge {
-    pub is_horizontal_mirror: bool,
+    pub mirroring: Mirroring,
+    pub has_battery: bool,
+    pub has_trainer: bool,
     pub character_rom: Vec<u8>,
+    pub character_ram: Vec<u8>,
     pub program_rom: Vec<u8>,
-    pub mapper: u8,
+    // Battery-backed NVRAM comes first, followed by volatile PRG RAM.
+    pub program_ram: Vec<u8>,
+    pub header_format: HeaderFormat,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub program_ram_size: usize,
+    pub program_nvram_size: usize,
+    pub character_ram_size: usize,
+    pub character_nvram_size: usize,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    pub misc_roms: u8,
+    pub expansion_device: u8,
+}
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    Io(io::Error),
+    BadMagic([u8; 4]),
+    TruncatedHeader,
+    TruncatedTrainer,
+    TruncatedProgramRom { expected: usize },
+    TruncatedCharacterRom { expected: usize },
+    RomSizeTooLarge,
+    UnsupportedMapper(u16),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::Io(e) => write!(f, "failed to read the ROM image: {}", e),
+            CartridgeError::BadMagic(magic) => {
+                write!(f, "not an iNES image (magic bytes {:02X?})", magic)
+            }
+            CartridgeError::TruncatedHeader => write!(f, "the iNES header is truncated"),
+            CartridgeError::TruncatedTrainer => write!(f, "the 512-byte trainer is truncated"),
+            CartridgeError::TruncatedProgramRom { expected } => {
+                write!(
+                    f,
+                    "PRG ROM is shorter than the {} bytes in the header",
+                    expected
+                )
+            }
+            CartridgeError::TruncatedCharacterRom { expected } => {
+                write!(
+                    f,
+                    "CHR ROM is shorter than the {} bytes in the header",
+                    expected
+                )
+            }
+            CartridgeError::RomSizeTooLarge => {
+                write!(f, "a ROM size in the header exceeds {} bytes", MAX_ROM_SIZE)
+            }
+            CartridgeError::UnsupportedMapper(mapper) => {
+                write!(f, "mapper {} is not supported", mapper)
+            }
+        }
+    }
+}
+
+impl error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CartridgeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CartridgeError {
+    fn from(e: io::Error) -> Self {
+        CartridgeError::Io(e)
+    }
 }
 
 impl Cartridge {
-    pub fn new(path: &str) -> io::Result<Self> {
-        let mut f = File::open(path)?;
-        let mut header = [0; 16];
-        f.read_exact(&mut header)?;
-        assert_eq!(&header[0..4], b"NES\x1A");
+    pub fn new(path: &str) -> Result<Self, CartridgeError> {
+        let f = File::open(path)?;
+        Cartridge::from_reader(BufReader::new(f))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CartridgeError> {
+        Cartridge::from_reader(bytes)
+    }
 
-        let program_rom_size = header[4] as usize;
-        let character_rom_size = header[5] as usize;
+    pub fn from_reader<R: Read>(mut f: R) -> Result<Self, CartridgeError> {
+        let mut header = [0; 16];
+        read_section(&mut f, &mut header, CartridgeError::TruncatedHeader)?;
+        if &header[0..4] != b"NES\x1A" {
+            return Err(CartridgeError::BadMagic([
+                header[0], header[1], header[2], header[3],
+            ]));
+        }
 
         let flag_6 = header[6];
-        let is_horizontal_mirror = (flag_6 & 1) == 0;
+        let mirroring = if flag_6 & 0x08 == 0x08 {
+            Mirroring::FourScreen
+        } else if flag_6 & 0x01 == 0x01 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let has_battery = flag_6 & 0x02 == 0x02;
+        let has_trainer = flag_6 & 0x04 == 0x04;
+
+        let header_format = match header[7] & 0x0C {
+            0x08 => HeaderFormat::Nes2,
+            0x00 if header[12..16].iter().all(|&b| b == 0) => HeaderFormat::INes,
+            _ => HeaderFormat::Archaic,
+        };
+        let (mut mapper, mut submapper) = ((flag_6 >> 4) as u16, 0);
+        if header_format != HeaderFormat::Archaic {
+            mapper |= (header[7] & 0xF0) as u16;
+        }
+        if header_format == HeaderFormat::Nes2 {
+            mapper |= ((header[8] & 0x0F) as u16) << 8;
+            submapper = header[8] >> 4;
+        }
 
-        let flag_7 = header[7];
-        let mapper = (flag_6 >> 4) | ((flag_7 >> 4) << 4);
+        let (program_rom_size, character_rom_size) = if header_format == HeaderFormat::Nes2 {
+            let checked = |size: Option<usize>| {
+                size.filter(|&size| size <= MAX_ROM_SIZE)
+                    .ok_or(CartridgeError::RomSizeTooLarge)
+            };
+            (
+                checked(nes2_rom_size(header[4], header[9] & 0x0F, PROGRAM_ROM_UNIT))?,
+                checked(nes2_rom_size(header[5], header[9] >> 4, CHARACTER_ROM_UNIT))?,
+            )
+        } else {
+            (
+                PROGRAM_ROM_UNIT * header[4] as usize,
+                CHARACTER_ROM_UNIT * header[5] as usize,
+            )
+        };
 
-        let mut program_rom = vec![0; 0x4000 * program_rom_size];
-        let mut character_rom = vec![0; 0x2000 * character_rom_size];
+        let mut program_ram_size;
+        let mut program_nvram_size = 0;
+        let mut character_ram_size = 0;
+        let mut character_nvram_size = 0;
+        let mut timing = Timing::Ntsc;
+        let mut console_type = ConsoleType::Nes;
+        let mut misc_roms = 0;
+        let mut expansion_device = 0;
+        match header_format {
+            HeaderFormat::Nes2 => {
+                program_ram_size = shift_size(header[10] & 0x0F);
+                program_nvram_size = shift_size(header[10] >> 4);
+                character_ram_size = shift_size(header[11] & 0x0F);
+                character_nvram_size = shift_size(header[11] >> 4);
+                timing = match header[12] & 0x03 {
+                    0 => Timing::Ntsc,
+                    1 => Timing::Pal,
+                    2 => Timing::MultiRegion,
+                    _ => Timing::Dendy,
+                };
+                console_type = match header[7] & 0x03 {
+                    0 => ConsoleType::Nes,
+                    1 => ConsoleType::VsSystem {
+                        ppu: header[13] & 0x0F,
+                        hardware: header[13] >> 4,
+                    },
+                    2 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Extended(header[13] & 0x0F),
+                };
+                misc_roms = header[14] & 0x03;
+                expansion_device = header[15] & 0x3F;
+            }
+            HeaderFormat::INes => {
+                // A zero PRG RAM size means 8 KiB for compatibility with older images.
+                program_ram_size = PROGRAM_RAM_UNIT * header[8].max(1) as usize;
+                if header[9] & 0x01 == 0x01 {
+                    timing = Timing::Pal;
+                }
+                console_type = match header[7] & 0x03 {
+                    0 => ConsoleType::Nes,
+                    1 => ConsoleType::VsSystem {
+                        ppu: 0,
+                        hardware: 0,
+                    },
+                    _ => ConsoleType::Playchoice10,
+                };
+            }
+            HeaderFormat::Archaic => {
+                program_ram_size = PROGRAM_RAM_UNIT;
+            }
+        }
+        if header_format != HeaderFormat::Nes2 {
+            if has_battery {
+                program_nvram_size = program_ram_size;
+                program_ram_size = 0;
+            }
+            // Boards without CHR ROM carry 8 KiB of CHR RAM instead.
+            if character_rom_size == 0 {
+                character_ram_size = CHARACTER_ROM_UNIT;
+            }
+        }
 
-        f.read_exact(&mut program_rom[..])?;
-        f.read_exact(&mut character_rom[..])?;
+        // The trainer is mapped at $7000-$71FF, so PRG RAM must at least cover it.
+        let mut program_ram = vec![0; program_nvram_size + program_ram_size];
+        if has_trainer {
+            if program_ram.len() < PROGRAM_RAM_UNIT {
+                program_ram.resize(PROGRAM_RAM_UNIT, 0);
+            }
+            read_section(
+                &mut f,
+                &mut program_ram[TRAINER_OFFSET..TRAINER_OFFSET + TRAINER_SIZE],
+                CartridgeError::TruncatedTrainer,
+            )?;
+        }
+
+        let program_rom = read_rom(
+            &mut f,
+            program_rom_size,
+            CartridgeError::TruncatedProgramRom {
+                expected: program_rom_size,
+            },
+        )?;
+        let character_rom = read_rom(
+            &mut f,
+            character_rom_size,
+            CartridgeError::TruncatedCharacterRom {
+                expected: character_rom_size,
+            },
+        )?;
+        let character_ram = vec![0; character_nvram_size + character_ram_size];
         Ok(Cartridge {
-            is_horizontal_mirror,
+            mirroring,
+            has_battery,
+            has_trainer,
             program_rom,
+            program_ram,
             character_rom,
+            character_ram,
+            header_format,
             mapper,
+            submapper,
+            program_ram_size,
+            program_nvram_size,
+            character_ram_size,
+            character_nvram_size,
+            timing,
+            console_type,
+            misc_roms,
+            expansion_device,
         })
     }
+
+    pub fn read_program_rom(&self, addr: usize) -> u8 {
+        if self.program_rom.is_empty() {
+            return 0;
+        }
+        self.program_rom[addr % self.program_rom.len()]
+    }
+
+    // Boards without PRG RAM leave $6000-$7FFF as open bus, approximated here by 0.
+    pub fn read_program_ram(&self, addr: usize) -> u8 {
+        if self.program_ram.is_empty() {
+            return 0;
+        }
+        self.program_ram[addr % self.program_ram.len()]
+    }
+
+    pub fn write_program_ram(&mut self, addr: usize, data: u8) {
+        if self.program_ram.is_empty() {
+            return;
+        }
+        let len = self.program_ram.len();
+        self.program_ram[addr % len] = data;
+    }
+
+    // Pattern data comes from CHR ROM, or from CHR RAM on boards that have no ROM.
+    pub fn read_character(&self, addr: usize) -> u8 {
+        let memory = if self.character_rom.is_empty() {
+            &self.character_ram
+        } else {
+            &self.character_rom
+        };
+        if memory.is_empty() {
+            return 0;
+        }
+        memory[addr % memory.len()]
+    }
+
+    pub fn write_character(&mut self, addr: usize, data: u8) {
+        if !self.character_rom.is_empty() || self.character_ram.is_empty() {
+            return;
+        }
+        let len = self.character_ram.len();
+        self.character_ram[addr % len] = data;
+    }
+
+    // The battery-backed part of PRG RAM, i.e. the contents of a .sav file.
+    pub fn battery_ram(&self) -> &[u8] {
+        &self.program_ram[..self.program_nvram_size]
+    }
+
+    pub fn load_battery_ram<R: Read>(&mut self, mut r: R) -> io::Result<()> {
+        let mut buf = vec![];
+        r.read_to_end(&mut buf)?;
+        let len = buf.len().min(self.program_nvram_size);
+        self.program_ram[..len].copy_from_slice(&buf[..len]);
+        Ok(())
+    }
+
+    pub fn save_battery_ram<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(self.battery_ram())
+    }
+
+    pub fn load_save_file(&mut self, path: &str) -> io::Result<()> {
+        let f = File::open(path)?;
+        self.load_battery_ram(BufReader::new(f))
+    }
+
+    pub fn write_save_file(&self, path: &str) -> io::Result<()> {
+        let f = File::create(path)?;
+        self.save_battery_ram(f)
+    }
+}
+
+// NES 2.0 ROM sizes use an exponent-multiplier form (2^E * (MM * 2 + 1) bytes)
+// when the most significant nibble is 0xF. Sizes that overflow are `None`.
+fn nes2_rom_size(lsb: u8, msb: u8, unit: usize) -> Option<usize> {
+    if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        1usize
+            .checked_shl(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+    } else {
+        unit.checked_mul((msb as usize) << 8 | lsb as usize)
+    }
+}
+
+// NES 2.0 RAM sizes are stored as a shift count: 64 << n bytes, or none when n is 0.
+fn shift_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
+
+fn read_section<R: Read>(
+    r: &mut R,
+    buf: &mut [u8],
+    truncated: CartridgeError,
+) -> Result<(), CartridgeError> {
+    match r.read_exact(buf) {
+        Ok(()) => Ok(()),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(truncated),
+        Err(e) => Err(CartridgeError::Io(e)),
+    }
+}
+
+// Reads exactly `size` bytes without consuming anything after them. The buffer grows
+// with the data actually read, so a short image never allocates the full size.
+fn read_rom<R: Read>(
+    r: &mut R,
+    size: usize,
+    truncated: CartridgeError,
+) -> Result<Vec<u8>, CartridgeError> {
+    let mut rom = Vec::new();
+    r.by_ref().take(size as u64).read_to_end(&mut rom)?;
+    if rom.len() < size {
+        return Err(truncated);
+    }
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(program_rom_size: u8, character_rom_size: u8, flag_6: u8, flag_7: u8) -> Vec<u8> {
+        let mut image = b"NES\x1A".to_vec();
+        image.extend_from_slice(&[program_rom_size, character_rom_size, flag_6, flag_7]);
+        image.extend_from_slice(&[0; 8]);
+        image
+    }
+
+    #[test]
+    fn test_load_nestest() {
+        let cartridge = Cartridge::new("./roms/nestest.nes").unwrap();
+        assert_eq!(cartridge.program_rom.len(), 0x4000);
+        assert_eq!(cartridge.character_rom.len(), 0x2000);
+        assert_eq!(cartridge.mapper, 0);
+    }
+
+    #[test]
+    fn test_load_from_bytes_and_reader() {
+        let image = include_bytes!("../roms/nestest.nes");
+        let from_bytes = Cartridge::from_bytes(image).unwrap();
+        let from_reader = Cartridge::from_reader(io::Cursor::new(&image[..])).unwrap();
+        let from_path = Cartridge::new("./roms/nestest.nes").unwrap();
+        assert_eq!(from_bytes.program_rom, from_path.program_rom);
+        assert_eq!(from_reader.character_rom, from_path.character_rom);
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let mut image = header(1, 1, 0, 0);
+        image[3] = 0x00;
+        match Cartridge::from_bytes(&image) {
+            Err(CartridgeError::BadMagic(magic)) => assert_eq!(&magic, b"NES\x00"),
+            _ => panic!("expected BadMagic"),
+        }
+    }
+
+    #[test]
+    fn test_truncated_program_rom() {
+        let mut image = header(2, 1, 0, 0);
+        image.extend_from_slice(&[0; 0x4000]);
+        match Cartridge::from_bytes(&image) {
+            Err(CartridgeError::TruncatedProgramRom { expected }) => assert_eq!(expected, 0x8000),
+            _ => panic!("expected TruncatedProgramRom"),
+        }
+    }
+
+    #[test]
+    fn test_truncated_character_rom() {
+        let mut image = header(1, 1, 0, 0);
+        image.extend_from_slice(&[0; 0x4000 + 0x100]);
+        match Cartridge::from_bytes(&image) {
+            Err(CartridgeError::TruncatedCharacterRom { expected }) => {
+                assert_eq!(expected, 0x2000)
+            }
+            _ => panic!("expected TruncatedCharacterRom"),
+        }
+    }
+
+    #[test]
+    fn test_nes2_rom_size_too_large() {
+        // 2^63 * 7 bytes of PRG ROM overflows.
+        let mut image = header(0xFF, 0x00, 0x00, 0x08);
+        image[9] = 0x0F;
+        image.extend_from_slice(&[0; 0x4000]);
+        assert!(matches!(
+            Cartridge::from_bytes(&image),
+            Err(CartridgeError::RomSizeTooLarge)
+        ));
+        // 2^26 * 1 bytes fits in a usize but is still larger than any image.
+        image[4] = 0x68;
+        assert!(matches!(
+            Cartridge::from_bytes(&image),
+            Err(CartridgeError::RomSizeTooLarge)
+        ));
+        // 2^20 * 3 bytes is plausible, so a short image is only truncated.
+        image[4] = 0x51;
+        match Cartridge::from_bytes(&image) {
+            Err(CartridgeError::TruncatedProgramRom { expected }) => assert_eq!(expected, 3 << 20),
+            _ => panic!("expected TruncatedProgramRom"),
+        }
+    }
+
+    #[test]
+    fn test_trailing_data_is_left_in_the_reader() {
+        let mut image = header(1, 1, 0, 0);
+        image.extend_from_slice(&[0; 0x6000]);
+        image.extend_from_slice(&[0xA5; 4]);
+        let mut reader = &image[..];
+        Cartridge::from_reader(&mut reader).unwrap();
+        assert_eq!(reader, &[0xA5; 4]);
+    }
+
+    #[test]
+    fn test_nes2_mapper() {
+        let mut image = header(0x02, 0x01, 0x10, 0x49);
+        image[8] = 0x31;
+        image.extend_from_slice(&[0; 0xA000]);
+        let cartridge = Cartridge::from_bytes(&image).unwrap();
+        assert_eq!(cartridge.mapper, 0x141);
+        assert_eq!(cartridge.submapper, 3);
+    }
+
+    #[test]
+    fn test_nes2_sizes() {
+        let mut image = header(0x02, 0x05, 0x00, 0x09);
+        image[9] = 0xF0;
+        image[10] = 0x70;
+        image[11] = 0x07;
+        image[12] = 0x01;
+        image[13] = 0x21;
+        image[14] = 0x01;
+        image[15] = 0x2A;
+        image.extend_from_slice(&[0; 0x8000]);
+        // CHR size 0x05 with an 0xF nibble: 2^1 * (1 * 2 + 1) = 6 bytes.
+        image.extend_from_slice(&[0; 6]);
+        let cartridge = Cartridge::from_bytes(&image).unwrap();
+        assert_eq!(cartridge.header_format, HeaderFormat::Nes2);
+        assert_eq!(cartridge.program_rom.len(), 0x8000);
+        assert_eq!(cartridge.character_rom.len(), 6);
+        assert_eq!(cartridge.program_ram_size, 0);
+        assert_eq!(cartridge.program_nvram_size, 64 << 7);
+        assert_eq!(cartridge.character_ram_size, 64 << 7);
+        assert_eq!(cartridge.timing, Timing::Pal);
+        assert_eq!(
+            cartridge.console_type,
+            ConsoleType::VsSystem {
+                ppu: 0x01,
+                hardware: 0x02
+            }
+        );
+        assert_eq!(cartridge.misc_roms, 1);
+        assert_eq!(cartridge.expansion_device, 0x2A);
+    }
+
+    #[test]
+    fn test_diskdude_header_is_archaic() {
+        let mut image = header(1, 1, 0x00, 0x44);
+        image[7..16].copy_from_slice(b"DiskDude!");
+        image.extend_from_slice(&[0; 0x6000]);
+        let cartridge = Cartridge::from_bytes(&image).unwrap();
+        assert_eq!(cartridge.header_format, HeaderFormat::Archaic);
+        assert_eq!(cartridge.mapper, 0);
+        assert_eq!(cartridge.program_ram_size, 0x2000);
+    }
+
+    #[test]
+    fn test_trainer_is_loaded_at_0x7000() {
+        let mut image = header(1, 1, 0x06, 0x00);
+        image.extend((0..0x200).map(|i| i as u8));
+        image.extend_from_slice(&[0xEA; 0x4000]);
+        image.extend_from_slice(&[0; 0x2000]);
+        let cartridge = Cartridge::from_bytes(&image).unwrap();
+        assert!(cartridge.has_trainer);
+        assert!(cartridge.has_battery);
+        assert_eq!(cartridge.program_nvram_size, 0x2000);
+        assert_eq!(cartridge.program_ram[0x1000], 0x00);
+        assert_eq!(cartridge.program_ram[0x11FF], 0xFF);
+        assert!(cartridge.program_rom.iter().all(|&b| b == 0xEA));
+    }
+
+    #[test]
+    fn test_battery_ram_round_trip() {
+        let mut image = header(1, 1, 0x02, 0x00);
+        image.extend_from_slice(&[0; 0x6000]);
+        let mut cartridge = Cartridge::from_bytes(&image).unwrap();
+        cartridge.write_program_ram(0x0010, 0xA5);
+        let mut save = vec![];
+        cartridge.save_battery_ram(&mut save).unwrap();
+        assert_eq!(save.len(), 0x2000);
+        assert_eq!(save[0x10], 0xA5);
+
+        let mut restored = Cartridge::from_bytes(&image).unwrap();
+        restored.load_battery_ram(&save[..]).unwrap();
+        assert_eq!(restored.read_program_ram(0x0010), 0xA5);
+    }
+
+    #[test]
+    fn test_truncated_trainer() {
+        let mut image = header(1, 1, 0x04, 0x00);
+        image.extend_from_slice(&[0; 0x100]);
+        match Cartridge::from_bytes(&image) {
+            Err(CartridgeError::TruncatedTrainer) => (),
+            _ => panic!("expected TruncatedTrainer"),
+        }
+    }
+
+    #[test]
+    fn test_mirroring_and_character_ram() {
+        let mut image = header(1, 0, 0x09, 0x00);
+        image.extend_from_slice(&[0; 0x4000]);
+        let cartridge = Cartridge::from_bytes(&image).unwrap();
+        assert_eq!(cartridge.mirroring, Mirroring::FourScreen);
+        assert!(cartridge.character_rom.is_empty());
+        assert_eq!(cartridge.character_ram.len(), 0x2000);
+    }
 }
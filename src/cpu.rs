@@ -10,25 +10,37 @@ use self::opecode::*;
 
 use crate::bus::CpuBus;
 use crate::cpu_registers::CpuRegisters;
-use crate::types::Byte;
+use crate::types::Word;
 
 pub fn reset<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     let pc = bus.read_word(0xFFFC);
-    registers.set_PC(pc);
+    registers.set_PC(pc).set_halted(false);
 }
 
 pub fn run<T: CpuRegisters + Debug, U: CpuBus>(
     registers: &mut T,
     bus: &mut U,
     nmi: &mut bool,
-) -> Byte {
+    irq: bool,
+) -> Word {
+    // A jammed CPU no longer fetches or services interrupts until it is reset.
+    if registers.get_halted() {
+        return 1;
+    }
     if *nmi {
         process_nmi(registers, bus);
         *nmi = false;
+        return 7;
+    }
+    // IRQ is level-triggered, so the source keeps asserting it until acknowledged.
+    if irq && !registers.get_interrupt() {
+        process_irq(registers, bus);
+        return 7;
     }
     let opecode = fetch(registers, bus);
     let code = get_opecode(opecode);
-    let operand = fetch_operand(&code, registers, bus);
+    let (operand, page_cross_cycle) = fetch_operand(&code, registers, bus);
+    let mut cycle = code.cycle + page_cross_cycle;
     match code.name {
         Instruction::LDA if code.mode == Addressing::Immediate => lda_imm(operand, registers),
         Instruction::LDA => lda(operand, registers, bus),
@@ -85,30 +97,126 @@ pub fn run<T: CpuRegisters + Debug, U: CpuBus>(
         Instruction::CLV => clv(registers),
         Instruction::SEC => sec(registers),
         Instruction::SEI => sei(registers),
-        Instruction::NOP => (),
+        Instruction::NOP if code.mode == Addressing::Implied => (),
+        Instruction::NOP if code.mode == Addressing::Immediate => (),
+        Instruction::NOP => nop(operand, bus),
         Instruction::BRK => brk(registers, bus),
         Instruction::JSR => jsr(operand, registers, bus),
         Instruction::JMP => jmp(operand, registers),
         Instruction::RTI => rti(registers, bus),
         Instruction::RTS => rts(registers, bus),
-        Instruction::BCC => bcc(operand, registers),
-        Instruction::BPL => bpl(operand, registers),
-        Instruction::BMI => bmi(operand, registers),
-        Instruction::BVC => bvc(operand, registers),
-        Instruction::BVS => bvs(operand, registers),
-        Instruction::BCS => bcs(operand, registers),
-        Instruction::BNE => bne(operand, registers),
-        Instruction::BEQ => beq(operand, registers),
+        Instruction::BCC => cycle += bcc(operand, registers),
+        Instruction::BPL => cycle += bpl(operand, registers),
+        Instruction::BMI => cycle += bmi(operand, registers),
+        Instruction::BVC => cycle += bvc(operand, registers),
+        Instruction::BVS => cycle += bvs(operand, registers),
+        Instruction::BCS => cycle += bcs(operand, registers),
+        Instruction::BNE => cycle += bne(operand, registers),
+        Instruction::BEQ => cycle += beq(operand, registers),
         Instruction::SED => sed(registers),
         Instruction::CLD => cld(registers),
-        Instruction::LAX => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::SAX => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::DCP => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::ISB => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::SLO => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::RLA => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::SRE => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::RRA => println!("{}", "TODO:Undocumented instruction"),
+        Instruction::LAX if code.mode == Addressing::Immediate => lax_imm(operand, registers),
+        Instruction::LAX => lax(operand, registers, bus),
+        Instruction::SAX => sax(operand, registers, bus),
+        Instruction::DCP => dcp(operand, registers, bus),
+        Instruction::ISB => isb(operand, registers, bus),
+        Instruction::SLO => slo(operand, registers, bus),
+        Instruction::RLA => rla(operand, registers, bus),
+        Instruction::SRE => sre(operand, registers, bus),
+        Instruction::RRA => rra(operand, registers, bus),
+        Instruction::ANC => anc(operand, registers),
+        Instruction::ALR => alr(operand, registers),
+        Instruction::ARR => arr(operand, registers),
+        Instruction::XAA => xaa(operand, registers),
+        Instruction::AXS => axs(operand, registers),
+        Instruction::LAS => las(operand, registers, bus),
+        Instruction::AHX => ahx(operand, registers, bus),
+        Instruction::TAS => tas(operand, registers, bus),
+        Instruction::SHY => shy(operand, registers, bus),
+        Instruction::SHX => shx(operand, registers, bus),
+        Instruction::KIL => kil(registers),
+    }
+    let cycle = cycle as Word;
+    cycle + bus.run_dma(cycle)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::cpu_registers::Registers;
+    use crate::types::{Address, Byte};
+
+    struct NestestBus {
+        mem: Vec<Byte>,
+    }
+
+    impl NestestBus {
+        fn new(program_rom: &[Byte]) -> Self {
+            let mut mem = vec![0; 0x10000];
+            mem[0x8000..0xC000].copy_from_slice(&program_rom[..0x4000]);
+            mem[0xC000..].copy_from_slice(&program_rom[program_rom.len() - 0x4000..]);
+            NestestBus { mem }
+        }
+    }
+
+    impl CpuBus for NestestBus {
+        fn read(&mut self, addr: Address) -> Byte {
+            match addr {
+                0x0000..=0x1FFF => self.mem[(addr & 0x07FF) as usize],
+                // nestest only touches the APU registers, which read back as open bus here.
+                0x4000..=0x401F => 0xFF,
+                _ => self.mem[addr as usize],
+            }
+        }
+        fn read_word(&mut self, addr: Address) -> Word {
+            let lower = self.read(addr) as Word;
+            let upper = self.read(addr.wrapping_add(1)) as Word;
+            upper << 8 | lower
+        }
+        fn write(&mut self, addr: Address, data: Byte) {
+            if let 0x0000..=0x1FFF = addr {
+                self.mem[(addr & 0x07FF) as usize] = data;
+            }
+        }
+    }
+
+    fn parse_hex(line: &str, start: usize, end: usize) -> Word {
+        Word::from_str_radix(&line[start..end], 16).unwrap()
+    }
+
+    #[test]
+    fn test_nestest_cycles() {
+        let cartridge = Cartridge::new("./roms/nestest.nes").unwrap();
+        let mut bus = NestestBus::new(&cartridge.program_rom);
+        let mut registers = Registers::new();
+        registers.set_PC(0xC000).set_P(0x24);
+        let mut nmi = false;
+
+        let log = include_str!("../assets/nestest.log");
+        let (mut scanline, mut dot) = (241, 0);
+        for (i, line) in log.lines().enumerate() {
+            assert_eq!(parse_hex(line, 0, 4), registers.get_PC(), "line {}", i + 1);
+            assert_eq!(parse_hex(line, 50, 52) as Byte, registers.get_A());
+            assert_eq!(parse_hex(line, 55, 57) as Byte, registers.get_X());
+            assert_eq!(parse_hex(line, 60, 62) as Byte, registers.get_Y());
+            assert_eq!(parse_hex(line, 65, 67) as Byte, registers.get_P());
+            assert_eq!(parse_hex(line, 71, 73) as Byte, registers.get_SP());
+            let expected_dot: i32 = line[78..81].trim().parse().unwrap();
+            let expected_scanline: i32 = line[85..].trim().parse().unwrap();
+            assert_eq!(
+                (expected_scanline, expected_dot),
+                (scanline, dot),
+                "line {}",
+                i + 1
+            );
+
+            let cycle = run(&mut registers, &mut bus, &mut nmi, false);
+            dot += cycle as i32 * 3;
+            while dot >= 341 {
+                dot -= 341;
+                scanline = if scanline == 260 { -1 } else { scanline + 1 };
+            }
+        }
     }
-    code.cycle
 }
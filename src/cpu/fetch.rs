@@ -9,25 +9,31 @@ pub fn fetch<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Byte
     code
 }
 
+// Returns the operand along with the extra cycle spent when an indexed read crosses a page.
 pub fn fetch_operand<T: CpuRegisters, U: CpuBus>(
     code: &Opecode,
     registers: &mut T,
     bus: &mut U,
-) -> Word {
-    match code.mode {
-        Addressing::Accumulator => 0x0000,
-        Addressing::Implied => 0x0000,
-        Addressing::Immediate => fetch(registers, bus) as Word,
-        Addressing::Relative => fetch_relative(registers, bus),
-        Addressing::ZeroPage => fetch(registers, bus) as Word,
-        Addressing::ZeroPageX => fetch_zeropage_x(registers, bus),
-        Addressing::ZeroPageY => fetch_zeropage_y(registers, bus),
-        Addressing::Absolute => fetch_word(registers, bus),
+) -> (Word, Byte) {
+    let (operand, page_crossed) = match code.mode {
+        Addressing::Accumulator => (0x0000, false),
+        Addressing::Implied => (0x0000, false),
+        Addressing::Immediate => (fetch(registers, bus) as Word, false),
+        Addressing::Relative => (fetch_relative(registers, bus), false),
+        Addressing::ZeroPage => (fetch(registers, bus) as Word, false),
+        Addressing::ZeroPageX => (fetch_zeropage_x(registers, bus), false),
+        Addressing::ZeroPageY => (fetch_zeropage_y(registers, bus), false),
+        Addressing::Absolute => (fetch_word(registers, bus), false),
         Addressing::AbsoluteX => fetch_absolute_x(registers, bus),
         Addressing::AbsoluteY => fetch_absolute_y(registers, bus),
-        Addressing::PreIndexedIndirect => fetch_pre_indexed_indirect(registers, bus),
+        Addressing::PreIndexedIndirect => (fetch_pre_indexed_indirect(registers, bus), false),
         Addressing::PostIndexedIndirect => fetch_post_indexed_indirect(registers, bus),
-        Addressing::IndirectAbsolute => fetch_indirect_absolute(registers, bus),
+        Addressing::IndirectAbsolute => (fetch_indirect_absolute(registers, bus), false),
+    };
+    if page_crossed && has_page_cross_penalty(&code.name) {
+        (operand, 1)
+    } else {
+        (operand, 0)
     }
 }
 
@@ -36,60 +42,92 @@ pub fn fetch_word<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) ->
     registers.inc_PC();
     let upper = bus.read(registers.get_PC()) as Word;
     registers.inc_PC();
-    (upper << 8 | lower) as Word
+    upper << 8 | lower
 }
 
 pub fn fetch_relative<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
-    let base = fetch(registers, bus) as Word;
-    if base < 0x80 {
-        base + registers.get_PC()
-    } else {
-        base + registers.get_PC() - 256
-    }
+    let offset = fetch(registers, bus) as i8;
+    registers.get_PC().wrapping_add(offset as Word)
 }
 
 pub fn fetch_zeropage_x<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
-    let addr = fetch(registers, bus) as Word;
-    (addr + registers.get_X() as Word) & 0xFF as Word
+    let addr = fetch(registers, bus);
+    addr.wrapping_add(registers.get_X()) as Word
 }
 
 pub fn fetch_zeropage_y<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
-    let addr = fetch(registers, bus) as Word;
-    (addr + registers.get_Y() as Word) & 0xFF as Word
+    let addr = fetch(registers, bus);
+    addr.wrapping_add(registers.get_Y()) as Word
 }
 
-pub fn fetch_absolute_x<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
-    let addr = fetch_word(registers, bus);
-    (addr + registers.get_X() as Word) & 0xFFFF
+pub fn fetch_absolute_x<T: CpuRegisters, U: CpuBus>(
+    registers: &mut T,
+    bus: &mut U,
+) -> (Word, bool) {
+    let base = fetch_word(registers, bus);
+    let addr = base.wrapping_add(registers.get_X() as Word);
+    (addr, is_page_crossed(base, addr))
 }
 
-pub fn fetch_absolute_y<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
-    let addr = fetch_word(registers, bus);
-    (addr + registers.get_Y() as Word) & 0xFFFF
+pub fn fetch_absolute_y<T: CpuRegisters, U: CpuBus>(
+    registers: &mut T,
+    bus: &mut U,
+) -> (Word, bool) {
+    let base = fetch_word(registers, bus);
+    let addr = base.wrapping_add(registers.get_Y() as Word);
+    (addr, is_page_crossed(base, addr))
 }
 
 pub fn fetch_pre_indexed_indirect<T: CpuRegisters, U: CpuBus>(
     registers: &mut T,
     bus: &mut U,
 ) -> Word {
-    let addr = ((fetch(registers, bus) + registers.get_X()) & 0xFF) as Address;
-    let addr =
-        (bus.read(addr) as Address) + ((bus.read((addr + 1) as Address & 0xFF) as Address) << 8);
-    addr & 0xFFFF
+    let addr = fetch(registers, bus).wrapping_add(registers.get_X());
+    let lower = bus.read(addr as Address) as Address;
+    let upper = bus.read(addr.wrapping_add(1) as Address) as Address;
+    upper << 8 | lower
 }
 
 pub fn fetch_post_indexed_indirect<T: CpuRegisters, U: CpuBus>(
     registers: &mut T,
     bus: &mut U,
-) -> Word {
-    let addr = fetch(registers, bus) as Address;
-    let base_addr = (bus.read(addr) as usize) + ((bus.read((addr + 1) & 0x00FF) as usize) * 0x100);
-    ((base_addr + (registers.get_Y() as usize)) & 0xFFFF) as u16
+) -> (Word, bool) {
+    let addr = fetch(registers, bus);
+    let lower = bus.read(addr as Address) as Address;
+    let upper = bus.read(addr.wrapping_add(1) as Address) as Address;
+    let base = upper << 8 | lower;
+    let addr = base.wrapping_add(registers.get_Y() as Word);
+    (addr, is_page_crossed(base, addr))
 }
 
 pub fn fetch_indirect_absolute<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
     let addr = fetch_word(registers, bus);
-    let upper = bus.read((addr & 0xFF00) | (((addr & 0xFF) + 1) & 0xFF) as Address) as Address;
-    let addr = (bus.read(addr) as Address) + (upper << 8) as Address;
-    addr & 0xFFFF
+    // The 6502 does not carry into the upper byte when the pointer sits on a page boundary.
+    let upper = bus.read((addr & 0xFF00) | (addr.wrapping_add(1) & 0x00FF)) as Address;
+    let lower = bus.read(addr) as Address;
+    upper << 8 | lower
+}
+
+pub fn is_page_crossed(base: Address, addr: Address) -> bool {
+    base & 0xFF00 != addr & 0xFF00
+}
+
+// Stores and read-modify-write instructions always spend the fix-up cycle, which is
+// already part of their base cycle count.
+fn has_page_cross_penalty(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::LDA
+            | Instruction::LDX
+            | Instruction::LDY
+            | Instruction::ADC
+            | Instruction::SBC
+            | Instruction::CMP
+            | Instruction::AND
+            | Instruction::EOR
+            | Instruction::ORA
+            | Instruction::NOP
+            | Instruction::LAX
+            | Instruction::LAS
+    )
 }
@@ -1,18 +1,25 @@
+use super::fetch::is_page_crossed;
 use crate::bus::CpuBus;
 use crate::cpu_registers::CpuRegisters;
 use crate::helper::*;
 use crate::types::{Address, Byte, Word};
 
 pub fn process_nmi<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
-    registers.set_break(false);
-    push((registers.get_PC() >> 8) as u8, registers, bus);
-    push(registers.get_PC() as u8, registers, bus);
-    push_status(registers, bus);
+    push_pc(registers, bus);
+    push_status(false, registers, bus);
     registers.set_interrupt(true);
     let next = bus.read_word(0xFFFA);
     registers.set_PC(next);
 }
 
+pub fn process_irq<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
+    push_pc(registers, bus);
+    push_status(false, registers, bus);
+    registers.set_interrupt(true);
+    let next = bus.read_word(0xFFFE);
+    registers.set_PC(next);
+}
+
 pub fn lda<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
     let computed = bus.read(operand);
     registers
@@ -110,14 +117,11 @@ pub fn tsx<T: CpuRegisters>(registers: &mut T) {
 }
 
 pub fn php<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
-    registers.set_break(true);
-    push_status(registers, bus);
+    push_status(true, registers, bus);
 }
 
 pub fn plp<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
-    registers.set_reserved(true);
-    let status = pop(registers, bus);
-    registers.set_P(status);
+    pop_status(registers, bus);
 }
 
 pub fn pha<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
@@ -131,12 +135,11 @@ pub fn pla<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
 }
 
 pub fn adc_imm<T: CpuRegisters>(operand: Word, registers: &mut T) {
-    let computed =
-        (operand as u16) + registers.get_A() as u16 + bool_to_u8(registers.get_carry()) as u16;
+    let computed = operand + registers.get_A() as u16 + bool_to_u8(registers.get_carry()) as u16;
     let acc = registers.get_A();
     registers
         .set_overflow(
-            !(((acc ^ (operand as Byte)) & 0x80) != 0) && ((acc ^ computed as Byte) & 0x80) != 0,
+            ((acc ^ (operand as Byte)) & 0x80) == 0 && ((acc ^ computed as Byte) & 0x80) != 0,
         )
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
@@ -150,9 +153,7 @@ pub fn adc<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &m
         fetched as u16 + registers.get_A() as u16 + bool_to_u8(registers.get_carry()) as u16;
     let acc = registers.get_A();
     registers
-        .set_overflow(
-            !(((acc ^ (fetched as Byte)) & 0x80) != 0) && ((acc ^ computed as Byte) & 0x80) != 0,
-        )
+        .set_overflow(((acc ^ fetched) & 0x80) == 0 && ((acc ^ computed as Byte) & 0x80) != 0)
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
         .set_carry(computed > 0xFF)
@@ -169,7 +170,7 @@ pub fn sbc_imm<T: CpuRegisters>(operand: Word, registers: &mut T) {
         )
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
-        .set_carry(computed >= 0 as i16)
+        .set_carry(computed >= 0)
         .set_A(computed as Byte);
 }
 
@@ -179,12 +180,10 @@ pub fn sbc<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &m
         registers.get_A() as i16 - fetched as i16 - bool_to_u8(!registers.get_carry()) as i16;
     let acc = registers.get_A();
     registers
-        .set_overflow(
-            (((acc ^ (fetched as Byte)) & 0x80) != 0) && ((acc ^ computed as Byte) & 0x80) != 0,
-        )
+        .set_overflow(((acc ^ fetched) & 0x80) != 0 && ((acc ^ computed as Byte) & 0x80) != 0)
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
-        .set_carry(computed >= 0 as i16)
+        .set_carry(computed >= 0)
         .set_A(computed as Byte);
 }
 
@@ -193,7 +192,7 @@ pub fn cpx_imm<T: CpuRegisters>(operand: Word, registers: &mut T) {
     registers
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
-        .set_carry(computed >= 0 as i16);
+        .set_carry(computed >= 0);
 }
 
 pub fn cpx<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
@@ -202,7 +201,7 @@ pub fn cpx<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &m
     registers
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
-        .set_carry(computed >= 0 as i16);
+        .set_carry(computed >= 0);
 }
 
 pub fn cpy_imm<T: CpuRegisters>(operand: Word, registers: &mut T) {
@@ -210,7 +209,7 @@ pub fn cpy_imm<T: CpuRegisters>(operand: Word, registers: &mut T) {
     registers
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
-        .set_carry(computed >= 0 as i16);
+        .set_carry(computed >= 0);
 }
 
 pub fn cpy<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
@@ -219,7 +218,7 @@ pub fn cpy<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &m
     registers
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
-        .set_carry(computed >= 0 as i16);
+        .set_carry(computed >= 0);
 }
 
 pub fn cmp_imm<T: CpuRegisters>(operand: Word, registers: &mut T) {
@@ -227,7 +226,7 @@ pub fn cmp_imm<T: CpuRegisters>(operand: Word, registers: &mut T) {
     registers
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
-        .set_carry(computed >= 0 as i16);
+        .set_carry(computed >= 0);
 }
 
 pub fn cmp<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
@@ -236,7 +235,7 @@ pub fn cmp<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &m
     registers
         .update_negative_by(computed as Byte)
         .update_zero_by(computed as Byte)
-        .set_carry(computed >= 0 as i16);
+        .set_carry(computed >= 0);
 }
 
 pub fn and_imm<T: CpuRegisters>(operand: Word, registers: &mut T) {
@@ -301,7 +300,7 @@ pub fn bit<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &m
 
 pub fn asl_acc<T: CpuRegisters>(registers: &mut T) {
     let acc = registers.get_A();
-    let shifted = (acc << 1) as u8;
+    let shifted = acc << 1;
     registers
         .set_carry(acc & 0x80 == 0x80)
         .update_negative_by(shifted)
@@ -310,8 +309,8 @@ pub fn asl_acc<T: CpuRegisters>(registers: &mut T) {
 }
 
 pub fn asl<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
-    let fetched = bus.read(operand);
-    let shifted = (fetched << 1) as u8;
+    let fetched = read_for_modify(operand, bus);
+    let shifted = fetched << 1;
     registers
         .set_carry(fetched & 0x80 == 0x80)
         .update_negative_by(shifted)
@@ -321,7 +320,7 @@ pub fn asl<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &m
 
 pub fn lsr_acc<T: CpuRegisters>(registers: &mut T) {
     let acc = registers.get_A();
-    let shifted = (acc >> 1) as u8;
+    let shifted = acc >> 1;
     registers
         .set_carry((acc & 0x01) == 0x01)
         .update_negative_by(shifted)
@@ -330,8 +329,8 @@ pub fn lsr_acc<T: CpuRegisters>(registers: &mut T) {
 }
 
 pub fn lsr<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
-    let fetched = bus.read(operand);
-    let shifted = (fetched >> 1) as u8;
+    let fetched = read_for_modify(operand, bus);
+    let shifted = fetched >> 1;
     registers
         .set_carry(fetched & 0x01 == 0x01)
         .update_negative_by(shifted)
@@ -350,7 +349,7 @@ pub fn rol_acc<T: CpuRegisters>(registers: &mut T) {
 }
 
 pub fn rol<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
-    let fetched = bus.read(operand);
+    let fetched = read_for_modify(operand, bus);
     let rotated = rotate_to_left(registers, fetched);
     registers
         .set_carry(fetched & 0x80 == 0x80)
@@ -370,7 +369,7 @@ pub fn ror_acc<T: CpuRegisters>(registers: &mut T) {
 }
 
 pub fn ror<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
-    let fetched = bus.read(operand);
+    let fetched = read_for_modify(operand, bus);
     let rotated = rotate_to_right(registers, fetched);
     registers
         .set_carry(fetched & 0x01 == 0x01)
@@ -380,43 +379,35 @@ pub fn ror<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &m
 }
 
 pub fn inx<T: CpuRegisters>(registers: &mut T) {
-    let x = registers.get_X() + 1;
+    let x = registers.get_X().wrapping_add(1);
     registers.set_X(x).update_negative_by(x).update_zero_by(x);
 }
 
 pub fn iny<T: CpuRegisters>(registers: &mut T) {
-    let y = registers.get_Y() + 1;
+    let y = registers.get_Y().wrapping_add(1);
     registers.set_Y(y).update_negative_by(y).update_zero_by(y);
 }
 
 pub fn inc<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
-    let data = bus.read(operand) + 1 as u8;
+    let data = read_for_modify(operand, bus).wrapping_add(1);
     registers.update_negative_by(data).update_zero_by(data);
     bus.write(operand, data);
 }
 
 pub fn dex<T: CpuRegisters>(registers: &mut T) {
-    let x = registers.get_X() as i8 - 1;
-    registers
-        .set_X(x as Byte)
-        .update_negative_by(x as Byte)
-        .update_zero_by(x as Byte);
+    let x = registers.get_X().wrapping_sub(1);
+    registers.set_X(x).update_negative_by(x).update_zero_by(x);
 }
 
 pub fn dey<T: CpuRegisters>(registers: &mut T) {
-    let y = registers.get_Y() as i8 - 1;
-    registers
-        .set_Y(y as Byte)
-        .update_negative_by(y as Byte)
-        .update_zero_by(y as Byte);
+    let y = registers.get_Y().wrapping_sub(1);
+    registers.set_Y(y).update_negative_by(y).update_zero_by(y);
 }
 
 pub fn dec<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
-    let data = bus.read(operand) as i8 - 1;
-    registers
-        .update_negative_by(data as Byte)
-        .update_zero_by(data as Byte);
-    bus.write(operand, data as Byte);
+    let data = read_for_modify(operand, bus).wrapping_sub(1);
+    registers.update_negative_by(data).update_zero_by(data);
+    bus.write(operand, data);
 }
 
 pub fn clc<T: CpuRegisters>(registers: &mut T) {
@@ -439,19 +430,19 @@ pub fn sei<T: CpuRegisters>(registers: &mut T) {
     registers.set_interrupt(true);
 }
 
+// The unofficial DOP/TOP variants still perform the read of their operand.
+pub fn nop<U: CpuBus>(operand: Word, bus: &mut U) {
+    bus.read(operand);
+}
+
 pub fn brk<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
-    let interrupt = registers.get_interrupt();
+    // BRK is followed by a padding byte which is skipped by the return address.
     registers.inc_PC();
     push_pc(registers, bus);
-    registers.set_break(true);
-    push_status(registers, bus);
+    push_status(true, registers, bus);
     registers.set_interrupt(true);
-    // Ignore interrupt when already set.
-    if !interrupt {
-        let fetched = bus.read_word(0xFFFE);
-        registers.set_PC(fetched);
-    }
-    registers.dec_PC();
+    let fetched = bus.read_word(0xFFFE);
+    registers.set_PC(fetched);
 }
 
 pub fn jsr<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
@@ -468,7 +459,6 @@ pub fn jmp<T: CpuRegisters>(operand: Word, registers: &mut T) {
 pub fn rti<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     pop_status(registers, bus);
     pop_pc(registers, bus);
-    registers.set_reserved(true);
 }
 
 pub fn rts<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
@@ -476,51 +466,67 @@ pub fn rts<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     registers.inc_PC();
 }
 
-pub fn bcc<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bcc<T: CpuRegisters>(operand: Word, registers: &mut T) -> Byte {
     if !registers.get_carry() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bcs<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bcs<T: CpuRegisters>(operand: Word, registers: &mut T) -> Byte {
     if registers.get_carry() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn beq<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn beq<T: CpuRegisters>(operand: Word, registers: &mut T) -> Byte {
     if registers.get_zero() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bmi<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bmi<T: CpuRegisters>(operand: Word, registers: &mut T) -> Byte {
     if registers.get_negative() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bne<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bne<T: CpuRegisters>(operand: Word, registers: &mut T) -> Byte {
     if !registers.get_zero() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bpl<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bpl<T: CpuRegisters>(operand: Word, registers: &mut T) -> Byte {
     if !registers.get_negative() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bvs<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bvs<T: CpuRegisters>(operand: Word, registers: &mut T) -> Byte {
     if registers.get_overflow() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bvc<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bvc<T: CpuRegisters>(operand: Word, registers: &mut T) -> Byte {
     if !registers.get_overflow() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
@@ -532,22 +538,194 @@ pub fn sed<T: CpuRegisters>(registers: &mut T) {
     registers.set_decimal(true);
 }
 
+pub fn lax<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = bus.read(operand);
+    registers
+        .set_A(fetched)
+        .set_X(fetched)
+        .update_negative_by(fetched)
+        .update_zero_by(fetched);
+}
+
+pub fn sax<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let computed = registers.get_A() & registers.get_X();
+    bus.write(operand, computed);
+}
+
+pub fn dcp<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = read_for_modify(operand, bus).wrapping_sub(1);
+    bus.write(operand, data);
+    cmp_imm(data as Word, registers);
+}
+
+pub fn isb<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = read_for_modify(operand, bus).wrapping_add(1);
+    bus.write(operand, data);
+    sbc_imm(data as Word, registers);
+}
+
+pub fn slo<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = read_for_modify(operand, bus);
+    let shifted = fetched << 1;
+    registers.set_carry(fetched & 0x80 == 0x80);
+    bus.write(operand, shifted);
+    ora_imm(shifted as Word, registers);
+}
+
+pub fn rla<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = read_for_modify(operand, bus);
+    let rotated = rotate_to_left(registers, fetched);
+    registers.set_carry(fetched & 0x80 == 0x80);
+    bus.write(operand, rotated);
+    and_imm(rotated as Word, registers);
+}
+
+pub fn sre<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = read_for_modify(operand, bus);
+    let shifted = fetched >> 1;
+    registers.set_carry(fetched & 0x01 == 0x01);
+    bus.write(operand, shifted);
+    eor_imm(shifted as Word, registers);
+}
+
+pub fn rra<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = read_for_modify(operand, bus);
+    let rotated = rotate_to_right(registers, fetched);
+    registers.set_carry(fetched & 0x01 == 0x01);
+    bus.write(operand, rotated);
+    adc_imm(rotated as Word, registers);
+}
+
+pub fn lax_imm<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    let computed = (registers.get_A() | UNSTABLE_MAGIC) & operand as Byte;
+    registers
+        .set_A(computed)
+        .set_X(computed)
+        .update_negative_by(computed)
+        .update_zero_by(computed);
+}
+
+pub fn anc<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    and_imm(operand, registers);
+    let negative = registers.get_negative();
+    registers.set_carry(negative);
+}
+
+pub fn alr<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    and_imm(operand, registers);
+    lsr_acc(registers);
+}
+
+pub fn arr<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    let computed = rotate_to_right(registers, registers.get_A() & operand as Byte);
+    registers
+        .set_carry(computed & 0x40 == 0x40)
+        .set_overflow(((computed >> 6) ^ (computed >> 5)) & 0x01 == 0x01)
+        .update_negative_by(computed)
+        .update_zero_by(computed)
+        .set_A(computed);
+}
+
+pub fn xaa<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    let computed = (registers.get_A() | UNSTABLE_MAGIC) & registers.get_X() & operand as Byte;
+    registers
+        .set_A(computed)
+        .update_negative_by(computed)
+        .update_zero_by(computed);
+}
+
+pub fn axs<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    let masked = registers.get_A() & registers.get_X();
+    let computed = masked.wrapping_sub(operand as Byte);
+    registers
+        .set_carry(masked >= operand as Byte)
+        .update_negative_by(computed)
+        .update_zero_by(computed)
+        .set_X(computed);
+}
+
+pub fn las<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let computed = bus.read(operand) & registers.get_SP();
+    registers
+        .set_A(computed)
+        .set_X(computed)
+        .set_SP(computed)
+        .update_negative_by(computed)
+        .update_zero_by(computed);
+}
+
+pub fn ahx<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = registers.get_A() & registers.get_X();
+    let index = registers.get_Y();
+    store_high_byte_masked(operand, index, data, bus);
+}
+
+pub fn tas<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = registers.get_A() & registers.get_X();
+    registers.set_SP(data);
+    let index = registers.get_Y();
+    store_high_byte_masked(operand, index, data, bus);
+}
+
+pub fn shy<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = registers.get_Y();
+    let index = registers.get_X();
+    store_high_byte_masked(operand, index, data, bus);
+}
+
+pub fn shx<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = registers.get_X();
+    let index = registers.get_Y();
+    store_high_byte_masked(operand, index, data, bus);
+}
+
+pub fn kil<T: CpuRegisters>(registers: &mut T) {
+    // Leave PC on the JAM opcode so the host can see where the CPU locked up.
+    registers.dec_PC().set_halted(true);
+}
+
+// The unstable XAA/LXA opcodes OR the accumulator with a chip-dependent constant before
+// the AND. 0xEE is the value most commonly measured on NES consoles.
+const UNSTABLE_MAGIC: Byte = 0xEE;
+
+// SHY/SHX/AHX/TAS store `data & (H + 1)` where H is the high byte of the base address,
+// and when the index crosses a page the stored value also replaces the high address byte.
+fn store_high_byte_masked<U: CpuBus>(operand: Word, index: Byte, data: Byte, bus: &mut U) {
+    let base = operand.wrapping_sub(index as Word);
+    let computed = data & ((base >> 8) as Byte).wrapping_add(1);
+    let addr = if base & 0xFF00 != operand & 0xFF00 {
+        (computed as Address) << 8 | (operand & 0x00FF)
+    } else {
+        operand
+    };
+    bus.write(addr, computed);
+}
+
+// Read-modify-write instructions write the unmodified value back while they compute the
+// result, so the target sees two writes on consecutive cycles.
+fn read_for_modify<U: CpuBus>(operand: Word, bus: &mut U) -> Byte {
+    let data = bus.read(operand);
+    bus.write(operand, data);
+    data
+}
+
 fn rotate_to_right<T: CpuRegisters>(registers: &mut T, v: Byte) -> Byte {
-    ((v >> 1) as Byte | if registers.get_carry() { 0x80 } else { 0x00 }) as Byte
+    v >> 1 | if registers.get_carry() { 0x80 } else { 0x00 }
 }
 
 fn rotate_to_left<T: CpuRegisters>(registers: &mut T, v: Byte) -> Byte {
-    ((v << 1) as Byte | if registers.get_carry() { 0x01 } else { 0x00 }) as Byte
+    v << 1 | if registers.get_carry() { 0x01 } else { 0x00 }
 }
 
 fn push<T: CpuRegisters, U: CpuBus>(data: Byte, registers: &mut T, bus: &mut U) {
     let addr = registers.get_SP() as Address;
-    bus.write((addr | 0x0100), data);
+    bus.write(addr | 0x0100, data);
     registers.dec_SP();
 }
 
-fn push_status<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
-    let status = registers.get_P();
+// The B flag only exists on the stack: it is set by PHP/BRK and cleared by NMI/IRQ.
+fn push_status<T: CpuRegisters, U: CpuBus>(break_mode: bool, registers: &mut T, bus: &mut U) {
+    let status = registers.get_P() & 0xCF | 0x20 | if break_mode { 0x10 } else { 0x00 };
     push(status, registers, bus);
 }
 
@@ -565,7 +743,7 @@ fn pop_pc<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
 
 fn pop_status<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     let status = pop(registers, bus);
-    registers.set_P(status);
+    registers.set_P(status & 0xEF | 0x20);
 }
 
 fn push_pc<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
@@ -574,8 +752,15 @@ fn push_pc<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     push(pc as u8, registers, bus);
 }
 
-fn branch<T: CpuRegisters>(registers: &mut T, addr: Address) {
+// Returns the extra cycles of a taken branch: one, plus one more when it lands on another page.
+fn branch<T: CpuRegisters>(registers: &mut T, addr: Address) -> Byte {
+    let pc = registers.get_PC();
     registers.set_PC(addr);
+    if is_page_crossed(pc, addr) {
+        2
+    } else {
+        1
+    }
 }
 
 #[cfg(test)]
@@ -585,11 +770,15 @@ mod test {
 
     struct MockBus {
         pub mem: Vec<Byte>,
+        pub writes: Vec<(Address, Byte)>,
     }
 
     impl MockBus {
         pub fn new() -> Self {
-            MockBus { mem: vec![0; 1024] }
+            MockBus {
+                mem: vec![0; 0x10000],
+                writes: Vec::new(),
+            }
         }
     }
 
@@ -600,10 +789,11 @@ mod test {
         fn read_word(&mut self, addr: Address) -> Word {
             let lower = self.read(addr) as u16;
             let upper = self.read(addr + 1) as u16;
-            (upper << 8 | lower) as u16
+            upper << 8 | lower
         }
         fn write(&mut self, addr: Address, data: Byte) {
             self.mem[addr as usize] = data;
+            self.writes.push((addr, data));
         }
     }
 
@@ -809,7 +999,7 @@ mod test {
         let mut reg = Registers::new();
         reg.set_X(0x05);
         cpx_imm(0x04, &mut reg);
-        assert_eq!(reg.get_carry(), true);
+        assert!(reg.get_carry());
     }
 
     #[test]
@@ -819,7 +1009,7 @@ mod test {
         let mut bus = MockBus::new();
         bus.mem[0xA5] = 0x04;
         cpx(0xA5, &mut reg, &mut bus);
-        assert_eq!(reg.get_carry(), true);
+        assert!(reg.get_carry());
     }
 
     #[test]
@@ -827,7 +1017,7 @@ mod test {
         let mut reg = Registers::new();
         reg.set_Y(0x05);
         cpy_imm(0x04, &mut reg);
-        assert_eq!(reg.get_carry(), true);
+        assert!(reg.get_carry());
     }
 
     #[test]
@@ -837,7 +1027,7 @@ mod test {
         let mut bus = MockBus::new();
         bus.mem[0xA5] = 0x04;
         cpy(0xA5, &mut reg, &mut bus);
-        assert_eq!(reg.get_carry(), true);
+        assert!(reg.get_carry());
     }
 
     #[test]
@@ -845,7 +1035,7 @@ mod test {
         let mut reg = Registers::new();
         reg.set_A(0x05);
         cmp_imm(0x04, &mut reg);
-        assert_eq!(reg.get_carry(), true);
+        assert!(reg.get_carry());
     }
 
     #[test]
@@ -855,7 +1045,7 @@ mod test {
         let mut bus = MockBus::new();
         bus.mem[0xA5] = 0x04;
         cmp(0xA5, &mut reg, &mut bus);
-        assert_eq!(reg.get_carry(), true);
+        assert!(reg.get_carry());
     }
 
     #[test]
@@ -927,6 +1117,8 @@ mod test {
         bus.mem[0x00] = 0x55;
         asl(0x00, &mut reg, &mut bus);
         assert_eq!(bus.mem[0x00], 0xAA);
+        // The unmodified value is written back first.
+        assert_eq!(bus.writes, vec![(0x00, 0x55), (0x00, 0xAA)]);
     }
 
     #[test]
@@ -992,6 +1184,7 @@ mod test {
         bus.mem[0x10] = 0xAA;
         inc(0x10, &mut reg, &mut bus);
         assert_eq!(bus.mem[0x10], 0xAB);
+        assert_eq!(bus.writes, vec![(0x10, 0xAA), (0x10, 0xAB)]);
     }
 
     #[test]
@@ -1033,4 +1226,176 @@ mod test {
         jmp(0x10, &mut reg);
         assert_eq!(reg.get_PC(), 0x10);
     }
+
+    #[test]
+    fn test_process_irq() {
+        let mut reg = Registers::new();
+        reg.set_PC(0x1234).set_SP(0xFF).set_P(0x20);
+        let mut bus = MockBus::new();
+        bus.mem[0xFFFE] = 0x00;
+        bus.mem[0xFFFF] = 0x80;
+        process_irq(&mut reg, &mut bus);
+        assert_eq!(bus.mem[0x1FF], 0x12);
+        assert_eq!(bus.mem[0x1FE], 0x34);
+        assert_eq!(bus.mem[0x1FD], 0x20);
+        assert_eq!(reg.get_PC(), 0x8000);
+        assert!(reg.get_interrupt());
+    }
+
+    #[test]
+    fn test_lax() {
+        let mut reg = Registers::new();
+        let mut bus = MockBus::new();
+        bus.mem[0xA5] = 0x80;
+        lax(0xA5, &mut reg, &mut bus);
+        assert_eq!(reg.get_A(), 0x80);
+        assert_eq!(reg.get_X(), 0x80);
+        assert!(reg.get_negative());
+    }
+
+    #[test]
+    fn test_sax() {
+        let mut reg = Registers::new();
+        reg.set_A(0xF0).set_X(0x3C);
+        let mut bus = MockBus::new();
+        sax(0xA5, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0xA5], 0x30);
+    }
+
+    #[test]
+    fn test_dcp() {
+        let mut reg = Registers::new();
+        reg.set_A(0x05);
+        let mut bus = MockBus::new();
+        bus.mem[0xA5] = 0x06;
+        dcp(0xA5, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0xA5], 0x05);
+        assert!(reg.get_zero());
+        assert!(reg.get_carry());
+    }
+
+    #[test]
+    fn test_isb() {
+        let mut reg = Registers::new();
+        reg.set_A(0x10).set_carry(true);
+        let mut bus = MockBus::new();
+        bus.mem[0xA5] = 0x05;
+        isb(0xA5, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0xA5], 0x06);
+        assert_eq!(reg.get_A(), 0x0A);
+    }
+
+    #[test]
+    fn test_slo() {
+        let mut reg = Registers::new();
+        reg.set_A(0x01);
+        let mut bus = MockBus::new();
+        bus.mem[0xA5] = 0x81;
+        slo(0xA5, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0xA5], 0x02);
+        assert_eq!(reg.get_A(), 0x03);
+        assert!(reg.get_carry());
+    }
+
+    #[test]
+    fn test_rla() {
+        let mut reg = Registers::new();
+        reg.set_A(0x0F).set_carry(true);
+        let mut bus = MockBus::new();
+        bus.mem[0xA5] = 0x85;
+        rla(0xA5, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0xA5], 0x0B);
+        assert_eq!(reg.get_A(), 0x0B);
+        assert!(reg.get_carry());
+    }
+
+    #[test]
+    fn test_sre() {
+        let mut reg = Registers::new();
+        reg.set_A(0xFF);
+        let mut bus = MockBus::new();
+        bus.mem[0xA5] = 0x03;
+        sre(0xA5, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0xA5], 0x01);
+        assert_eq!(reg.get_A(), 0xFE);
+        assert!(reg.get_carry());
+    }
+
+    #[test]
+    fn test_rra() {
+        let mut reg = Registers::new();
+        reg.set_A(0x10);
+        let mut bus = MockBus::new();
+        bus.mem[0xA5] = 0x03;
+        rra(0xA5, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0xA5], 0x01);
+        assert_eq!(reg.get_A(), 0x12);
+    }
+
+    #[test]
+    fn test_anc() {
+        let mut reg = Registers::new();
+        reg.set_A(0xF0);
+        anc(0x8F, &mut reg);
+        assert_eq!(reg.get_A(), 0x80);
+        assert!(reg.get_carry());
+    }
+
+    #[test]
+    fn test_alr() {
+        let mut reg = Registers::new();
+        reg.set_A(0xFF);
+        alr(0x03, &mut reg);
+        assert_eq!(reg.get_A(), 0x01);
+        assert!(reg.get_carry());
+    }
+
+    #[test]
+    fn test_arr() {
+        let mut reg = Registers::new();
+        reg.set_A(0xFF).set_carry(true);
+        arr(0x80, &mut reg);
+        assert_eq!(reg.get_A(), 0xC0);
+        assert!(reg.get_carry());
+        assert!(reg.get_overflow());
+    }
+
+    #[test]
+    fn test_axs() {
+        let mut reg = Registers::new();
+        reg.set_A(0x0F).set_X(0x3C);
+        axs(0x02, &mut reg);
+        assert_eq!(reg.get_X(), 0x0A);
+        assert!(reg.get_carry());
+    }
+
+    #[test]
+    fn test_las() {
+        let mut reg = Registers::new();
+        reg.set_SP(0xF0);
+        let mut bus = MockBus::new();
+        bus.mem[0xA5] = 0x3C;
+        las(0xA5, &mut reg, &mut bus);
+        assert_eq!(reg.get_A(), 0x30);
+        assert_eq!(reg.get_X(), 0x30);
+        assert_eq!(reg.get_SP(), 0x30);
+    }
+
+    #[test]
+    fn test_shy() {
+        let mut reg = Registers::new();
+        reg.set_Y(0xFF).set_X(0x10);
+        let mut bus = MockBus::new();
+        shy(0x0110, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0x0110], 0x02);
+    }
+
+    #[test]
+    fn test_kil() {
+        let mut reg = Registers::new();
+        reg.set_PC(0x0011);
+        kil(&mut reg);
+        assert!(reg.get_halted());
+        assert_eq!(reg.get_PC(), 0x0010);
+    }
 }
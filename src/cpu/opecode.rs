@@ -71,6 +71,17 @@ pub enum Instruction {
     RLA,
     SRE,
     RRA,
+    ANC,
+    ALR,
+    ARR,
+    XAA,
+    AXS,
+    LAS,
+    AHX,
+    TAS,
+    SHY,
+    SHX,
+    KIL,
 }
 
 #[derive(Debug, PartialEq)]
@@ -91,14 +102,14 @@ pub enum Addressing {
 }
 
 const CYCLES: [u8; 256] = [
-    7, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 6, 7,
-    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 6, 7,
-    6, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 6, 7,
-    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 6, 7,
-    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, 2, 6, 2, 6, 4, 4, 4, 4, 2, 4, 2, 5, 5, 4, 5, 5,
+    7, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    6, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, 2, 6, 2, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5,
     2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, 2, 5, 2, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4,
     2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
-    2, 6, 3, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
 ];
 
 pub fn get_opecode(x: u8) -> Opecode {
@@ -263,39 +274,27 @@ pub fn get_opecode(x: u8) -> Opecode {
         0x7A => (NOP, Implied),
         0xDA => (NOP, Implied),
         0xFA => (NOP, Implied),
-        0x02 => (NOP, Implied),
-        0x12 => (NOP, Implied),
-        0x22 => (NOP, Implied),
-        0x32 => (NOP, Implied),
-        0x42 => (NOP, Implied),
-        0x52 => (NOP, Implied),
-        0x62 => (NOP, Implied),
-        0x72 => (NOP, Implied),
-        0x92 => (NOP, Implied),
-        0xB2 => (NOP, Implied),
-        0xD2 => (NOP, Implied),
-        0xF2 => (NOP, Implied),
-        0x80 => (NOP, Implied),
-        0x82 => (NOP, Implied),
-        0x89 => (NOP, Implied),
-        0xC2 => (NOP, Implied),
-        0xE2 => (NOP, Implied),
-        0x04 => (NOP, Implied),
-        0x44 => (NOP, Implied),
-        0x64 => (NOP, Implied),
-        0x14 => (NOP, Implied),
-        0x34 => (NOP, Implied),
-        0x54 => (NOP, Implied),
-        0x74 => (NOP, Implied),
-        0xD4 => (NOP, Implied),
-        0xF4 => (NOP, Implied),
-        0x0C => (NOP, Implied),
-        0x1C => (NOP, Implied),
-        0x3C => (NOP, Implied),
-        0x5C => (NOP, Implied),
-        0x7C => (NOP, Implied),
-        0xDC => (NOP, Implied),
-        0xFC => (NOP, Implied),
+        0x80 => (NOP, Immediate),
+        0x82 => (NOP, Immediate),
+        0x89 => (NOP, Immediate),
+        0xC2 => (NOP, Immediate),
+        0xE2 => (NOP, Immediate),
+        0x04 => (NOP, ZeroPage),
+        0x44 => (NOP, ZeroPage),
+        0x64 => (NOP, ZeroPage),
+        0x14 => (NOP, ZeroPageX),
+        0x34 => (NOP, ZeroPageX),
+        0x54 => (NOP, ZeroPageX),
+        0x74 => (NOP, ZeroPageX),
+        0xD4 => (NOP, ZeroPageX),
+        0xF4 => (NOP, ZeroPageX),
+        0x0C => (NOP, Absolute),
+        0x1C => (NOP, AbsoluteX),
+        0x3C => (NOP, AbsoluteX),
+        0x5C => (NOP, AbsoluteX),
+        0x7C => (NOP, AbsoluteX),
+        0xDC => (NOP, AbsoluteX),
+        0xFC => (NOP, AbsoluteX),
         0xA7 => (LAX, ZeroPage),
         0xB7 => (LAX, ZeroPageY),
         0xAF => (LAX, Absolute),
@@ -349,7 +348,31 @@ pub fn get_opecode(x: u8) -> Opecode {
         0x7B => (RRA, AbsoluteY),
         0x63 => (RRA, PreIndexedIndirect),
         0x73 => (RRA, PostIndexedIndirect),
-        _ => unreachable!(),
+        0x0B => (ANC, Immediate),
+        0x2B => (ANC, Immediate),
+        0x4B => (ALR, Immediate),
+        0x6B => (ARR, Immediate),
+        0x8B => (XAA, Immediate),
+        0xAB => (LAX, Immediate),
+        0xCB => (AXS, Immediate),
+        0xBB => (LAS, AbsoluteY),
+        0x93 => (AHX, PostIndexedIndirect),
+        0x9F => (AHX, AbsoluteY),
+        0x9B => (TAS, AbsoluteY),
+        0x9C => (SHY, AbsoluteX),
+        0x9E => (SHX, AbsoluteY),
+        0x02 => (KIL, Implied),
+        0x12 => (KIL, Implied),
+        0x22 => (KIL, Implied),
+        0x32 => (KIL, Implied),
+        0x42 => (KIL, Implied),
+        0x52 => (KIL, Implied),
+        0x62 => (KIL, Implied),
+        0x72 => (KIL, Implied),
+        0x92 => (KIL, Implied),
+        0xB2 => (KIL, Implied),
+        0xD2 => (KIL, Implied),
+        0xF2 => (KIL, Implied),
     };
     Opecode { name, mode, cycle }
 }
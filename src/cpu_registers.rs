@@ -21,6 +21,7 @@ pub struct Registers {
     SP: u8,
     PC: u16,
     P: Status,
+    halted: bool,
 }
 
 #[allow(non_snake_case)]
@@ -92,6 +93,10 @@ pub trait CpuRegisters {
     fn inc_PC(&mut self) -> &mut Self;
 
     fn dec_PC(&mut self) -> &mut Self;
+
+    fn get_halted(&self) -> bool;
+
+    fn set_halted(&mut self, v: bool) -> &mut Self;
 }
 
 impl Registers {
@@ -112,10 +117,17 @@ impl Registers {
                 zero: false,
                 carry: false,
             },
+            halted: false,
         }
     }
 }
 
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[allow(non_snake_case)]
 impl CpuRegisters for Registers {
     fn get_PC(&self) -> u16 {
@@ -146,7 +158,7 @@ impl CpuRegisters for Registers {
             | bool_to_u8(self.P.decimal_mode) << 3
             | bool_to_u8(self.P.interrupt) << 2
             | bool_to_u8(self.P.zero) << 1
-            | bool_to_u8(self.P.carry) as u8
+            | bool_to_u8(self.P.carry)
     }
 
     fn set_A(&mut self, v: u8) -> &mut Self {
@@ -269,22 +281,31 @@ impl CpuRegisters for Registers {
     }
 
     fn inc_SP(&mut self) -> &mut Self {
-        self.SP += 1;
+        self.SP = self.SP.wrapping_add(1);
         self
     }
 
     fn dec_SP(&mut self) -> &mut Self {
-        self.SP -= 1;
+        self.SP = self.SP.wrapping_sub(1);
         self
     }
 
     fn inc_PC(&mut self) -> &mut Self {
-        self.PC += 1;
+        self.PC = self.PC.wrapping_add(1);
         self
     }
 
     fn dec_PC(&mut self) -> &mut Self {
-        self.PC -= 1;
+        self.PC = self.PC.wrapping_sub(1);
+        self
+    }
+
+    fn get_halted(&self) -> bool {
+        self.halted
+    }
+
+    fn set_halted(&mut self, v: bool) -> &mut Self {
+        self.halted = v;
         self
     }
 }
@@ -0,0 +1,175 @@
+use crate::types::{Byte, Word};
+
+// OAM DMA ($4014). A write latches the source page, and the bus copies that page into
+// OAM through $2004 once the writing instruction has finished.
+#[derive(Default)]
+pub struct Dma {
+    page: Option<Byte>,
+    // CPU cycles since power-on, which decide the alignment of a transfer.
+    cycles: u64,
+    // The length of the OAM DMA at the end of the last instruction, or 0.
+    transfer_cycles: Word,
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Dma::default()
+    }
+
+    pub fn write(&mut self, data: Byte) {
+        self.page = Some(data);
+    }
+
+    pub fn take_page(&mut self) -> Option<Byte> {
+        self.page.take()
+    }
+
+    pub fn add_cycles(&mut self, cycles: Word) {
+        self.cycles += cycles as u64;
+    }
+
+    // The CPU halts for one cycle, one more if the transfer would start on an odd
+    // cycle, then alternates 256 reads and 256 writes. `elapsed` counts the cycles of
+    // the current instruction not yet added.
+    pub fn stall_cycles(&mut self, elapsed: Word) -> Word {
+        let is_odd_cycle = (self.cycles + elapsed as u64) & 0x01 == 0x01;
+        self.transfer_cycles = if is_odd_cycle { 514 } else { 513 };
+        self.transfer_cycles
+    }
+
+    pub fn take_transfer_cycles(&mut self) -> Word {
+        std::mem::replace(&mut self.transfer_cycles, 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::apu::Apu;
+    use crate::bus::{Bus, CpuAccess, CpuBus};
+    use crate::cpu;
+    use crate::cpu_registers::{CpuRegisters, Registers};
+    use crate::mapper::nrom::Nrom;
+    use crate::mapper::test::cartridge;
+    use crate::ppu::Ppu;
+    use crate::ram::Ram;
+
+    #[test]
+    fn test_oam_dma_copies_a_page_and_stalls_the_cpu() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut work_ram = Ram::new(vec![0; 0x800]);
+        // STA $4014 at $0000, copying page 2.
+        work_ram.field[..3].copy_from_slice(&[0x8D, 0x14, 0x40]);
+        for i in 0..0x100 {
+            work_ram.field[0x200 + i] = i as Byte;
+        }
+        let mut ppu = Ppu::new();
+        let mut apu = Apu::new();
+        let mut dma = Dma::new();
+        let mut last_access = CpuAccess::None;
+        let mut registers = Registers::new();
+        let mut nmi = false;
+
+        registers.set_PC(0x0000).set_A(0x02);
+        let mut bus = Bus::new(
+            &mut mapper,
+            &mut work_ram,
+            &mut ppu,
+            &mut apu,
+            &mut dma,
+            &mut last_access,
+        );
+        // The write lands at the end of cycle 4, so the transfer starts on an even cycle.
+        assert_eq!(cpu::run(&mut registers, &mut bus, &mut nmi, false), 4 + 513);
+        ppu.write_register(0x2003, 0x05, &mut mapper);
+        assert_eq!(ppu.read_register(0x2004, &mut mapper), 0x05);
+
+        dma.add_cycles(4 + 513);
+        registers.set_PC(0x0000);
+        let mut bus = Bus::new(
+            &mut mapper,
+            &mut work_ram,
+            &mut ppu,
+            &mut apu,
+            &mut dma,
+            &mut last_access,
+        );
+        assert_eq!(cpu::run(&mut registers, &mut bus, &mut nmi, false), 4 + 514);
+    }
+
+    #[test]
+    fn test_dmc_fetch_repeats_a_halted_register_read() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut work_ram = Ram::new(vec![0; 0x800]);
+        let mut ppu = Ppu::new();
+        let mut apu = Apu::new();
+        let mut dma = Dma::new();
+        let mut last_access = CpuAccess::None;
+        let mut bus = Bus::new(
+            &mut mapper,
+            &mut work_ram,
+            &mut ppu,
+            &mut apu,
+            &mut dma,
+            &mut last_access,
+        );
+        bus.write(0x2006, 0x20);
+        bus.write(0x2006, 0x00);
+        for data in 1..=4 {
+            bus.write(0x2007, data);
+        }
+        bus.write(0x2006, 0x20);
+        bus.write(0x2006, 0x00);
+
+        // Start a one-byte sample at $C000.
+        bus.write(0x4015, 0x10);
+        assert_eq!(bus.read(0x4015) & 0x10, 0x10);
+        bus.read(0x2007);
+        assert_eq!(bus.read(0x2007), 1);
+        assert_eq!(bus.fetch_dmc_sample(0xC000, true, false), 4);
+        // The repeated read swallowed the 2.
+        assert_eq!(bus.read(0x2007), 3);
+        assert_eq!(bus.read(0x4015) & 0x10, 0x00);
+
+        // During an OAM DMA the fetch is shorter and the last read is not repeated.
+        bus.write(0x4015, 0x10);
+        assert_eq!(bus.read(0x2007), 4);
+        assert_eq!(bus.fetch_dmc_sample(0xC000, true, true), 2);
+        assert_eq!(bus.read(0x2007), 0);
+    }
+
+    #[test]
+    fn test_dmc_fetch_does_not_repeat_a_final_write() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut work_ram = Ram::new(vec![0; 0x800]);
+        let mut ppu = Ppu::new();
+        let mut apu = Apu::new();
+        let mut dma = Dma::new();
+        let mut last_access = CpuAccess::None;
+        let mut bus = Bus::new(
+            &mut mapper,
+            &mut work_ram,
+            &mut ppu,
+            &mut apu,
+            &mut dma,
+            &mut last_access,
+        );
+        bus.write(0x2006, 0x20);
+        bus.write(0x2006, 0x00);
+        for data in 1..=3 {
+            bus.write(0x2007, data);
+        }
+        bus.write(0x2006, 0x20);
+        bus.write(0x2006, 0x00);
+
+        bus.write(0x4015, 0x10);
+        bus.read(0x2007);
+        assert_eq!(bus.read(0x2007), 1);
+        // A STA $4016 after the $2007 read ends on a write, which is not repeated.
+        bus.write(0x4016, 0x00);
+        assert_eq!(bus.fetch_dmc_sample(0xC000, true, false), 4);
+        assert_eq!(bus.read(0x2007), 2);
+        // The fetch itself is not a CPU access.
+        assert_eq!(last_access, CpuAccess::Read(0x2007));
+    }
+}
@@ -1,8 +1,12 @@
+pub mod apu;
 pub mod bus;
 pub mod cartridge;
 pub mod cpu;
 pub mod cpu_registers;
+pub mod dma;
 pub mod helper;
+pub mod mapper;
+pub mod ppu;
 pub mod ram;
 pub mod rom;
 pub mod types;
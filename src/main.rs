@@ -2,14 +2,17 @@ extern crate simple_nes_rs as nes;
 
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{self, BufReader};
+use std::io::BufReader;
 
-use nes::bus::Bus;
+use nes::apu::Apu;
+use nes::bus::{Bus, CpuAccess};
 use nes::cartridge::Cartridge;
 use nes::cpu;
 use nes::cpu_registers::{CpuRegisters, Registers};
+use nes::dma::Dma;
+use nes::mapper::{self, Mapper};
+use nes::ppu::Ppu;
 use nes::ram::Ram;
-use nes::rom::Rom;
 
 fn main() {
     let cartridge = Cartridge::new("./roms/nestest.nes").unwrap();
@@ -37,33 +40,102 @@ fn main() {
         assert_eq!(p, console.cpu_registers.get_P());
         assert_eq!(sp, console.cpu_registers.get_SP());
 
-        let mut cpu_bus = Bus::new(&console.program_rom, &mut console.work_ram);
-        cpu::run(&mut console.cpu_registers, &mut cpu_bus, &mut console.nmi);
+        console.step();
         println!("{}", i);
     }
 }
 
 struct Console {
-    program_rom: Rom,
+    mapper: Box<dyn Mapper>,
     work_ram: Ram,
     cpu_registers: Registers,
+    ppu: Ppu,
+    apu: Apu,
+    dma: Dma,
+    last_access: CpuAccess,
     nmi: bool,
 }
 
 impl Console {
     fn new(cartridge: Cartridge) -> Self {
-        let program_rom = Rom::new(cartridge.program_rom);
         let work_ram = Ram::new(vec![0; 0x0800]);
         let cpu_registers = Registers::new();
+        let mapper = mapper::create_mapper(cartridge).unwrap();
         Self {
-            program_rom,
+            mapper,
             work_ram,
             cpu_registers,
+            ppu: Ppu::new(),
+            apu: Apu::new(),
+            dma: Dma::new(),
+            last_access: CpuAccess::None,
             nmi: false,
         }
     }
+    fn step(&mut self) {
+        let irq = self.mapper.irq() || self.apu.irq();
+        let mut cpu_bus = Bus::new(
+            &mut *self.mapper,
+            &mut self.work_ram,
+            &mut self.ppu,
+            &mut self.apu,
+            &mut self.dma,
+            &mut self.last_access,
+        );
+        // Includes the cycles an OAM DMA halted the CPU for.
+        let cycle = cpu::run(&mut self.cpu_registers, &mut cpu_bus, &mut self.nmi, irq);
+        let instruction_cycles = cycle - self.dma.take_transfer_cycles();
+        self.dma.add_cycles(cycle);
+        // DMC sample fetches halt the CPU on top of the cycles it spent.
+        let (mut elapsed, mut halted) = (0, 0);
+        while elapsed < cycle || halted > 0 {
+            self.step_cycle();
+            if halted > 0 {
+                halted -= 1;
+            } else {
+                elapsed += 1;
+            }
+            if let Some(addr) = self.apu.get_dmc_request() {
+                let mut cpu_bus = Bus::new(
+                    &mut *self.mapper,
+                    &mut self.work_ram,
+                    &mut self.ppu,
+                    &mut self.apu,
+                    &mut self.dma,
+                    &mut self.last_access,
+                );
+                let stall = cpu_bus.fetch_dmc_sample(
+                    addr,
+                    elapsed == instruction_cycles,
+                    elapsed > instruction_cycles,
+                );
+                self.dma.add_cycles(stall);
+                halted += stall;
+            }
+        }
+        if self.ppu.take_nmi() {
+            self.nmi = true;
+        }
+    }
+
+    fn step_cycle(&mut self) {
+        // The PPU runs three dots per CPU cycle.
+        for _ in 0..3 {
+            self.ppu.step(&mut *self.mapper);
+        }
+        self.apu.step();
+        self.mapper.on_cpu_cycle();
+    }
+
     fn reset(&mut self) {
-        let mut cpu_bus = Bus::new(&self.program_rom, &mut self.work_ram);
+        let mut cpu_bus = Bus::new(
+            &mut *self.mapper,
+            &mut self.work_ram,
+            &mut self.ppu,
+            &mut self.apu,
+            &mut self.dma,
+            &mut self.last_access,
+        );
         cpu::reset(&mut self.cpu_registers, &mut cpu_bus);
     }
 }
@@ -0,0 +1,231 @@
+pub mod action52;
+pub mod axrom;
+pub mod bandai_fcg;
+pub mod bmc_72in1;
+pub mod bnrom;
+pub mod camerica;
+pub mod cnrom;
+pub mod color_dreams;
+pub mod fme7;
+pub mod gxrom;
+pub mod irem_g101;
+pub mod irem_h3001;
+pub mod jaleco_ss88006;
+pub mod mmc1;
+pub mod mmc2;
+pub mod mmc3;
+pub mod mmc5;
+pub mod namco163;
+pub mod nina06;
+pub mod nrom;
+pub mod taito_tc0190;
+pub mod taito_x1005;
+pub mod uxrom;
+pub mod vrc;
+pub mod vrc4;
+pub mod vrc6;
+pub mod vrc7;
+
+use crate::cartridge::{Cartridge, CartridgeError, Mirroring};
+use crate::types::{Address, Byte};
+
+// A cartridge board as seen from the CPU ($4020-$FFFF) and the PPU pattern tables
+// ($0000-$1FFF). Boards that react to timing override the notification hooks.
+pub trait Mapper {
+    fn cartridge(&self) -> &Cartridge;
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge;
+
+    fn cpu_read(&mut self, addr: Address) -> Byte;
+
+    fn cpu_write(&mut self, addr: Address, data: Byte);
+
+    fn ppu_read(&mut self, addr: Address) -> Byte;
+
+    fn ppu_write(&mut self, addr: Address, data: Byte);
+
+    fn mirroring(&self) -> Mirroring {
+        self.cartridge().mirroring
+    }
+
+    fn irq(&self) -> bool {
+        false
+    }
+
+    fn on_cpu_cycle(&mut self) {}
+
+    // Called with every address the PPU drives onto its bus, before the access itself,
+    // for boards that watch A12 or count fetches.
+    fn on_ppu_address(&mut self, _addr: Address) {}
+
+    // Called for CPU writes to the PPU registers, for boards that snoop PPUCTRL/PPUMASK.
+    fn on_ppu_register_write(&mut self, _addr: Address, _data: Byte) {}
+
+    // Which CIRAM page (0 or 1) backs nametable `table` (0-3). Four-screen boards return
+    // the table itself; pages 2 and 3 are their extra 2 KiB, which the PPU holds.
+    fn ciram_page(&self, table: usize) -> usize {
+        match self.mirroring() {
+            Mirroring::Horizontal => table >> 1,
+            Mirroring::Vertical => table & 0x01,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+            Mirroring::FourScreen => table,
+        }
+    }
+
+    // Nametable reads ($2000-$2FFF) the board answers itself instead of CIRAM.
+    fn read_nametable(&mut self, _addr: Address) -> Option<Byte> {
+        None
+    }
+
+    // Returns true when the board took a nametable write away from CIRAM.
+    fn write_nametable(&mut self, _addr: Address, _data: Byte) -> bool {
+        false
+    }
+
+    // Expansion audio output, on the same 0.0-1.0 scale as the APU mixer.
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+}
+
+type Constructor = fn(Cartridge) -> Box<dyn Mapper>;
+
+// iNES mapper numbers and the boards implementing them.
+const REGISTRY: &[(u16, Constructor)] = &[
+    (0, nrom::Nrom::boxed),
+    (1, mmc1::Mmc1::boxed),
+    (2, uxrom::Uxrom::boxed),
+    (3, cnrom::Cnrom::boxed),
+    (4, mmc3::Mmc3::boxed),
+    (5, mmc5::Mmc5::boxed),
+    (7, axrom::Axrom::boxed),
+    (9, mmc2::Mmc2::boxed),
+    (10, mmc2::Mmc2::boxed_mmc4),
+    (11, color_dreams::ColorDreams::boxed),
+    (16, bandai_fcg::BandaiFcg::boxed),
+    (18, jaleco_ss88006::JalecoSs88006::boxed),
+    (19, namco163::Namco163::boxed),
+    (21, vrc4::Vrc4::boxed),
+    (22, vrc4::Vrc4::boxed),
+    (23, vrc4::Vrc4::boxed),
+    (24, vrc6::Vrc6::boxed),
+    (25, vrc4::Vrc4::boxed),
+    (26, vrc6::Vrc6::boxed),
+    (32, irem_g101::IremG101::boxed),
+    (33, taito_tc0190::TaitoTc0190::boxed),
+    (34, bnrom::Bnrom::boxed),
+    (38, gxrom::Gxrom::boxed),
+    (48, taito_tc0190::TaitoTc0190::boxed),
+    (65, irem_h3001::IremH3001::boxed),
+    (66, gxrom::Gxrom::boxed),
+    (69, fme7::Fme7::boxed),
+    (71, camerica::Camerica::boxed),
+    (79, nina06::Nina06::boxed),
+    (80, taito_x1005::TaitoX1005::boxed),
+    (85, vrc7::Vrc7::boxed),
+    (113, nina06::Nina06::boxed),
+    (140, gxrom::Gxrom::boxed),
+    (146, nina06::Nina06::boxed),
+    (153, bandai_fcg::BandaiFcg::boxed),
+    (159, bandai_fcg::BandaiFcg::boxed),
+    (225, bmc_72in1::Bmc72In1::boxed),
+    (228, action52::Action52::boxed),
+    (232, camerica::Camerica::boxed),
+];
+
+pub fn create_mapper(cartridge: Cartridge) -> Result<Box<dyn Mapper>, CartridgeError> {
+    match REGISTRY
+        .iter()
+        .find(|&&(number, _)| number == cartridge.mapper)
+    {
+        Some(&(_, constructor)) => Ok(constructor(cartridge)),
+        None => Err(CartridgeError::UnsupportedMapper(cartridge.mapper)),
+    }
+}
+
+// Offset into ROM/RAM of `addr` inside a switchable window of `bank_size` bytes.
+pub fn bank_address(bank: usize, bank_size: usize, addr: Address) -> usize {
+    bank * bank_size + (addr as usize & (bank_size - 1))
+}
+
+// Discrete-logic boards without write protection see the value on the data bus ANDed
+// with the ROM byte that is driven at the same address.
+pub fn bus_conflict(cartridge: &Cartridge, rom_addr: usize, data: Byte) -> Byte {
+    data & cartridge.read_program_rom(rom_addr)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::apu::Apu;
+    use crate::bus::{Bus, CpuAccess, CpuBus};
+    use crate::dma::Dma;
+    use crate::ppu::Ppu;
+    use crate::ram::Ram;
+
+    pub fn cartridge(mapper: u16, program_banks: usize, character_banks: usize) -> Cartridge {
+        let mut image = b"NES\x1A".to_vec();
+        image.extend_from_slice(&[
+            program_banks as u8,
+            character_banks as u8,
+            ((mapper & 0x0F) << 4) as u8,
+            (mapper & 0xF0) as u8,
+        ]);
+        image.extend_from_slice(&[0; 8]);
+        // Every 1 KiB of PRG and CHR is filled with its own little-endian index so tests
+        // can identify banks with `prg_index`/`chr_index`.
+        for i in (0..program_banks * 16).chain(0..character_banks * 8) {
+            for _ in 0..0x200 {
+                image.extend_from_slice(&[i as u8, (i >> 8) as u8]);
+            }
+        }
+        Cartridge::from_bytes(&image).unwrap()
+    }
+
+    // Index of the 1 KiB PRG ROM page mapped at `addr`.
+    pub fn prg_index(mapper: &mut dyn Mapper, addr: Address) -> usize {
+        let addr = addr & !1;
+        mapper.cpu_read(addr) as usize | (mapper.cpu_read(addr + 1) as usize) << 8
+    }
+
+    // Index of the 1 KiB CHR page mapped at `addr`.
+    pub fn chr_index(mapper: &mut dyn Mapper, addr: Address) -> usize {
+        let addr = addr & !1;
+        mapper.ppu_read(addr) as usize | (mapper.ppu_read(addr + 1) as usize) << 8
+    }
+
+    // Writes through the CPU bus the way a program switches banks.
+    pub fn bus_write(mapper: &mut dyn Mapper, addr: Address, data: Byte) {
+        let mut work_ram = Ram::new(vec![0; 0x800]);
+        let mut ppu = Ppu::new();
+        let mut apu = Apu::new();
+        let mut dma = Dma::new();
+        let mut last_access = CpuAccess::None;
+        Bus::new(
+            mapper,
+            &mut work_ram,
+            &mut ppu,
+            &mut apu,
+            &mut dma,
+            &mut last_access,
+        )
+        .write(addr, data);
+    }
+
+    #[test]
+    fn test_create_nrom() {
+        let mut mapper = create_mapper(cartridge(0, 1, 1)).unwrap();
+        assert_eq!(prg_index(&mut *mapper, 0x8000), 0);
+        assert_eq!(prg_index(&mut *mapper, 0xC400), 1);
+        assert_eq!(chr_index(&mut *mapper, 0x1C00), 7);
+    }
+
+    #[test]
+    fn test_unsupported_mapper() {
+        match create_mapper(cartridge(0xFF, 1, 1)) {
+            Err(CartridgeError::UnsupportedMapper(mapper)) => assert_eq!(mapper, 0xFF),
+            _ => panic!("expected UnsupportedMapper"),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mapper 228: Active Enterprises (Action 52, Cheetahmen II). Writes to $8000-$FFFF latch
+// the address: A13 mirroring, A12-A11 PRG chip, A10-A6 PRG page, A5 16 KiB mode and
+// A3-A0 plus data bits 0-1 the CHR bank. Chip 2 is not populated, so chip 3 follows
+// chip 1 in the ROM image.
+pub struct Action52 {
+    cartridge: Cartridge,
+    program_chip: usize,
+    program_page: usize,
+    is_16k_mode: bool,
+    character_bank: usize,
+    mirroring: Mirroring,
+    // Four 4-bit registers at $4020-$5FFF.
+    nibbles: [Byte; 4],
+}
+
+impl Action52 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Action52 {
+            cartridge,
+            program_chip: 0,
+            program_page: 0,
+            is_16k_mode: false,
+            character_bank: 0,
+            mirroring: Mirroring::Vertical,
+            nibbles: [0; 4],
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Action52::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> Option<usize> {
+        let chip = match self.program_chip {
+            2 => return None,
+            3 => 2,
+            chip => chip,
+        };
+        let bank = chip << 5 | self.program_page;
+        let bank = match (self.is_16k_mode, addr) {
+            (true, _) => bank,
+            (false, 0x8000..=0xBFFF) => bank & !0x01,
+            (false, _) => bank | 0x01,
+        };
+        Some(bank_address(bank, 0x4000, addr))
+    }
+}
+
+impl Mapper for Action52 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x4020..=0x5FFF => self.nibbles[(addr & 0x03) as usize],
+            // The missing chip leaves the bus open, approximated by 0.
+            0x8000..=0xFFFF => match self.program_rom_address(addr) {
+                Some(addr) => self.cartridge.read_program_rom(addr),
+                None => 0,
+            },
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x4020..=0x5FFF => self.nibbles[(addr & 0x03) as usize] = data & 0x0F,
+            0x8000..=0xFFFF => {
+                self.mirroring = if addr & 0x2000 == 0x2000 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+                self.program_chip = ((addr >> 11) & 0x03) as usize;
+                self.program_page = ((addr >> 6) & 0x1F) as usize;
+                self.is_16k_mode = addr & 0x20 == 0x20;
+                self.character_bank = ((addr & 0x0F) << 2) as usize | (data & 0x03) as usize;
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.read_character(addr)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_address_latch_through_bus() {
+        let mut mapper = Action52::new(cartridge(228, 96, 64));
+        // Chip 1, page 5, 16 KiB mode, CHR high bits 3, horizontal mirroring.
+        bus_write(
+            &mut mapper,
+            0x8000 | 0x2000 | 1 << 11 | 5 << 6 | 0x20 | 0x03,
+            0x02,
+        );
+        assert_eq!(prg_index(&mut mapper, 0x8000), (32 + 5) * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), (32 + 5) * 16);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 14 * 8);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+
+        // Chip 3 in 32 KiB mode reads the third 512 KiB of the image.
+        bus_write(&mut mapper, 0x8000 | 3 << 11 | 5 << 6, 0);
+        assert_eq!(prg_index(&mut mapper, 0x8000), (64 + 4) * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), (64 + 5) * 16);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+
+        bus_write(&mut mapper, 0x8000 | 2 << 11, 0);
+        assert_eq!(mapper.cpu_read(0x8000), 0);
+    }
+
+    #[test]
+    fn test_nibble_ram() {
+        let mut mapper = Action52::new(cartridge(228, 32, 8));
+        bus_write(&mut mapper, 0x4021, 0xAB);
+        assert_eq!(mapper.cpu_read(0x5FF5), 0x0B);
+    }
+}
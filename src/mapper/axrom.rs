@@ -0,0 +1,94 @@
+use super::{bank_address, bus_conflict, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mapper 7: a switchable 32 KiB PRG bank and single-screen mirroring selected by bit 4.
+pub struct Axrom {
+    cartridge: Cartridge,
+    program_bank: usize,
+    mirroring: Mirroring,
+    has_bus_conflicts: bool,
+}
+
+impl Axrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        // Only AOROM (NES 2.0 submapper 2) has bus conflicts; ANROM and AMROM do not rely on them.
+        let has_bus_conflicts = cartridge.submapper == 2;
+        Axrom {
+            cartridge,
+            program_bank: 0,
+            mirroring: Mirroring::SingleScreenLower,
+            has_bus_conflicts,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Axrom::new(cartridge))
+    }
+}
+
+impl Mapper for Axrom {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => {
+                let addr = bank_address(self.program_bank, 0x8000, addr);
+                self.cartridge.read_program_rom(addr)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        if let 0x8000..=0xFFFF = addr {
+            let data = if self.has_bus_conflicts {
+                let rom_addr = bank_address(self.program_bank, 0x8000, addr);
+                bus_conflict(&self.cartridge, rom_addr, data)
+            } else {
+                data
+            };
+            self.program_bank = (data & 0x07) as usize;
+            self.mirroring = if data & 0x10 == 0x10 {
+                Mirroring::SingleScreenUpper
+            } else {
+                Mirroring::SingleScreenLower
+            };
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        self.cartridge.write_character(addr as usize, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_program_bank_and_mirroring() {
+        let mut mapper = Axrom::new(cartridge(7, 16, 0));
+        mapper.cpu_write(0x8000, 0x13);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 32);
+        assert_eq!(prg_index(&mut mapper, 0xFC00), 3 * 32 + 31);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenUpper);
+        mapper.cpu_write(0x8000, 0x01);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenLower);
+    }
+}
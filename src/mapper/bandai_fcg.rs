@@ -0,0 +1,466 @@
+use super::vrc;
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EepromPhase {
+    Idle,
+    Device,
+    Address,
+    Write,
+    Read,
+}
+
+// The serial EEPROM on LZ93D50 boards: a 24C02 (256 bytes, I2C with a device byte, MSB
+// first) or a 24C01 (128 bytes, whose first byte is the address and R/W bit, LSB first).
+// The contents live in the cartridge PRG RAM so they are saved like battery RAM.
+struct Eeprom {
+    size: usize,
+    is_24c01: bool,
+    phase: EepromPhase,
+    next_phase: EepromPhase,
+    shift: Byte,
+    bits: u8,
+    address: usize,
+    scl: bool,
+    sda: bool,
+    // The level the EEPROM drives on SDA; high means released.
+    output: bool,
+}
+
+impl Eeprom {
+    fn new(is_24c01: bool) -> Self {
+        Eeprom {
+            size: if is_24c01 { 0x80 } else { 0x100 },
+            is_24c01,
+            phase: EepromPhase::Idle,
+            next_phase: EepromPhase::Idle,
+            shift: 0,
+            bits: 0,
+            address: 0,
+            scl: false,
+            sda: false,
+            output: true,
+        }
+    }
+
+    fn write(&mut self, scl: bool, sda: bool, cartridge: &mut Cartridge) {
+        if self.scl && scl && self.sda != sda {
+            // SDA falling while SCL is high is a start condition, rising is a stop.
+            self.phase = if sda {
+                EepromPhase::Idle
+            } else if self.is_24c01 {
+                EepromPhase::Address
+            } else {
+                EepromPhase::Device
+            };
+            self.bits = 0;
+            self.shift = 0;
+            self.output = true;
+        } else if !self.scl && scl {
+            self.rise(sda, cartridge);
+        } else if self.scl && !scl {
+            self.fall(cartridge);
+        }
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    fn rise(&mut self, sda: bool, cartridge: &mut Cartridge) {
+        match (self.phase, self.bits) {
+            (EepromPhase::Idle, _) => (),
+            // The ninth clock of a read carries the master's acknowledge.
+            (EepromPhase::Read, 8) => {
+                if sda {
+                    self.phase = EepromPhase::Idle;
+                } else {
+                    self.address = (self.address + 1) % self.size;
+                    self.bits = 0;
+                }
+            }
+            (EepromPhase::Read, _) => self.bits += 1,
+            (_, 8) => {
+                self.phase = self.next_phase;
+                self.bits = 0;
+                self.shift = 0;
+            }
+            (_, _) => {
+                if self.is_24c01 {
+                    self.shift |= (sda as Byte) << self.bits;
+                } else {
+                    self.shift = self.shift << 1 | sda as Byte;
+                }
+                self.bits += 1;
+                if self.bits == 8 {
+                    self.receive(cartridge);
+                }
+            }
+        }
+    }
+
+    fn receive(&mut self, cartridge: &mut Cartridge) {
+        let data = self.shift;
+        self.next_phase = match self.phase {
+            EepromPhase::Device if data & 0xF0 != 0xA0 => EepromPhase::Idle,
+            EepromPhase::Device if data & 0x01 == 0x01 => EepromPhase::Read,
+            EepromPhase::Device => EepromPhase::Address,
+            EepromPhase::Address if self.is_24c01 => {
+                self.address = (data & 0x7F) as usize;
+                if data & 0x80 == 0x80 {
+                    EepromPhase::Read
+                } else {
+                    EepromPhase::Write
+                }
+            }
+            EepromPhase::Address => {
+                self.address = data as usize;
+                EepromPhase::Write
+            }
+            _ => {
+                cartridge.write_program_ram(self.address, data);
+                self.address = (self.address + 1) % self.size;
+                EepromPhase::Write
+            }
+        };
+    }
+
+    fn fall(&mut self, cartridge: &Cartridge) {
+        self.output = match (self.phase, self.bits) {
+            (EepromPhase::Read, 0..=7) => {
+                let data = cartridge.read_program_ram(self.address);
+                let bit = if self.is_24c01 {
+                    self.bits
+                } else {
+                    7 - self.bits
+                };
+                (data >> bit) & 0x01 == 0x01
+            }
+            (EepromPhase::Idle, _) | (EepromPhase::Read, _) => true,
+            // Acknowledge a received byte by pulling SDA low.
+            (_, 8) => self.next_phase == EepromPhase::Idle,
+            _ => true,
+        };
+    }
+}
+
+// Mappers 16, 153 and 159: Bandai FCG-1/2 and LZ93D50. Submapper 4 of mapper 16 is the
+// FCG-1/2 with registers at $6000, submapper 5 the LZ93D50 with a 24C02; otherwise both
+// register ranges are decoded. Mapper 153 uses SRAM and an outer 256 KiB PRG bank, and
+// mapper 159 has a 24C01.
+pub struct BandaiFcg {
+    cartridge: Cartridge,
+    register_ranges: (bool, bool),
+    // FCG-1/2 load the counter directly; the LZ93D50 writes a latch copied on enable.
+    has_latch: bool,
+    character_banks: [Byte; 8],
+    program_bank: Byte,
+    outer_bank: Byte,
+    mirroring: Mirroring,
+    irq_enabled: bool,
+    irq_counter: u16,
+    irq_latch: u16,
+    irq_pending: bool,
+    eeprom: Option<Eeprom>,
+    is_sram_board: bool,
+    sram_enabled: bool,
+}
+
+impl BandaiFcg {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let (register_ranges, has_latch, eeprom) = match (cartridge.mapper, cartridge.submapper) {
+            (16, 4) => ((true, false), false, None),
+            (16, 5) => ((false, true), true, Some(Eeprom::new(false))),
+            (16, _) => ((true, true), true, Some(Eeprom::new(false))),
+            (159, _) => ((false, true), true, Some(Eeprom::new(true))),
+            _ => ((false, true), true, None),
+        };
+        let is_sram_board = cartridge.mapper == 153;
+        let mirroring = cartridge.mirroring;
+        BandaiFcg {
+            cartridge,
+            register_ranges,
+            has_latch,
+            character_banks: [0; 8],
+            program_bank: 0,
+            outer_bank: 0,
+            mirroring,
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq_pending: false,
+            eeprom,
+            is_sram_board,
+            sram_enabled: false,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(BandaiFcg::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let outer = (self.outer_bank as usize) << 4;
+        let bank = match addr {
+            0x8000..=0xBFFF => outer | (self.program_bank & 0x0F) as usize,
+            _ => outer | 0x0F.min((self.cartridge.program_rom.len() / 0x4000).max(1) - 1),
+        };
+        bank_address(bank, 0x4000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        if self.is_sram_board {
+            return addr as usize;
+        }
+        let bank = self.character_banks[(addr >> 10) as usize & 0x07];
+        bank_address(bank as usize, 0x400, addr)
+    }
+
+    fn write_register(&mut self, addr: Address, data: Byte) {
+        match addr & 0x0F {
+            register @ 0x0..=0x7 => {
+                self.character_banks[register as usize] = data;
+                if self.is_sram_board && register < 4 {
+                    self.outer_bank = data & 0x01;
+                }
+            }
+            0x8 => self.program_bank = data,
+            0x9 => self.mirroring = vrc::mirroring(data),
+            0xA => {
+                self.irq_enabled = data & 0x01 == 0x01;
+                self.irq_pending = false;
+                if self.has_latch {
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            0xB | 0xC => {
+                let shift = (addr & 0x0F) - 0xB;
+                let target = if self.has_latch {
+                    &mut self.irq_latch
+                } else {
+                    &mut self.irq_counter
+                };
+                *target = (*target & !(0xFF << (shift * 8))) | (data as u16) << (shift * 8);
+            }
+            0xD if self.is_sram_board => self.sram_enabled = data & 0x20 == 0x20,
+            0xD => {
+                if let Some(eeprom) = self.eeprom.as_mut() {
+                    // Bit 7 releases SDA so the EEPROM can drive it.
+                    let sda = data & 0xC0 != 0;
+                    eeprom.write(data & 0x20 == 0x20, sda, &mut self.cartridge);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Mapper for BandaiFcg {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.is_sram_board && self.sram_enabled => {
+                self.cartridge.read_program_ram((addr - 0x6000) as usize)
+            }
+            0x6000..=0x7FFF => match self.eeprom.as_ref() {
+                Some(eeprom) => (eeprom.output as Byte) << 4,
+                None => 0,
+            },
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.is_sram_board && self.sram_enabled => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x6000..=0x7FFF if self.register_ranges.0 => self.write_register(addr, data),
+            0x8000..=0xFFFF if self.register_ranges.1 => self.write_register(addr, data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn on_cpu_cycle(&mut self) {
+        if self.irq_enabled {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0 {
+                self.irq_pending = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    fn mapper(number: u16, submapper: u8) -> BandaiFcg {
+        let mut cartridge = cartridge(number, 32, 8);
+        cartridge.submapper = submapper;
+        BandaiFcg::new(cartridge)
+    }
+
+    // Bit-bangs the $800D port like the games do.
+    fn set_lines(mapper: &mut BandaiFcg, scl: bool, sda: bool) {
+        mapper.cpu_write(0x800D, (scl as Byte) << 5 | (sda as Byte) << 6);
+    }
+
+    fn start(mapper: &mut BandaiFcg) {
+        set_lines(mapper, false, true);
+        set_lines(mapper, true, true);
+        set_lines(mapper, true, false);
+        set_lines(mapper, false, false);
+    }
+
+    fn stop(mapper: &mut BandaiFcg) {
+        set_lines(mapper, false, false);
+        set_lines(mapper, true, false);
+        set_lines(mapper, true, true);
+    }
+
+    fn send_bit(mapper: &mut BandaiFcg, bit: bool) {
+        set_lines(mapper, false, bit);
+        set_lines(mapper, true, bit);
+        set_lines(mapper, false, bit);
+    }
+
+    // Sends a byte and returns whether the EEPROM acknowledged it.
+    fn send_byte(mapper: &mut BandaiFcg, data: Byte, lsb_first: bool) -> bool {
+        for i in 0..8 {
+            let bit = if lsb_first { i } else { 7 - i };
+            send_bit(mapper, (data >> bit) & 0x01 == 0x01);
+        }
+        let ack = mapper.cpu_read(0x6000) & 0x10 == 0;
+        send_bit(mapper, true);
+        ack
+    }
+
+    fn receive_byte(mapper: &mut BandaiFcg, lsb_first: bool, last: bool) -> Byte {
+        let mut data = 0;
+        for i in 0..8 {
+            let bit = (mapper.cpu_read(0x6000) >> 4) & 0x01;
+            data |= bit << if lsb_first { i } else { 7 - i };
+            mapper.cpu_write(0x800D, 0xA0);
+            mapper.cpu_write(0x800D, 0x80);
+        }
+        send_bit(mapper, last);
+        data
+    }
+
+    #[test]
+    fn test_banking_and_irq() {
+        let mut mapper = mapper(16, 4);
+        mapper.cpu_write(0x6008, 3);
+        mapper.cpu_write(0x6002, 30);
+        mapper.cpu_write(0x6009, 1);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 15 * 16);
+        assert_eq!(chr_index(&mut mapper, 0x0800), 30);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+        // FCG-1/2 ignore $8000 writes.
+        mapper.cpu_write(0x8008, 5);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 16);
+
+        mapper.cpu_write(0x600B, 2);
+        mapper.cpu_write(0x600C, 0);
+        mapper.cpu_write(0x600A, 1);
+        mapper.on_cpu_cycle();
+        assert!(!mapper.irq());
+        mapper.on_cpu_cycle();
+        assert!(mapper.irq());
+        mapper.cpu_write(0x600A, 0);
+        assert!(!mapper.irq());
+    }
+
+    #[test]
+    fn test_lz93d50_irq_latch() {
+        let mut mapper = mapper(16, 5);
+        mapper.cpu_write(0x800B, 1);
+        mapper.cpu_write(0x800C, 0);
+        mapper.on_cpu_cycle();
+        assert!(!mapper.irq());
+        mapper.cpu_write(0x800A, 1);
+        mapper.on_cpu_cycle();
+        assert!(mapper.irq());
+    }
+
+    #[test]
+    fn test_24c02_write_and_random_read() {
+        let mut mapper = mapper(16, 5);
+        start(&mut mapper);
+        assert!(send_byte(&mut mapper, 0xA0, false));
+        assert!(send_byte(&mut mapper, 0x10, false));
+        assert!(send_byte(&mut mapper, 0x5A, false));
+        assert!(send_byte(&mut mapper, 0xC3, false));
+        stop(&mut mapper);
+        assert_eq!(mapper.cartridge().program_ram[0x10..0x12], [0x5A, 0xC3]);
+
+        start(&mut mapper);
+        send_byte(&mut mapper, 0xA0, false);
+        send_byte(&mut mapper, 0x10, false);
+        start(&mut mapper);
+        send_byte(&mut mapper, 0xA1, false);
+        assert_eq!(receive_byte(&mut mapper, false, false), 0x5A);
+        assert_eq!(receive_byte(&mut mapper, false, true), 0xC3);
+        stop(&mut mapper);
+    }
+
+    #[test]
+    fn test_24c01_lsb_first() {
+        let mut mapper = mapper(159, 0);
+        start(&mut mapper);
+        assert!(send_byte(&mut mapper, 0x05, true));
+        assert!(send_byte(&mut mapper, 0x81, true));
+        stop(&mut mapper);
+        assert_eq!(mapper.cartridge().program_ram[0x05], 0x81);
+
+        start(&mut mapper);
+        send_byte(&mut mapper, 0x85, true);
+        assert_eq!(receive_byte(&mut mapper, true, true), 0x81);
+        stop(&mut mapper);
+    }
+
+    #[test]
+    fn test_mapper_153_outer_bank_and_sram() {
+        let mut mapper = mapper(153, 0);
+        mapper.cpu_write(0x8000, 1);
+        mapper.cpu_write(0x8008, 2);
+        assert_eq!(prg_index(&mut mapper, 0x8000), (16 + 2) * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), (16 + 15) * 16);
+
+        mapper.cpu_write(0x6000, 0x33);
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+        mapper.cpu_write(0x800D, 0x20);
+        mapper.cpu_write(0x6000, 0x33);
+        assert_eq!(mapper.cpu_read(0x6000), 0x33);
+    }
+}
@@ -0,0 +1,124 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mapper 225: 52/64/72-in-1 multicarts. Writes to $8000-$FFFF latch the address: A14
+// selects the upper half of a 2 MiB board, A13 mirroring, A12 16 KiB mode, A11-A6 the
+// PRG bank and A5-A0 the CHR bank.
+pub struct Bmc72In1 {
+    cartridge: Cartridge,
+    program_bank: usize,
+    is_16k_mode: bool,
+    character_bank: usize,
+    mirroring: Mirroring,
+    // Four 4-bit registers at $5800-$5FFF.
+    nibbles: [Byte; 4],
+}
+
+impl Bmc72In1 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Bmc72In1 {
+            cartridge,
+            program_bank: 0,
+            is_16k_mode: false,
+            character_bank: 0,
+            mirroring: Mirroring::Vertical,
+            nibbles: [0; 4],
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Bmc72In1::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = match (self.is_16k_mode, addr) {
+            (true, _) => self.program_bank,
+            (false, 0x8000..=0xBFFF) => self.program_bank & !0x01,
+            (false, _) => self.program_bank | 0x01,
+        };
+        bank_address(bank, 0x4000, addr)
+    }
+}
+
+impl Mapper for Bmc72In1 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x5800..=0x5FFF => self.nibbles[(addr & 0x03) as usize],
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x5800..=0x5FFF => self.nibbles[(addr & 0x03) as usize] = data & 0x0F,
+            0x8000..=0xFFFF => {
+                let high = ((addr >> 8) & 0x40) as usize;
+                self.mirroring = if addr & 0x2000 == 0x2000 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+                self.is_16k_mode = addr & 0x1000 == 0x1000;
+                self.program_bank = high | ((addr >> 6) & 0x3F) as usize;
+                self.character_bank = high | (addr & 0x3F) as usize;
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.read_character(addr)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_address_latch_through_bus() {
+        let mut mapper = Bmc72In1::new(cartridge(225, 128, 128));
+        // Upper half, 16 KiB mode, PRG bank 9 and CHR bank 3.
+        bus_write(&mut mapper, 0xC000 | 0x1000 | 9 << 6 | 3, 0);
+        assert_eq!(prg_index(&mut mapper, 0x8000), (64 + 9) * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), (64 + 9) * 16);
+        assert_eq!(chr_index(&mut mapper, 0x0000), (64 + 3) * 8);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+
+        bus_write(&mut mapper, 0x8000 | 0x2000 | 9 << 6, 0);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 8 * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 9 * 16);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn test_nibble_ram() {
+        let mut mapper = Bmc72In1::new(cartridge(225, 32, 8));
+        bus_write(&mut mapper, 0x5802, 0x5C);
+        assert_eq!(mapper.cpu_read(0x5FFE), 0x0C);
+        assert_eq!(mapper.cpu_read(0x5800), 0);
+    }
+}
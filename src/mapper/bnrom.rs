@@ -0,0 +1,126 @@
+use super::{bank_address, bus_conflict, Mapper};
+use crate::cartridge::Cartridge;
+use crate::types::{Address, Byte};
+
+// Mapper 34: BNROM (a 32 KiB PRG bank at $8000-$FFFF with bus conflicts) and AVE
+// NINA-001 (registers at $7FFD-$7FFF for PRG and two 4 KiB CHR banks). NES 2.0 tells
+// them apart with submappers 2 and 1; otherwise boards with CHR ROM over 8 KiB are
+// NINA-001.
+pub struct Bnrom {
+    cartridge: Cartridge,
+    is_nina001: bool,
+    program_bank: usize,
+    character_banks: [usize; 2],
+}
+
+impl Bnrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let is_nina001 = match cartridge.submapper {
+            1 => true,
+            2 => false,
+            _ => cartridge.character_rom.len() > 0x2000,
+        };
+        Bnrom {
+            cartridge,
+            is_nina001,
+            program_bank: 0,
+            character_banks: [0, 1],
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Bnrom::new(cartridge))
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        if self.is_nina001 {
+            bank_address(
+                self.character_banks[(addr >> 12) as usize & 0x01],
+                0x1000,
+                addr,
+            )
+        } else {
+            addr as usize
+        }
+    }
+}
+
+impl Mapper for Bnrom {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.read_program_ram((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => {
+                let addr = bank_address(self.program_bank, 0x8000, addr);
+                self.cartridge.read_program_rom(addr)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            // The NINA-001 registers sit on top of the RAM, which still takes the write.
+            0x6000..=0x7FFF => {
+                self.cartridge
+                    .write_program_ram((addr - 0x6000) as usize, data);
+                if self.is_nina001 {
+                    match addr {
+                        0x7FFD => self.program_bank = (data & 0x01) as usize,
+                        0x7FFE => self.character_banks[0] = (data & 0x0F) as usize,
+                        0x7FFF => self.character_banks[1] = (data & 0x0F) as usize,
+                        _ => (),
+                    }
+                }
+            }
+            0x8000..=0xFFFF if !self.is_nina001 => {
+                let rom_addr = bank_address(self.program_bank, 0x8000, addr);
+                self.program_bank = bus_conflict(&self.cartridge, rom_addr, data) as usize;
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_bnrom_through_bus() {
+        let mut mapper = Bnrom::new(cartridge(34, 8, 0));
+        mapper.cartridge_mut().program_rom[0x7FFF] = 0xFF;
+        bus_write(&mut mapper, 0xFFFF, 3);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 32);
+        assert_eq!(prg_index(&mut mapper, 0xFC00), 3 * 32 + 31);
+    }
+
+    #[test]
+    fn test_nina001_through_bus() {
+        let mut mapper = Bnrom::new(cartridge(34, 4, 4));
+        bus_write(&mut mapper, 0x7FFD, 1);
+        bus_write(&mut mapper, 0x7FFE, 5);
+        bus_write(&mut mapper, 0x7FFF, 2);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 32);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 5 * 4);
+        assert_eq!(chr_index(&mut mapper, 0x1000), 2 * 4);
+        assert_eq!(mapper.cpu_read(0x7FFE), 5);
+    }
+}
@@ -0,0 +1,150 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mappers 71 and 232: Camerica/Codemasters BF9093 and BF9096. Both switch a 16 KiB PRG
+// bank at $8000 with the last bank fixed at $C000; the BF9096 (Quattro multicarts)
+// adds a 64 KiB outer bank. Submapper 1 of mapper 71 is Fire Hawk, whose board
+// selects single-screen mirroring at $8000-$9FFF.
+pub struct Camerica {
+    cartridge: Cartridge,
+    is_quattro: bool,
+    has_mirroring_control: bool,
+    // The Aladdin Deck Enhancer (mapper 232 submapper 1) swaps the outer bank bits.
+    is_aladdin: bool,
+    outer_bank: usize,
+    program_bank: usize,
+    mirroring: Mirroring,
+}
+
+impl Camerica {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let is_quattro = cartridge.mapper == 232;
+        let has_mirroring_control = !is_quattro && cartridge.submapper == 1;
+        let is_aladdin = is_quattro && cartridge.submapper == 1;
+        let mirroring = cartridge.mirroring;
+        Camerica {
+            cartridge,
+            is_quattro,
+            has_mirroring_control,
+            is_aladdin,
+            outer_bank: 0,
+            program_bank: 0,
+            mirroring,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Camerica::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = match (addr, self.is_quattro) {
+            (0x8000..=0xBFFF, false) => self.program_bank,
+            (_, false) => (self.cartridge.program_rom.len() / 0x4000).max(1) - 1,
+            (0x8000..=0xBFFF, true) => self.outer_bank << 2 | self.program_bank,
+            (_, true) => self.outer_bank << 2 | 0x03,
+        };
+        bank_address(bank, 0x4000, addr)
+    }
+}
+
+impl Mapper for Camerica {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match (addr, self.is_quattro) {
+            (0x8000..=0x9FFF, false) if self.has_mirroring_control => {
+                self.mirroring = if data & 0x10 == 0x10 {
+                    Mirroring::SingleScreenUpper
+                } else {
+                    Mirroring::SingleScreenLower
+                };
+            }
+            (0xC000..=0xFFFF, false) => self.program_bank = (data & 0x0F) as usize,
+            (0x8000..=0xBFFF, true) => {
+                let outer = (data >> 3) & 0x03;
+                self.outer_bank = if self.is_aladdin {
+                    (outer >> 1 | outer << 1) & 0x03
+                } else {
+                    outer
+                } as usize;
+            }
+            (0xC000..=0xFFFF, true) => self.program_bank = (data & 0x03) as usize,
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        self.cartridge.write_character(addr as usize, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    fn camerica(number: u16, submapper: u8) -> Camerica {
+        let mut cartridge = cartridge(number, 16, 0);
+        cartridge.submapper = submapper;
+        Camerica::new(cartridge)
+    }
+
+    #[test]
+    fn test_bf9093_through_bus() {
+        let mut mapper = camerica(71, 0);
+        bus_write(&mut mapper, 0xC000, 5);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 5 * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 15 * 16);
+        // Without the Fire Hawk submapper the mirroring is fixed.
+        let mirroring = mapper.mirroring();
+        bus_write(&mut mapper, 0x9000, 0x10);
+        assert_eq!(mapper.mirroring(), mirroring);
+    }
+
+    #[test]
+    fn test_fire_hawk_mirroring_through_bus() {
+        let mut mapper = camerica(71, 1);
+        bus_write(&mut mapper, 0x9000, 0x10);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenUpper);
+        bus_write(&mut mapper, 0x9000, 0x00);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenLower);
+    }
+
+    #[test]
+    fn test_quattro_through_bus() {
+        let mut mapper = camerica(232, 0);
+        bus_write(&mut mapper, 0x8000, 0x10);
+        bus_write(&mut mapper, 0xC000, 0x01);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 9 * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 11 * 16);
+
+        let mut mapper = camerica(232, 1);
+        bus_write(&mut mapper, 0x8000, 0x10);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 7 * 16);
+    }
+}
@@ -0,0 +1,97 @@
+use super::{bank_address, bus_conflict, Mapper};
+use crate::cartridge::Cartridge;
+use crate::types::{Address, Byte};
+
+// Mapper 3: fixed PRG ROM like NROM with a switchable 8 KiB CHR ROM bank.
+pub struct Cnrom {
+    cartridge: Cartridge,
+    character_bank: usize,
+    has_bus_conflicts: bool,
+}
+
+impl Cnrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        // NES 2.0 submapper 1 marks boards without bus conflicts.
+        let has_bus_conflicts = cartridge.submapper != 1;
+        Cnrom {
+            cartridge,
+            character_bank: 0,
+            has_bus_conflicts,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Cnrom::new(cartridge))
+    }
+}
+
+impl Mapper for Cnrom {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.read_program_ram((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => self.cartridge.read_program_rom((addr - 0x8000) as usize),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000..=0xFFFF => {
+                let data = if self.has_bus_conflicts {
+                    bus_conflict(&self.cartridge, (addr - 0x8000) as usize, data)
+                } else {
+                    data
+                };
+                self.character_bank = data as usize;
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.read_character(addr)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.write_character(addr, data);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_character_bank_switch() {
+        let mut mapper = Cnrom::new(cartridge(3, 2, 4));
+        mapper.cartridge_mut().program_rom[0x7FFF] = 0xFF;
+        mapper.cpu_write(0xFFFF, 2);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 2 * 8);
+        assert_eq!(chr_index(&mut mapper, 0x1C00), 2 * 8 + 7);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 16);
+    }
+
+    #[test]
+    fn test_bus_conflict() {
+        let mut mapper = Cnrom::new(cartridge(3, 2, 4));
+        // PRG 1 KiB page 3 is filled with 0x03/0x00, so only bit 0 and 1 survive.
+        mapper.cpu_write(0x8C00, 0x02);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 2 * 8);
+        mapper.cpu_write(0x8C01, 0x03);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 0);
+    }
+}
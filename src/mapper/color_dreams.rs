@@ -0,0 +1,79 @@
+use super::{bank_address, bus_conflict, Mapper};
+use crate::cartridge::Cartridge;
+use crate::types::{Address, Byte};
+
+// Mapper 11: Color Dreams. One register selects a 32 KiB PRG bank (bits 0-1) and an
+// 8 KiB CHR bank (bits 4-7).
+pub struct ColorDreams {
+    cartridge: Cartridge,
+    program_bank: usize,
+    character_bank: usize,
+}
+
+impl ColorDreams {
+    pub fn new(cartridge: Cartridge) -> Self {
+        ColorDreams {
+            cartridge,
+            program_bank: 0,
+            character_bank: 0,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(ColorDreams::new(cartridge))
+    }
+}
+
+impl Mapper for ColorDreams {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => {
+                let addr = bank_address(self.program_bank, 0x8000, addr);
+                self.cartridge.read_program_rom(addr)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        if let 0x8000..=0xFFFF = addr {
+            let rom_addr = bank_address(self.program_bank, 0x8000, addr);
+            let data = bus_conflict(&self.cartridge, rom_addr, data);
+            self.program_bank = (data & 0x03) as usize;
+            self.character_bank = (data >> 4) as usize;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.read_character(addr)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.write_character(addr, data);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_bank_switch_through_bus() {
+        let mut mapper = ColorDreams::new(cartridge(11, 8, 16));
+        mapper.cartridge_mut().program_rom[0x7FFF] = 0xFF;
+        bus_write(&mut mapper, 0xFFFF, 0x52);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 2 * 32);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 5 * 8);
+    }
+}
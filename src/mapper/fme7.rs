@@ -0,0 +1,372 @@
+use super::vrc;
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// The 5B timers run from the CPU clock divided by 16.
+const AUDIO_PRESCALER: u8 = 16;
+
+// Output level of one 5B channel at full volume, on the APU mixer scale.
+const CHANNEL_LEVEL: f32 = 0.08;
+
+// Sunsoft 5B audio: a YM2149F (AY-3-8910) with three square channels, a noise generator
+// and a 32-step envelope, volumes in 1.5 dB steps.
+#[derive(Default)]
+struct Sunsoft5b {
+    address: Byte,
+    registers: [Byte; 16],
+    prescaler: u8,
+    tone_timers: [u16; 3],
+    tone_outputs: [bool; 3],
+    noise_timer: u8,
+    noise_shift: u32,
+    envelope_timer: u16,
+    envelope_step: u8,
+    envelope_attack: bool,
+    envelope_holding: bool,
+}
+
+impl Sunsoft5b {
+    fn new() -> Self {
+        Sunsoft5b {
+            noise_shift: 1,
+            ..Sunsoft5b::default()
+        }
+    }
+
+    fn write(&mut self, data: Byte) {
+        if self.address >= 0x10 {
+            return;
+        }
+        self.registers[self.address as usize] = data;
+        if self.address == 0x0D {
+            self.envelope_step = 0;
+            self.envelope_attack = data & 0x04 == 0x04;
+            self.envelope_holding = false;
+        }
+    }
+
+    fn tone_period(&self, channel: usize) -> u16 {
+        let period = self.registers[channel * 2] as u16
+            | ((self.registers[channel * 2 + 1] & 0x0F) as u16) << 8;
+        period.max(1)
+    }
+
+    fn clock(&mut self) {
+        self.prescaler += 1;
+        if self.prescaler < AUDIO_PRESCALER {
+            return;
+        }
+        self.prescaler = 0;
+
+        for channel in 0..3 {
+            self.tone_timers[channel] += 1;
+            if self.tone_timers[channel] >= self.tone_period(channel) {
+                self.tone_timers[channel] = 0;
+                self.tone_outputs[channel] = !self.tone_outputs[channel];
+            }
+        }
+
+        self.noise_timer += 1;
+        if self.noise_timer >= (self.registers[6] & 0x1F).max(1) {
+            self.noise_timer = 0;
+            // 17-bit LFSR with taps at bits 0 and 3.
+            let feedback = (self.noise_shift ^ (self.noise_shift >> 3)) & 0x01;
+            self.noise_shift = (self.noise_shift >> 1) | feedback << 16;
+        }
+
+        let envelope_period = (self.registers[11] as u16 | (self.registers[12] as u16) << 8).max(1);
+        self.envelope_timer += 1;
+        if self.envelope_timer >= envelope_period {
+            self.envelope_timer = 0;
+            self.clock_envelope();
+        }
+    }
+
+    fn clock_envelope(&mut self) {
+        if self.envelope_holding {
+            return;
+        }
+        if self.envelope_step < 31 {
+            self.envelope_step += 1;
+            return;
+        }
+        let shape = self.registers[13];
+        let (continues, attack, alternate, hold) = (
+            shape & 0x08 == 0x08,
+            shape & 0x04 == 0x04,
+            shape & 0x02 == 0x02,
+            shape & 0x01 == 0x01,
+        );
+        if !continues || hold {
+            // Park at the final level: 0 for one-shot shapes, otherwise the end of the ramp,
+            // flipped by the alternate bit.
+            self.envelope_holding = true;
+            self.envelope_attack = continues && (attack != alternate);
+            self.envelope_step = 31;
+        } else {
+            if alternate {
+                self.envelope_attack = !self.envelope_attack;
+            }
+            self.envelope_step = 0;
+        }
+    }
+
+    fn envelope_level(&self) -> u8 {
+        if self.envelope_attack {
+            self.envelope_step
+        } else {
+            31 - self.envelope_step
+        }
+    }
+
+    fn output(&self) -> f32 {
+        let mixer = self.registers[7];
+        (0..3)
+            .map(|channel| {
+                let tone = mixer & (0x01 << channel) != 0 || self.tone_outputs[channel];
+                let noise = mixer & (0x08 << channel) != 0 || self.noise_shift & 0x01 == 0x01;
+                let volume = self.registers[8 + channel];
+                let level = if volume & 0x10 == 0x10 {
+                    self.envelope_level()
+                } else if volume & 0x0F == 0 {
+                    0
+                } else {
+                    (volume & 0x0F) * 2 + 1
+                };
+                if tone && noise && level > 0 {
+                    10f32.powf(-((31 - level) as f32) * 1.5 / 20.0)
+                } else {
+                    0.0
+                }
+            })
+            .sum::<f32>()
+            * CHANNEL_LEVEL
+    }
+}
+
+// Mapper 69: Sunsoft FME-7 and the 5B, which adds audio. Registers are written through a
+// command port at $8000 and a parameter port at $A000.
+pub struct Fme7 {
+    cartridge: Cartridge,
+    command: Byte,
+    character_banks: [Byte; 8],
+    program_banks: [Byte; 4],
+    mirroring: Mirroring,
+    irq_control: Byte,
+    irq_counter: u16,
+    irq_pending: bool,
+    audio: Sunsoft5b,
+}
+
+impl Fme7 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring;
+        Fme7 {
+            cartridge,
+            command: 0,
+            character_banks: [0; 8],
+            program_banks: [0; 4],
+            mirroring,
+            irq_control: 0,
+            irq_counter: 0,
+            irq_pending: false,
+            audio: Sunsoft5b::new(),
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Fme7::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = match addr {
+            0x6000..=0xDFFF => {
+                (self.program_banks[((addr - 0x6000) >> 13) as usize] & 0x3F) as usize
+            }
+            _ => (self.cartridge.program_rom.len() / 0x2000).max(1) - 1,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        let bank = self.character_banks[(addr >> 10) as usize & 0x07];
+        bank_address(bank as usize, 0x400, addr)
+    }
+
+    fn write_parameter(&mut self, data: Byte) {
+        match self.command {
+            0x0..=0x7 => self.character_banks[self.command as usize] = data,
+            0x8..=0xB => self.program_banks[(self.command - 0x8) as usize] = data,
+            0xC => self.mirroring = vrc::mirroring(data),
+            0xD => {
+                self.irq_control = data;
+                self.irq_pending = false;
+            }
+            0xE => self.irq_counter = (self.irq_counter & 0xFF00) | data as u16,
+            _ => self.irq_counter = (self.irq_counter & 0x00FF) | (data as u16) << 8,
+        }
+    }
+
+    // $6000 maps ROM, or RAM when bit 6 of register 8 is set and bit 7 enables it.
+    fn program_ram_state(&self) -> (bool, bool) {
+        let register = self.program_banks[0];
+        (register & 0x40 == 0x40, register & 0x80 == 0x80)
+    }
+}
+
+impl Mapper for Fme7 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match (addr, self.program_ram_state()) {
+            (0x6000..=0x7FFF, (true, true)) => {
+                let bank = (self.program_banks[0] & 0x3F) as usize;
+                self.cartridge
+                    .read_program_ram(bank_address(bank, 0x2000, addr))
+            }
+            (0x6000..=0x7FFF, (true, false)) => 0,
+            (0x6000..=0xFFFF, _) => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.program_ram_state() == (true, true) => {
+                let bank = (self.program_banks[0] & 0x3F) as usize;
+                self.cartridge
+                    .write_program_ram(bank_address(bank, 0x2000, addr), data);
+            }
+            0x8000..=0x9FFF => self.command = data & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(data),
+            0xC000..=0xDFFF => self.audio.address = data,
+            0xE000..=0xFFFF => self.audio.write(data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    // The counter decrements every cycle while bit 7 of register D is set, and an IRQ is
+    // raised when it wraps from $0000 to $FFFF with bit 0 set.
+    fn on_cpu_cycle(&mut self) {
+        if self.irq_control & 0x80 == 0x80 {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0xFFFF && self.irq_control & 0x01 == 0x01 {
+                self.irq_pending = true;
+            }
+        }
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    fn write_register(mapper: &mut Fme7, command: Byte, data: Byte) {
+        mapper.cpu_write(0x8000, command);
+        mapper.cpu_write(0xA000, data);
+    }
+
+    #[test]
+    fn test_banking() {
+        let mut mapper = Fme7::new(cartridge(69, 8, 8));
+        write_register(&mut mapper, 0x9, 3);
+        write_register(&mut mapper, 0xB, 5);
+        write_register(&mut mapper, 0x4, 40);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 5 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 15 * 8);
+        assert_eq!(chr_index(&mut mapper, 0x1000), 40);
+
+        // ROM at $6000, then enabled RAM.
+        write_register(&mut mapper, 0x8, 0x02);
+        assert_eq!(prg_index(&mut mapper, 0x6000), 2 * 8);
+        write_register(&mut mapper, 0x8, 0xC0);
+        mapper.cpu_write(0x6000, 0x5A);
+        assert_eq!(mapper.cpu_read(0x6000), 0x5A);
+        write_register(&mut mapper, 0x8, 0x40);
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+
+        write_register(&mut mapper, 0xC, 0x03);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenUpper);
+    }
+
+    #[test]
+    fn test_cycle_irq() {
+        let mut mapper = Fme7::new(cartridge(69, 2, 1));
+        write_register(&mut mapper, 0xE, 0x02);
+        write_register(&mut mapper, 0xF, 0x00);
+        write_register(&mut mapper, 0xD, 0x81);
+        mapper.on_cpu_cycle();
+        mapper.on_cpu_cycle();
+        assert!(!mapper.irq());
+        mapper.on_cpu_cycle();
+        assert!(mapper.irq());
+        write_register(&mut mapper, 0xD, 0x00);
+        assert!(!mapper.irq());
+    }
+
+    #[test]
+    fn test_tone_and_envelope() {
+        let mut mapper = Fme7::new(cartridge(69, 2, 1));
+        let mut write_audio = |register: Byte, data: Byte| {
+            mapper.cpu_write(0xC000, register);
+            mapper.cpu_write(0xE000, data);
+        };
+        write_audio(0x00, 0x01);
+        write_audio(0x07, 0x3E);
+        write_audio(0x08, 0x0F);
+        let mut levels = (0..64).map(|_| {
+            mapper.on_cpu_cycle();
+            mapper.audio_output()
+        });
+        // Period 1 toggles the square every 16 cycles.
+        assert!(levels.any(|level| level == 0.0));
+        assert!(levels.any(|level| level > 0.0));
+
+        // Shape $0D ramps up once and holds at the top.
+        let audio = &mut mapper.audio;
+        audio.address = 0x0D;
+        audio.write(0x0D);
+        for _ in 0..40 {
+            audio.clock_envelope();
+        }
+        assert_eq!(audio.envelope_level(), 31);
+        audio.write(0x0F);
+        for _ in 0..40 {
+            audio.clock_envelope();
+        }
+        assert_eq!(audio.envelope_level(), 0);
+    }
+}
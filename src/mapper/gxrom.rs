@@ -0,0 +1,124 @@
+use super::{bank_address, bus_conflict, Mapper};
+use crate::cartridge::Cartridge;
+use crate::types::{Address, Byte};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Board {
+    // Mapper 66: bits 4-5 PRG, bits 0-1 CHR at $8000-$FFFF, with bus conflicts.
+    Gxrom,
+    // Mapper 38 (Bit Corp PCI556): bits 0-1 PRG, bits 2-3 CHR at $7000-$7FFF.
+    BitCorp,
+    // Mapper 140 (Jaleco JF-11/JF-14): bits 4-5 PRG, bits 0-3 CHR at $6000-$7FFF.
+    Jaleco,
+}
+
+// Boards with one register selecting a 32 KiB PRG bank and an 8 KiB CHR bank.
+pub struct Gxrom {
+    cartridge: Cartridge,
+    board: Board,
+    program_bank: usize,
+    character_bank: usize,
+}
+
+impl Gxrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let board = match cartridge.mapper {
+            38 => Board::BitCorp,
+            140 => Board::Jaleco,
+            _ => Board::Gxrom,
+        };
+        Gxrom {
+            cartridge,
+            board,
+            program_bank: 0,
+            character_bank: 0,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Gxrom::new(cartridge))
+    }
+}
+
+impl Mapper for Gxrom {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => {
+                let addr = bank_address(self.program_bank, 0x8000, addr);
+                self.cartridge.read_program_rom(addr)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match (self.board, addr) {
+            (Board::Gxrom, 0x8000..=0xFFFF) => {
+                let rom_addr = bank_address(self.program_bank, 0x8000, addr);
+                let data = bus_conflict(&self.cartridge, rom_addr, data);
+                self.program_bank = ((data >> 4) & 0x03) as usize;
+                self.character_bank = (data & 0x03) as usize;
+            }
+            (Board::BitCorp, 0x7000..=0x7FFF) => {
+                self.program_bank = (data & 0x03) as usize;
+                self.character_bank = ((data >> 2) & 0x03) as usize;
+            }
+            (Board::Jaleco, 0x6000..=0x7FFF) => {
+                self.program_bank = ((data >> 4) & 0x03) as usize;
+                self.character_bank = (data & 0x0F) as usize;
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.read_character(addr)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.write_character(addr, data);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_gxrom_through_bus() {
+        let mut mapper = Gxrom::new(cartridge(66, 8, 4));
+        mapper.cartridge_mut().program_rom[0x7FFF] = 0xFF;
+        bus_write(&mut mapper, 0xFFFF, 0x23);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 2 * 32);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 3 * 8);
+    }
+
+    #[test]
+    fn test_bit_corp_through_bus() {
+        let mut mapper = Gxrom::new(cartridge(38, 8, 4));
+        bus_write(&mut mapper, 0x8000, 0x0E);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0);
+        bus_write(&mut mapper, 0x7000, 0x0E);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 2 * 32);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 3 * 8);
+    }
+
+    #[test]
+    fn test_jaleco_through_bus() {
+        let mut mapper = Gxrom::new(cartridge(140, 8, 16));
+        bus_write(&mut mapper, 0x6000, 0x3B);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 32);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 11 * 8);
+    }
+}
@@ -0,0 +1,139 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mapper 32: Irem G-101. Submapper 1 (Major League) is wired to single-screen mirroring
+// and always uses the first PRG mode.
+pub struct IremG101 {
+    cartridge: Cartridge,
+    program_banks: [Byte; 2],
+    program_swap: bool,
+    character_banks: [Byte; 8],
+    mirroring: Mirroring,
+    is_fixed_mirroring: bool,
+}
+
+impl IremG101 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let is_fixed_mirroring = cartridge.submapper == 1;
+        let mirroring = if is_fixed_mirroring {
+            Mirroring::SingleScreenLower
+        } else {
+            cartridge.mirroring
+        };
+        IremG101 {
+            cartridge,
+            program_banks: [0, 0],
+            program_swap: false,
+            character_banks: [0; 8],
+            mirroring,
+            is_fixed_mirroring,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(IremG101::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let second_last = (self.cartridge.program_rom.len() / 0x2000).max(2) - 2;
+        let bank = match (addr, self.program_swap) {
+            (0x8000..=0x9FFF, false) | (0xC000..=0xDFFF, true) => self.program_banks[0] as usize,
+            (0xA000..=0xBFFF, _) => self.program_banks[1] as usize,
+            (0xE000..=0xFFFF, _) => second_last + 1,
+            _ => second_last,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        let bank = self.character_banks[(addr >> 10) as usize & 0x07];
+        bank_address(bank as usize, 0x400, addr)
+    }
+}
+
+impl Mapper for IremG101 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.read_program_ram((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr & 0xF007 {
+            0x6000..=0x7FFF => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000..=0x8007 => self.program_banks[0] = data & 0x1F,
+            0x9000..=0x9007 if !self.is_fixed_mirroring => {
+                self.mirroring = if data & 0x01 == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+                self.program_swap = data & 0x02 == 0x02;
+            }
+            0xA000..=0xA007 => self.program_banks[1] = data & 0x1F,
+            0xB000..=0xB007 => self.character_banks[(addr & 0x07) as usize] = data,
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_banking_and_mirroring() {
+        let mut mapper = IremG101::new(cartridge(32, 8, 4));
+        mapper.cpu_write(0x8000, 3);
+        mapper.cpu_write(0xA000, 5);
+        mapper.cpu_write(0xB005, 27);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xA000), 5 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 14 * 8);
+        assert_eq!(chr_index(&mut mapper, 0x1400), 27);
+
+        mapper.cpu_write(0x9000, 0x03);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 14 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 3 * 8);
+    }
+
+    #[test]
+    fn test_major_league_single_screen() {
+        let mut cartridge = cartridge(32, 8, 4);
+        cartridge.submapper = 1;
+        let mut mapper = IremG101::new(cartridge);
+        mapper.cpu_write(0x9000, 0x03);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenLower);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 14 * 8);
+    }
+}
@@ -0,0 +1,167 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mapper 65: Irem H3001, with a 16-bit CPU-cycle IRQ counter.
+pub struct IremH3001 {
+    cartridge: Cartridge,
+    program_banks: [Byte; 3],
+    character_banks: [Byte; 8],
+    mirroring: Mirroring,
+    irq_enabled: bool,
+    irq_counter: u16,
+    irq_latch: u16,
+    irq_pending: bool,
+}
+
+impl IremH3001 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring;
+        IremH3001 {
+            cartridge,
+            program_banks: [0, 1, 0xFE],
+            character_banks: [0; 8],
+            mirroring,
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq_pending: false,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(IremH3001::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = match addr {
+            0x8000..=0xDFFF => self.program_banks[((addr - 0x8000) >> 13) as usize] as usize,
+            _ => (self.cartridge.program_rom.len() / 0x2000).max(1) - 1,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        let bank = self.character_banks[(addr >> 10) as usize & 0x07];
+        bank_address(bank as usize, 0x400, addr)
+    }
+}
+
+impl Mapper for IremH3001 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.read_program_ram((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000 => self.program_banks[0] = data,
+            0x9001 => {
+                self.mirroring = if data & 0x80 == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+            }
+            0x9003 => {
+                self.irq_enabled = data & 0x80 == 0x80;
+                self.irq_pending = false;
+            }
+            0x9004 => {
+                self.irq_counter = self.irq_latch;
+                self.irq_pending = false;
+            }
+            0x9005 => self.irq_latch = (self.irq_latch & 0x00FF) | (data as u16) << 8,
+            0x9006 => self.irq_latch = (self.irq_latch & 0xFF00) | data as u16,
+            0xA000 => self.program_banks[1] = data,
+            0xB000..=0xB007 => self.character_banks[(addr & 0x07) as usize] = data,
+            0xC000 => self.program_banks[2] = data,
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    // The counter stops at zero instead of wrapping.
+    fn on_cpu_cycle(&mut self) {
+        if self.irq_enabled && self.irq_counter > 0 {
+            self.irq_counter -= 1;
+            if self.irq_counter == 0 {
+                self.irq_pending = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_banking() {
+        let mut mapper = IremH3001::new(cartridge(65, 8, 4));
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0);
+        assert_eq!(prg_index(&mut mapper, 0xA000), 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 14 * 8);
+        mapper.cpu_write(0x8000, 3);
+        mapper.cpu_write(0xC000, 5);
+        mapper.cpu_write(0xB007, 31);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 5 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 15 * 8);
+        assert_eq!(chr_index(&mut mapper, 0x1C00), 31);
+        mapper.cpu_write(0x9001, 0x80);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn test_cycle_irq() {
+        let mut mapper = IremH3001::new(cartridge(65, 2, 1));
+        mapper.cpu_write(0x9005, 0x00);
+        mapper.cpu_write(0x9006, 0x02);
+        mapper.cpu_write(0x9004, 0);
+        mapper.cpu_write(0x9003, 0x80);
+        mapper.on_cpu_cycle();
+        assert!(!mapper.irq());
+        mapper.on_cpu_cycle();
+        assert!(mapper.irq());
+        mapper.cpu_write(0x9004, 0);
+        assert!(!mapper.irq());
+        for _ in 0..2 {
+            mapper.on_cpu_cycle();
+        }
+        assert!(mapper.irq());
+    }
+}
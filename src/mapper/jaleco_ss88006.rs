@@ -0,0 +1,227 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mapper 18: Jaleco SS88006. Every bank register is written four bits at a time through
+// a pair of addresses. The ADPCM sound chip on some boards is not emulated.
+pub struct JalecoSs88006 {
+    cartridge: Cartridge,
+    program_banks: [Byte; 3],
+    character_banks: [Byte; 8],
+    mirroring: Mirroring,
+    program_ram_enabled: bool,
+    program_ram_writable: bool,
+    irq_reload: u16,
+    irq_counter: u16,
+    // Only the low 4, 8, 12 or 16 bits of the counter count.
+    irq_mask: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+}
+
+impl JalecoSs88006 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring;
+        JalecoSs88006 {
+            cartridge,
+            program_banks: [0; 3],
+            character_banks: [0; 8],
+            mirroring,
+            program_ram_enabled: false,
+            program_ram_writable: false,
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_mask: 0xFFFF,
+            irq_enabled: false,
+            irq_pending: false,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(JalecoSs88006::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = match addr {
+            0x8000..=0xDFFF => self.program_banks[((addr - 0x8000) >> 13) as usize] as usize,
+            _ => (self.cartridge.program_rom.len() / 0x2000).max(1) - 1,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        let bank = self.character_banks[(addr >> 10) as usize & 0x07];
+        bank_address(bank as usize, 0x400, addr)
+    }
+
+    // Even addresses set the low nibble, odd ones the high nibble.
+    fn set_nibble(register: &mut Byte, addr: Address, data: Byte) {
+        *register = if addr & 0x01 == 0 {
+            (*register & 0xF0) | (data & 0x0F)
+        } else {
+            (*register & 0x0F) | (data & 0x0F) << 4
+        };
+    }
+
+    fn write_register(&mut self, addr: Address, data: Byte) {
+        match addr & 0xF003 {
+            0x8000..=0x8003 | 0x9000 | 0x9001 => {
+                let index = (((addr & 0x1000) >> 11) | ((addr & 0x02) >> 1)) as usize;
+                Self::set_nibble(&mut self.program_banks[index], addr, data);
+            }
+            0x9002 => {
+                self.program_ram_enabled = data & 0x01 == 0x01;
+                self.program_ram_writable = data & 0x02 == 0x02;
+            }
+            0xA000..=0xDFFF => {
+                let index = ((((addr - 0xA000) & 0x3000) >> 11) | ((addr & 0x02) >> 1)) as usize;
+                Self::set_nibble(&mut self.character_banks[index], addr, data);
+            }
+            0xE000..=0xE003 => {
+                let shift = (addr & 0x03) * 4;
+                self.irq_reload =
+                    (self.irq_reload & !(0x0F << shift)) | ((data & 0x0F) as u16) << shift;
+            }
+            0xF000 => {
+                self.irq_counter = self.irq_reload;
+                self.irq_pending = false;
+            }
+            0xF001 => {
+                self.irq_enabled = data & 0x01 == 0x01;
+                self.irq_mask = if data & 0x08 == 0x08 {
+                    0x000F
+                } else if data & 0x04 == 0x04 {
+                    0x00FF
+                } else if data & 0x02 == 0x02 {
+                    0x0FFF
+                } else {
+                    0xFFFF
+                };
+                self.irq_pending = false;
+            }
+            0xF002 => {
+                self.mirroring = match data & 0x03 {
+                    0 => Mirroring::Horizontal,
+                    1 => Mirroring::Vertical,
+                    2 => Mirroring::SingleScreenLower,
+                    _ => Mirroring::SingleScreenUpper,
+                };
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Mapper for JalecoSs88006 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.program_ram_enabled => {
+                self.cartridge.read_program_ram((addr - 0x6000) as usize)
+            }
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.program_ram_enabled && self.program_ram_writable => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    // The masked bits count down and raise the IRQ when they wrap; the others hold.
+    fn on_cpu_cycle(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+        let count = self.irq_counter & self.irq_mask;
+        if count == 0 {
+            self.irq_pending = true;
+        }
+        self.irq_counter =
+            (self.irq_counter & !self.irq_mask) | (count.wrapping_sub(1) & self.irq_mask);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_nibble_banking() {
+        let mut mapper = JalecoSs88006::new(cartridge(18, 16, 32));
+        mapper.cpu_write(0x8000, 0x05);
+        mapper.cpu_write(0x8001, 0x01);
+        mapper.cpu_write(0x9000, 0x02);
+        mapper.cpu_write(0xD002, 0x0E);
+        mapper.cpu_write(0xD003, 0x0F);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0x15 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 2 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 31 * 8);
+        assert_eq!(chr_index(&mut mapper, 0x1C00), 0xFE);
+
+        mapper.cpu_write(0xF002, 0x01);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn test_program_ram_enable() {
+        let mut mapper = JalecoSs88006::new(cartridge(18, 2, 1));
+        mapper.cpu_write(0x9002, 0x01);
+        mapper.cpu_write(0x6000, 0x12);
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+        mapper.cpu_write(0x9002, 0x03);
+        mapper.cpu_write(0x6000, 0x12);
+        assert_eq!(mapper.cpu_read(0x6000), 0x12);
+    }
+
+    #[test]
+    fn test_masked_irq_counter() {
+        let mut mapper = JalecoSs88006::new(cartridge(18, 2, 1));
+        // A reload of $0F01 with a 4-bit counter: only the low nibble counts.
+        mapper.cpu_write(0xE000, 0x01);
+        mapper.cpu_write(0xE001, 0x00);
+        mapper.cpu_write(0xE002, 0x0F);
+        mapper.cpu_write(0xF000, 0);
+        mapper.cpu_write(0xF001, 0x09);
+        mapper.on_cpu_cycle();
+        assert!(!mapper.irq());
+        mapper.on_cpu_cycle();
+        assert!(mapper.irq());
+        assert_eq!(mapper.irq_counter, 0x0F0F);
+        mapper.cpu_write(0xF000, 0);
+        assert!(!mapper.irq());
+    }
+}
@@ -0,0 +1,297 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mapper 1 (SxROM). Registers are loaded serially through a 5-bit shift register.
+// The SUROM/SOROM/SXROM variants reuse the CHR bank register to select a 256 KiB
+// PRG ROM half and an 8 KiB PRG RAM bank.
+pub struct Mmc1 {
+    cartridge: Cartridge,
+    shift: Byte,
+    shift_count: u8,
+    control: Byte,
+    character_bank_0: Byte,
+    character_bank_1: Byte,
+    program_bank: Byte,
+    cycle: u64,
+    last_write_cycle: Option<u64>,
+}
+
+impl Mmc1 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Mmc1 {
+            cartridge,
+            shift: 0,
+            shift_count: 0,
+            // Power-up state fixes the last PRG bank at $C000.
+            control: 0x0C,
+            character_bank_0: 0,
+            character_bank_1: 0,
+            program_bank: 0,
+            cycle: 0,
+            last_write_cycle: None,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Mmc1::new(cartridge))
+    }
+
+    fn write_register(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x8000..=0x9FFF => self.control = data,
+            0xA000..=0xBFFF => self.character_bank_0 = data,
+            0xC000..=0xDFFF => self.character_bank_1 = data,
+            _ => self.program_bank = data,
+        }
+    }
+
+    fn is_program_ram_enabled(&self) -> bool {
+        self.program_bank & 0x10 == 0
+    }
+
+    // 512 KiB boards use CHR bank bit 4 to choose the 256 KiB PRG half.
+    fn program_outer_bank(&self) -> usize {
+        if self.cartridge.program_rom.len() > 0x40000 {
+            ((self.character_bank_0 >> 4) & 0x01) as usize * 0x40000
+        } else {
+            0
+        }
+    }
+
+    // SOROM (16 KiB) and SXROM (32 KiB) select the 8 KiB PRG RAM bank through CHR bank bits 2-3.
+    fn program_ram_bank(&self) -> usize {
+        match self.cartridge.program_ram.len() {
+            0x4000 => ((self.character_bank_0 >> 3) & 0x01) as usize,
+            0x8000 => ((self.character_bank_0 >> 2) & 0x03) as usize,
+            _ => 0,
+        }
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = (self.program_bank & 0x0F) as usize;
+        let last = (self.cartridge.program_rom.len().min(0x40000) / 0x4000).max(1) - 1;
+        let offset = match (self.control >> 2) & 0x03 {
+            0 | 1 => bank_address(bank >> 1, 0x8000, addr),
+            2 if addr < 0xC000 => bank_address(0, 0x4000, addr),
+            2 => bank_address(bank, 0x4000, addr),
+            _ if addr < 0xC000 => bank_address(bank, 0x4000, addr),
+            _ => bank_address(last, 0x4000, addr),
+        };
+        self.program_outer_bank() + offset
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        if self.control & 0x10 == 0 {
+            bank_address((self.character_bank_0 >> 1) as usize, 0x2000, addr)
+        } else if addr < 0x1000 {
+            bank_address(self.character_bank_0 as usize, 0x1000, addr)
+        } else {
+            bank_address(self.character_bank_1 as usize, 0x1000, addr)
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enabled() => {
+                let addr = bank_address(self.program_ram_bank(), 0x2000, addr);
+                self.cartridge.read_program_ram(addr)
+            }
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enabled() => {
+                let addr = bank_address(self.program_ram_bank(), 0x2000, addr);
+                self.cartridge.write_program_ram(addr, data);
+            }
+            0x8000..=0xFFFF => {
+                // Read-modify-write instructions write the old value and then the result on
+                // consecutive cycles; the serial port only takes the first.
+                if self.last_write_cycle == Some(self.cycle) {
+                    return;
+                }
+                self.last_write_cycle = Some(self.cycle);
+                if data & 0x80 == 0x80 {
+                    self.shift = 0;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    return;
+                }
+                self.shift |= (data & 0x01) << self.shift_count;
+                self.shift_count += 1;
+                if self.shift_count == 5 {
+                    let value = self.shift;
+                    self.write_register(addr, value);
+                    self.shift = 0;
+                    self.shift_count = 0;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn on_cpu_cycle(&mut self) {
+        self.cycle += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+    use crate::apu::Apu;
+    use crate::bus::{Bus, CpuAccess};
+    use crate::cpu;
+    use crate::cpu_registers::{CpuRegisters, Registers};
+    use crate::dma::Dma;
+    use crate::ppu::Ppu;
+    use crate::ram::Ram;
+
+    fn write_serial(mapper: &mut Mmc1, addr: Address, value: Byte) {
+        for i in 0..5 {
+            mapper.cpu_write(addr, (value >> i) & 0x01);
+            mapper.on_cpu_cycle();
+        }
+    }
+
+    #[test]
+    fn test_program_banking_modes() {
+        let mut mapper = Mmc1::new(cartridge(1, 8, 2));
+        // Power-up: switchable $8000, last bank fixed at $C000.
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 7 * 16);
+        write_serial(&mut mapper, 0xE000, 3);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 16);
+
+        // Fix the first bank at $8000 and switch $C000.
+        write_serial(&mut mapper, 0x8000, 0x08);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 3 * 16);
+
+        // 32 KiB mode ignores the low bit of the bank number.
+        write_serial(&mut mapper, 0x8000, 0x00);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 2 * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 3 * 16);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenLower);
+    }
+
+    #[test]
+    fn test_character_banking_modes() {
+        let mut mapper = Mmc1::new(cartridge(1, 2, 4));
+        write_serial(&mut mapper, 0x8000, 0x12);
+        write_serial(&mut mapper, 0xA000, 5);
+        write_serial(&mut mapper, 0xC000, 2);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 5 * 4);
+        assert_eq!(chr_index(&mut mapper, 0x1000), 2 * 4);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+
+        write_serial(&mut mapper, 0x8000, 0x02);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 4 * 4);
+        assert_eq!(chr_index(&mut mapper, 0x1000), 5 * 4);
+    }
+
+    #[test]
+    fn test_reset_and_consecutive_writes() {
+        let mut mapper = Mmc1::new(cartridge(1, 8, 2));
+        mapper.cpu_write(0xE000, 0x01);
+        mapper.on_cpu_cycle();
+        mapper.cpu_write(0xE000, 0x80);
+        mapper.on_cpu_cycle();
+        // The second write of a read-modify-write lands on the same cycle and is dropped.
+        mapper.cpu_write(0xE000, 0x01);
+        mapper.cpu_write(0xE000, 0x01);
+        mapper.on_cpu_cycle();
+        for _ in 0..4 {
+            mapper.cpu_write(0xE000, 0x00);
+            mapper.on_cpu_cycle();
+        }
+        assert_eq!(prg_index(&mut mapper, 0x8000), 16);
+    }
+
+    #[test]
+    fn test_read_modify_write_through_cpu() {
+        let mut mapper = Mmc1::new(cartridge(1, 32, 0));
+        let mut work_ram = Ram::new(vec![0; 0x800]);
+        // INC $A000 at $0000. $A000 reads 0x08, so the two writes are 0x08 and 0x09.
+        work_ram.field[..3].copy_from_slice(&[0xEE, 0x00, 0xA0]);
+        let mut ppu = Ppu::new();
+        let mut apu = Apu::new();
+        let mut dma = Dma::new();
+        let mut last_access = CpuAccess::None;
+        let mut registers = Registers::new();
+        let mut nmi = false;
+        for _ in 0..4 {
+            mapper.cpu_write(0xA000, 0x00);
+            mapper.on_cpu_cycle();
+        }
+
+        registers.set_PC(0x0000);
+        let mut bus = Bus::new(
+            &mut mapper,
+            &mut work_ram,
+            &mut ppu,
+            &mut apu,
+            &mut dma,
+            &mut last_access,
+        );
+        cpu::run(&mut registers, &mut bus, &mut nmi, false);
+        // Only the old value's bit 0 was shifted in, leaving the outer bank at 0.
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0);
+    }
+
+    #[test]
+    fn test_surom_outer_bank_and_program_ram() {
+        let mut mapper = Mmc1::new(cartridge(1, 32, 0));
+        write_serial(&mut mapper, 0xA000, 0x10);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 256);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 256 + 15 * 16);
+
+        mapper.cpu_write(0x6000, 0xA5);
+        assert_eq!(mapper.cpu_read(0x6000), 0xA5);
+        write_serial(&mut mapper, 0xE000, 0x10);
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+    }
+
+    #[test]
+    fn test_sxrom_program_ram_banks() {
+        let mut mapper = Mmc1::new(cartridge(1, 8, 0));
+        mapper.cartridge_mut().program_ram = vec![0; 0x8000];
+        write_serial(&mut mapper, 0xA000, 0x08);
+        mapper.cpu_write(0x6000, 0x5A);
+        assert_eq!(mapper.cartridge().program_ram[0x4000], 0x5A);
+    }
+}
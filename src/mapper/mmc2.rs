@@ -0,0 +1,196 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mappers 9 (MMC2, PxROM) and 10 (MMC4, FxROM). Each pattern table has two 4 KiB CHR
+// banks, and a latch picks between them whenever the PPU fetches tile $FD or $FE.
+// The latch flips after the fetch, so the triggering tile still comes from the old bank.
+pub struct Mmc2 {
+    cartridge: Cartridge,
+    is_mmc4: bool,
+    program_bank: Byte,
+    // Indexed by [pattern table][latch], where latch 0 is $FD and 1 is $FE.
+    character_banks: [[Byte; 2]; 2],
+    latches: [usize; 2],
+    mirroring: Mirroring,
+}
+
+impl Mmc2 {
+    pub fn new(cartridge: Cartridge, is_mmc4: bool) -> Self {
+        let mirroring = cartridge.mirroring;
+        Mmc2 {
+            cartridge,
+            is_mmc4,
+            program_bank: 0,
+            character_banks: [[0; 2]; 2],
+            latches: [1, 1],
+            mirroring,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Mmc2::new(cartridge, false))
+    }
+
+    pub fn boxed_mmc4(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Mmc2::new(cartridge, true))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = self.program_bank as usize;
+        if self.is_mmc4 {
+            // 16 KiB switchable at $8000, last bank fixed at $C000.
+            let last = (self.cartridge.program_rom.len() / 0x4000).max(1) - 1;
+            match addr {
+                0x8000..=0xBFFF => bank_address(bank, 0x4000, addr),
+                _ => bank_address(last, 0x4000, addr),
+            }
+        } else {
+            // 8 KiB switchable at $8000, the last three banks fixed at $A000-$FFFF.
+            let count = (self.cartridge.program_rom.len() / 0x2000).max(4);
+            match addr {
+                0x8000..=0x9FFF => bank_address(bank, 0x2000, addr),
+                _ => bank_address(count - 4 + ((addr - 0x8000) >> 13) as usize, 0x2000, addr),
+            }
+        }
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        let table = ((addr >> 12) & 0x01) as usize;
+        let bank = self.character_banks[table][self.latches[table]];
+        bank_address(bank as usize, 0x1000, addr)
+    }
+
+    fn update_latch(&mut self, addr: Address) {
+        // MMC2 only watches the exact $0FD8/$0FE8 fetch for the first pattern table;
+        // the other table (and both on MMC4) trigger on any byte of the tile.
+        let (table, tile_addr) = if addr < 0x1000 && !self.is_mmc4 {
+            (0, addr)
+        } else {
+            ((addr >> 12) as usize & 0x01, addr & 0xFFF8)
+        };
+        match tile_addr & 0x0FFF {
+            0x0FD8 => self.latches[table] = 0,
+            0x0FE8 => self.latches[table] = 1,
+            _ => (),
+        }
+    }
+}
+
+impl Mapper for Mmc2 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.read_program_ram((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0xA000..=0xAFFF => self.program_bank = data & 0x0F,
+            0xB000..=0xEFFF => {
+                let register = ((addr - 0xB000) >> 12) as usize;
+                self.character_banks[register >> 1][register & 0x01] = data & 0x1F;
+            }
+            0xF000..=0xFFFF => {
+                self.mirroring = if data & 0x01 == 0x01 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        let data = self.cartridge.read_character(self.character_address(addr));
+        self.update_latch(addr);
+        data
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    // Sets up $FD/$FE banks 2/3 for the left table and 4/5 for the right one.
+    fn mapper(is_mmc4: bool) -> Mmc2 {
+        let mut mapper = Mmc2::new(cartridge(if is_mmc4 { 10 } else { 9 }, 8, 4), is_mmc4);
+        for (addr, bank) in [(0xB000, 2), (0xC000, 3), (0xD000, 4), (0xE000, 5)].iter() {
+            mapper.cpu_write(*addr, *bank);
+        }
+        mapper
+    }
+
+    #[test]
+    fn test_mmc2_program_banking() {
+        let mut mapper = mapper(false);
+        mapper.cpu_write(0xA000, 3);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xA000), 13 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 15 * 8);
+        mapper.cpu_write(0xF000, 1);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn test_mmc4_program_banking() {
+        let mut mapper = mapper(true);
+        mapper.cpu_write(0xA000, 3);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 7 * 16);
+    }
+
+    #[test]
+    fn test_latch_flips_after_fetch() {
+        let mut mapper = mapper(false);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 3 * 4);
+        // The $FD fetch itself still reads the $FE bank; the next fetch sees the switch.
+        assert_eq!(mapper.ppu_read(0x0FD8), (3 * 4 + 3) as Byte);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 2 * 4);
+        mapper.ppu_read(0x0FE8);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 3 * 4);
+
+        // The right table has its own latch, triggered by any byte of the tile.
+        assert_eq!(chr_index(&mut mapper, 0x1000), 5 * 4);
+        mapper.ppu_read(0x1FDD);
+        assert_eq!(chr_index(&mut mapper, 0x1000), 4 * 4);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 3 * 4);
+    }
+
+    #[test]
+    fn test_mmc2_left_latch_needs_exact_address() {
+        let mut mmc2 = mapper(false);
+        mmc2.ppu_read(0x0FD9);
+        assert_eq!(chr_index(&mut mmc2, 0x0000), 3 * 4);
+
+        let mut mmc4 = mapper(true);
+        mmc4.ppu_read(0x0FD9);
+        assert_eq!(chr_index(&mut mmc4, 0x0000), 2 * 4);
+    }
+}
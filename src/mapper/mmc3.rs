@@ -0,0 +1,382 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, HeaderFormat, Mirroring};
+use crate::types::{Address, Byte};
+
+// The two MMC3 IRQ counter behaviors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IrqRevision {
+    // MMC3B/C (Sharp): the IRQ fires on every clock that leaves the counter at 0.
+    Sharp,
+    // MMC3A (NEC): the IRQ only fires when the counter goes from non-zero to 0, or
+    // right after a $C001 reload.
+    Nec,
+}
+
+// Number of consecutive PPU accesses with A12 low before a rise clocks the counter.
+// This stands in for the M2-based filter that ignores the short A12 drops between
+// sprite pattern fetches.
+pub const A12_LOW_FILTER: u8 = 3;
+
+// Mapper 4: MMC3 and its MMC6 variant (NES 2.0 submapper 1).
+pub struct Mmc3 {
+    cartridge: Cartridge,
+    bank_select: Byte,
+    banks: [Byte; 8],
+    mirroring: Mirroring,
+    program_ram_protect: Byte,
+    irq_latch: Byte,
+    irq_counter: Byte,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    irq_revision: IrqRevision,
+    a12_low_count: u8,
+    // MMC6 has 1 KiB of internal PRG RAM mirrored through $7000-$7FFF, kept in the
+    // cartridge PRG RAM for battery saves.
+    is_mmc6: bool,
+}
+
+impl Mmc3 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let irq_revision = if cartridge.submapper == 4 {
+            IrqRevision::Nec
+        } else {
+            IrqRevision::Sharp
+        };
+        Mmc3::with_revision(cartridge, irq_revision)
+    }
+
+    pub fn with_revision(cartridge: Cartridge, irq_revision: IrqRevision) -> Self {
+        let is_mmc6 = cartridge.submapper == 1;
+        let mirroring = cartridge.mirroring;
+        Mmc3 {
+            cartridge,
+            bank_select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring,
+            program_ram_protect: 0x80,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            irq_revision,
+            a12_low_count: 0,
+            is_mmc6,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Mmc3::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let second_last = (self.cartridge.program_rom.len() / 0x2000).max(2) - 2;
+        let bank = match (addr, self.bank_select & 0x40 == 0x40) {
+            (0x8000..=0x9FFF, false) => self.banks[6] as usize,
+            (0x8000..=0x9FFF, true) => second_last,
+            (0xA000..=0xBFFF, _) => self.banks[7] as usize,
+            (0xC000..=0xDFFF, false) => second_last,
+            (0xC000..=0xDFFF, true) => self.banks[6] as usize,
+            _ => second_last + 1,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        // CHR inversion swaps the 2 KiB and 1 KiB halves of the pattern tables.
+        let addr = if self.bank_select & 0x80 == 0x80 {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+        let page = (addr >> 10) as usize & 0x07;
+        let bank = match page {
+            // R0 and R1 select 2 KiB banks, so their low bit comes from the address.
+            0..=3 => (self.banks[page >> 1] & 0xFE) as usize | (page & 0x01),
+            _ => self.banks[page - 2] as usize,
+        };
+        bank_address(bank, 0x400, addr)
+    }
+
+    fn write_register(&mut self, addr: Address, data: Byte) {
+        match (addr, addr & 0x01 == 0) {
+            (0x8000..=0x9FFF, true) => self.bank_select = data,
+            (0x8000..=0x9FFF, false) => self.banks[(self.bank_select & 0x07) as usize] = data,
+            (0xA000..=0xBFFF, true) => {
+                if self.mirroring != Mirroring::FourScreen {
+                    self.mirroring = if data & 0x01 == 0x01 {
+                        Mirroring::Horizontal
+                    } else {
+                        Mirroring::Vertical
+                    };
+                }
+            }
+            (0xA000..=0xBFFF, false) => self.program_ram_protect = data,
+            (0xC000..=0xDFFF, true) => self.irq_latch = data,
+            (0xC000..=0xDFFF, false) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (_, true) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            (_, false) => self.irq_enabled = true,
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        let previous = self.irq_counter;
+        let reloaded = self.irq_reload;
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        let fire = match self.irq_revision {
+            IrqRevision::Sharp => self.irq_counter == 0,
+            IrqRevision::Nec => self.irq_counter == 0 && (previous != 0 || reloaded),
+        };
+        if fire && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    // The MMC3 protect register only applies to NES 2.0 images; iNES dumps of MMC6 games
+    // program it with MMC6 semantics, so it is ignored there as most emulators do.
+    fn can_read_program_ram(&self) -> bool {
+        self.cartridge.header_format != HeaderFormat::Nes2
+            || self.program_ram_protect & 0x80 == 0x80
+    }
+
+    fn can_write_program_ram(&self) -> bool {
+        self.cartridge.header_format != HeaderFormat::Nes2
+            || self.program_ram_protect & 0xC0 == 0x80
+    }
+
+    // MMC6 splits its RAM into two 512-byte halves with separate read/write enables in $A001,
+    // all gated by bit 5 of $8000.
+    fn mmc6_access(&self, addr: Address) -> (bool, bool) {
+        if self.bank_select & 0x20 == 0 {
+            return (false, false);
+        }
+        let shift = if addr & 0x0200 == 0x0200 { 6 } else { 4 };
+        let read = (self.program_ram_protect >> (shift + 1)) & 0x01 == 0x01;
+        let write = read && (self.program_ram_protect >> shift) & 0x01 == 0x01;
+        (read, write)
+    }
+
+    fn mmc6_read(&self, addr: Address) -> Byte {
+        let (read, _) = self.mmc6_access(addr);
+        let (other, _) = self.mmc6_access(addr ^ 0x0200);
+        if read {
+            self.cartridge.read_program_ram((addr & 0x03FF) as usize)
+        } else if other {
+            // With only the other half readable, this half reads back as 0.
+            0
+        } else {
+            // Open bus, approximated by the high byte of the address.
+            (addr >> 8) as Byte
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x7000..=0x7FFF if self.is_mmc6 => self.mmc6_read(addr),
+            0x6000..=0x7FFF if !self.is_mmc6 && self.can_read_program_ram() => {
+                self.cartridge.read_program_ram((addr - 0x6000) as usize)
+            }
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x7000..=0x7FFF if self.is_mmc6 && self.mmc6_access(addr).1 => {
+                self.cartridge
+                    .write_program_ram((addr & 0x03FF) as usize, data);
+            }
+            0x7000..=0x7FFF if self.is_mmc6 => (),
+            0x6000..=0x7FFF if !self.is_mmc6 && self.can_write_program_ram() => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn on_ppu_address(&mut self, addr: Address) {
+        if addr & 0x1000 == 0 {
+            self.a12_low_count = self.a12_low_count.saturating_add(1);
+            return;
+        }
+        if self.a12_low_count >= A12_LOW_FILTER {
+            self.clock_irq_counter();
+        }
+        self.a12_low_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    // Simulates the PPU bus of one rendered scanline with background at $0000 and
+    // sprites at $1000: a run of low accesses followed by the sprite fetches.
+    fn scanline(mapper: &mut Mmc3) {
+        for _ in 0..(34 * 4) {
+            mapper.on_ppu_address(0x0000);
+        }
+        for _ in 0..8 {
+            mapper.on_ppu_address(0x2000);
+            mapper.on_ppu_address(0x2000);
+            mapper.on_ppu_address(0x1000);
+            mapper.on_ppu_address(0x1008);
+        }
+    }
+
+    #[test]
+    fn test_program_banking() {
+        let mut mapper = Mmc3::new(cartridge(4, 8, 8));
+        mapper.cpu_write(0x8000, 0x06);
+        mapper.cpu_write(0x8001, 3);
+        mapper.cpu_write(0x8000, 0x07);
+        mapper.cpu_write(0x8001, 5);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xA000), 5 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 14 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 15 * 8);
+
+        mapper.cpu_write(0x8000, 0x46);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 14 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 3 * 8);
+    }
+
+    #[test]
+    fn test_character_banking_and_inversion() {
+        let mut mapper = Mmc3::new(cartridge(4, 2, 8));
+        for (register, bank) in [(0, 8), (1, 11), (2, 20), (5, 23)].iter() {
+            mapper.cpu_write(0x8000, *register);
+            mapper.cpu_write(0x8001, *bank);
+        }
+        assert_eq!(chr_index(&mut mapper, 0x0000), 8);
+        assert_eq!(chr_index(&mut mapper, 0x0400), 9);
+        assert_eq!(chr_index(&mut mapper, 0x0800), 10);
+        assert_eq!(chr_index(&mut mapper, 0x0C00), 11);
+        assert_eq!(chr_index(&mut mapper, 0x1000), 20);
+        assert_eq!(chr_index(&mut mapper, 0x1C00), 23);
+
+        mapper.cpu_write(0x8000, 0x80);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 20);
+        assert_eq!(chr_index(&mut mapper, 0x1000), 8);
+        assert_eq!(chr_index(&mut mapper, 0x1C00), 11);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        let mut mapper = Mmc3::new(cartridge(4, 2, 1));
+        mapper.cpu_write(0xA000, 0x01);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+        mapper.cpu_write(0xA000, 0x00);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn test_scanline_irq() {
+        let mut mapper = Mmc3::new(cartridge(4, 2, 1));
+        mapper.cpu_write(0xC000, 2);
+        mapper.cpu_write(0xC001, 0);
+        mapper.cpu_write(0xE001, 0);
+        scanline(&mut mapper);
+        scanline(&mut mapper);
+        assert!(!mapper.irq());
+        scanline(&mut mapper);
+        assert!(mapper.irq());
+        mapper.cpu_write(0xE000, 0);
+        assert!(!mapper.irq());
+    }
+
+    #[test]
+    fn test_irq_revisions_with_zero_latch() {
+        let mut sharp = Mmc3::with_revision(cartridge(4, 2, 1), IrqRevision::Sharp);
+        let mut nec = Mmc3::with_revision(cartridge(4, 2, 1), IrqRevision::Nec);
+        for mapper in [&mut sharp, &mut nec].iter_mut() {
+            mapper.cpu_write(0xC000, 0);
+            mapper.cpu_write(0xC001, 0);
+            mapper.cpu_write(0xE001, 0);
+            scanline(mapper);
+            assert!(mapper.irq());
+            mapper.cpu_write(0xE000, 0);
+            mapper.cpu_write(0xE001, 0);
+            scanline(mapper);
+        }
+        // With a latch of 0 the Sharp chip keeps firing, the NEC chip only fires after $C001.
+        assert!(sharp.irq());
+        assert!(!nec.irq());
+    }
+
+    #[test]
+    fn test_mmc6_program_ram() {
+        let mut cartridge = cartridge(4, 2, 1);
+        cartridge.submapper = 1;
+        let mut mapper = Mmc3::new(cartridge);
+        mapper.cpu_write(0x8000, 0x20);
+        mapper.cpu_write(0xA001, 0x30);
+        mapper.cpu_write(0x7001, 0xA5);
+        assert_eq!(mapper.cpu_read(0x7001), 0xA5);
+        assert_eq!(mapper.cpu_read(0x7401), 0xA5);
+        // The upper half is disabled and reads back as 0 while the lower half is readable.
+        assert_eq!(mapper.cpu_read(0x7201), 0);
+        mapper.cpu_write(0xA001, 0x20);
+        mapper.cpu_write(0x7001, 0x5A);
+        assert_eq!(mapper.cpu_read(0x7001), 0xA5);
+    }
+
+    #[test]
+    fn test_mmc6_ram_is_battery_backed() {
+        let mut cartridge = cartridge(4, 2, 1);
+        cartridge.submapper = 1;
+        cartridge.has_battery = true;
+        cartridge.program_nvram_size = cartridge.program_ram.len();
+        let mut mapper = Mmc3::new(cartridge);
+        mapper.cpu_write(0x8000, 0x20);
+        mapper.cpu_write(0xA001, 0xF0);
+        mapper.cpu_write(0x7000, 0x12);
+        mapper.cpu_write(0x7BFF, 0x34);
+        assert_eq!(mapper.cartridge().battery_ram()[0x000], 0x12);
+        assert_eq!(mapper.cartridge().battery_ram()[0x3FF], 0x34);
+    }
+}
@@ -0,0 +1,695 @@
+use super::{bank_address, Mapper};
+use crate::apu::pulse::Pulse;
+use crate::cartridge::Cartridge;
+use crate::types::{Address, Byte};
+
+// The MMC5 clocks its envelopes and length counters at a fixed 240 Hz instead of
+// following the APU frame counter.
+const FRAME_PERIOD: u32 = 7457;
+
+// CPU cycles without a PPU read after which the MMC5 decides rendering has stopped.
+const IDLE_CYCLES: u8 = 3;
+
+// PPU fetches are counted from the scanline detection, which happens on the nametable
+// read of the third tile. 32 background tiles take 128 fetches, then come the 8 sprite
+// slots and the two-tile prefetch for the next line.
+const SPRITE_FETCHES: std::ops::Range<u16> = 128..160;
+const PREFETCH_FETCHES: std::ops::Range<u16> = 160..168;
+
+// Mapper 5 (ExROM). Besides PRG/CHR banking the MMC5 watches the PPU bus to find
+// scanlines, which drives its IRQ, the 8x16 sprite CHR set, extended attributes and
+// the vertical split.
+pub struct Mmc5 {
+    cartridge: Cartridge,
+    program_mode: Byte,
+    character_mode: Byte,
+    program_ram_protect: [Byte; 2],
+    extended_ram_mode: Byte,
+    nametable_mapping: Byte,
+    fill_tile: Byte,
+    fill_attribute: Byte,
+    // $5113-$5117.
+    program_banks: [Byte; 5],
+    // $5120-$5127, used for sprites (and everything with 8x8 sprites).
+    sprite_banks: [u16; 8],
+    // $5128-$512B, used for the background with 8x16 sprites.
+    background_banks: [u16; 4],
+    character_upper: Byte,
+    last_wrote_background: bool,
+    split_control: Byte,
+    split_scroll: Byte,
+    split_bank: Byte,
+    irq_target: Byte,
+    irq_enabled: bool,
+    irq_pending: bool,
+    in_frame: bool,
+    scanline: Byte,
+    last_nametable_address: Option<Address>,
+    nametable_matches: u8,
+    fetch_count: u16,
+    idle_cycles: u8,
+    sprite_size_16: bool,
+    // ExRAM byte latched at the nametable fetch of the current background tile.
+    extended_attribute: Byte,
+    in_split: bool,
+    multiplicand: Byte,
+    multiplier: Byte,
+    extended_ram: [Byte; 0x400],
+    pulses: [Pulse; 2],
+    pcm_read_mode: bool,
+    pcm_irq_enabled: bool,
+    pcm_irq_pending: bool,
+    pcm_output: Byte,
+    frame_cycle: u32,
+    odd_cycle: bool,
+}
+
+impl Mmc5 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Mmc5 {
+            cartridge,
+            program_mode: 3,
+            character_mode: 0,
+            program_ram_protect: [0; 2],
+            extended_ram_mode: 0,
+            nametable_mapping: 0,
+            fill_tile: 0,
+            fill_attribute: 0,
+            program_banks: [0, 0xFF, 0xFF, 0xFF, 0xFF],
+            sprite_banks: [0; 8],
+            background_banks: [0; 4],
+            character_upper: 0,
+            last_wrote_background: false,
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            irq_target: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            in_frame: false,
+            scanline: 0,
+            last_nametable_address: None,
+            nametable_matches: 0,
+            fetch_count: 0,
+            idle_cycles: 0,
+            sprite_size_16: false,
+            extended_attribute: 0,
+            in_split: false,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            extended_ram: [0; 0x400],
+            pulses: [Pulse::without_sweep(), Pulse::without_sweep()],
+            pcm_read_mode: false,
+            pcm_irq_enabled: false,
+            pcm_irq_pending: false,
+            pcm_output: 0,
+            frame_cycle: 0,
+            odd_cycle: false,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Mmc5::new(cartridge))
+    }
+
+    // Returns whether $8000-$FFFF maps ROM at `addr`, and the offset into ROM or RAM.
+    fn program_address(&self, addr: Address) -> (bool, usize) {
+        // Window sizes are in 8 KiB units; $5117 is always ROM.
+        let (register, size) = match (self.program_mode & 0x03, addr) {
+            (0, _) => (4, 4),
+            (1, 0x8000..=0xBFFF) | (2, 0x8000..=0xBFFF) => (2, 2),
+            (1, _) => (4, 2),
+            (2, 0xC000..=0xDFFF) => (3, 1),
+            (2, _) => (4, 1),
+            _ => (1 + ((addr - 0x8000) >> 13) as usize, 1),
+        };
+        let value = self.program_banks[register];
+        let is_rom = register == 4 || value & 0x80 == 0x80;
+        let bank = if is_rom { value & 0x7F } else { value & 0x07 } as usize;
+        (is_rom, bank_address(bank / size, size * 0x2000, addr))
+    }
+
+    fn is_program_ram_writable(&self) -> bool {
+        self.program_ram_protect[0] & 0x03 == 0x02 && self.program_ram_protect[1] & 0x03 == 0x01
+    }
+
+    fn is_sprite_fetch(&self) -> bool {
+        self.in_frame && SPRITE_FETCHES.contains(&self.fetch_count)
+    }
+
+    // Screen column of the background tile being fetched, if any.
+    fn background_tile(&self) -> Option<usize> {
+        if !self.in_frame {
+            None
+        } else if self.fetch_count < SPRITE_FETCHES.start {
+            Some(2 + self.fetch_count as usize / 4)
+        } else if PREFETCH_FETCHES.contains(&self.fetch_count) {
+            Some((self.fetch_count - PREFETCH_FETCHES.start) as usize / 4)
+        } else {
+            None
+        }
+    }
+
+    fn is_in_split(&self, tile: usize) -> bool {
+        let threshold = (self.split_control & 0x1F) as usize;
+        match (
+            self.split_control & 0x80 == 0x80,
+            self.split_control & 0x40 == 0x40,
+        ) {
+            (false, _) => false,
+            (true, false) => tile < threshold,
+            (true, true) => tile >= threshold,
+        }
+    }
+
+    // Row of the split region being fetched; the prefetched tiles belong to the next line.
+    fn split_y(&self) -> usize {
+        let line = self.scanline as usize + (self.fetch_count >= PREFETCH_FETCHES.start) as usize;
+        (self.split_scroll as usize + line) % 240
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        if self.in_split {
+            let addr = (addr & !0x07) | (self.split_y() & 0x07) as Address;
+            return bank_address(self.split_bank as usize, 0x1000, addr);
+        }
+        if self.extended_ram_mode == 1 && self.background_tile().is_some() {
+            let bank = (self.extended_attribute & 0x3F) as usize
+                | ((self.character_upper & 0x03) as usize) << 6;
+            return bank_address(bank, 0x1000, addr);
+        }
+        let use_background = self.sprite_size_16
+            && if self.in_frame {
+                !self.is_sprite_fetch()
+            } else {
+                self.last_wrote_background
+            };
+        let mode = self.character_mode & 0x03;
+        let size = 0x2000 >> mode;
+        let (bank, addr) = if use_background {
+            let addr = if mode == 0 { addr } else { addr & 0x0FFF };
+            let slot = addr as usize / size;
+            (
+                self.background_banks[((slot + 1) * (8 >> mode) - 1) & 0x03],
+                addr,
+            )
+        } else {
+            let slot = addr as usize / size;
+            (self.sprite_banks[(slot + 1) * (8 >> mode) - 1], addr)
+        };
+        bank_address(bank as usize, size, addr)
+    }
+
+    fn detect_scanline(&mut self) {
+        if self.in_frame {
+            self.scanline = self.scanline.wrapping_add(1);
+            if self.scanline == self.irq_target {
+                self.irq_pending = true;
+            }
+        } else {
+            self.in_frame = true;
+            self.scanline = 0;
+            self.irq_pending = false;
+        }
+        self.fetch_count = 0;
+    }
+
+    fn leave_frame(&mut self) {
+        self.in_frame = false;
+        self.last_nametable_address = None;
+        self.nametable_matches = 0;
+    }
+
+    fn write_register(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x5000..=0x5007 => self.pulses[((addr >> 2) & 0x01) as usize].write(addr & 0x03, data),
+            0x5010 => {
+                self.pcm_read_mode = data & 0x01 == 0x01;
+                self.pcm_irq_enabled = data & 0x80 == 0x80;
+            }
+            // Writing 0 is ignored; in read mode the level comes from PRG reads instead.
+            0x5011 if !self.pcm_read_mode && data != 0 => self.pcm_output = data,
+            0x5015 => {
+                self.pulses[0].set_enabled(data & 0x01 == 0x01);
+                self.pulses[1].set_enabled(data & 0x02 == 0x02);
+            }
+            0x5100 => self.program_mode = data & 0x03,
+            0x5101 => self.character_mode = data & 0x03,
+            0x5102 | 0x5103 => self.program_ram_protect[(addr - 0x5102) as usize] = data,
+            0x5104 => self.extended_ram_mode = data & 0x03,
+            0x5105 => self.nametable_mapping = data,
+            0x5106 => self.fill_tile = data,
+            0x5107 => self.fill_attribute = data & 0x03,
+            0x5113..=0x5117 => self.program_banks[(addr - 0x5113) as usize] = data,
+            0x5120..=0x5127 => {
+                self.sprite_banks[(addr - 0x5120) as usize] =
+                    data as u16 | ((self.character_upper & 0x03) as u16) << 8;
+                self.last_wrote_background = false;
+            }
+            0x5128..=0x512B => {
+                self.background_banks[(addr - 0x5128) as usize] =
+                    data as u16 | ((self.character_upper & 0x03) as u16) << 8;
+                self.last_wrote_background = true;
+            }
+            0x5130 => self.character_upper = data & 0x03,
+            0x5200 => self.split_control = data,
+            0x5201 => self.split_scroll = data,
+            0x5202 => self.split_bank = data,
+            0x5203 => self.irq_target = data,
+            0x5204 => self.irq_enabled = data & 0x80 == 0x80,
+            0x5205 => self.multiplicand = data,
+            0x5206 => self.multiplier = data,
+            0x5C00..=0x5FFF => {
+                let addr = (addr & 0x03FF) as usize;
+                // In the nametable modes the CPU can only write while the PPU is rendering.
+                match self.extended_ram_mode {
+                    0 | 1 => self.extended_ram[addr] = if self.in_frame { data } else { 0 },
+                    2 => self.extended_ram[addr] = data,
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Mapper for Mmc5 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x5010 => {
+                let status = (self.pcm_irq_pending as Byte) << 7;
+                self.pcm_irq_pending = false;
+                status
+            }
+            0x5015 => {
+                self.pulses[0].is_active() as Byte | (self.pulses[1].is_active() as Byte) << 1
+            }
+            0x5204 => {
+                let status = (self.irq_pending as Byte) << 7 | (self.in_frame as Byte) << 6;
+                self.irq_pending = false;
+                status
+            }
+            0x5205 => (self.multiplicand as u16 * self.multiplier as u16) as Byte,
+            0x5206 => ((self.multiplicand as u16 * self.multiplier as u16) >> 8) as Byte,
+            0x5C00..=0x5FFF if self.extended_ram_mode >= 2 => {
+                self.extended_ram[(addr & 0x03FF) as usize]
+            }
+            0x6000..=0x7FFF => {
+                let bank = (self.program_banks[0] & 0x07) as usize;
+                self.cartridge
+                    .read_program_ram(bank_address(bank, 0x2000, addr))
+            }
+            0x8000..=0xFFFF => {
+                let data = match self.program_address(addr) {
+                    (true, offset) => self.cartridge.read_program_rom(offset),
+                    (false, offset) => self.cartridge.read_program_ram(offset),
+                };
+                if self.pcm_read_mode && addr < 0xC000 {
+                    if data == 0 {
+                        self.pcm_irq_pending = true;
+                    } else {
+                        self.pcm_output = data;
+                    }
+                }
+                data
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_writable() => {
+                let bank = (self.program_banks[0] & 0x07) as usize;
+                self.cartridge
+                    .write_program_ram(bank_address(bank, 0x2000, addr), data);
+            }
+            0x8000..=0xDFFF if self.is_program_ram_writable() => {
+                if let (false, offset) = self.program_address(addr) {
+                    self.cartridge.write_program_ram(offset, data);
+                }
+            }
+            0x5000..=0x5FFF => self.write_register(addr, data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn irq(&self) -> bool {
+        (self.irq_pending && self.irq_enabled) || (self.pcm_irq_pending && self.pcm_irq_enabled)
+    }
+
+    fn on_cpu_cycle(&mut self) {
+        self.idle_cycles = self.idle_cycles.saturating_add(1);
+        if self.idle_cycles == IDLE_CYCLES {
+            self.leave_frame();
+        }
+        self.odd_cycle = !self.odd_cycle;
+        if self.odd_cycle {
+            self.pulses.iter_mut().for_each(Pulse::clock_timer);
+        }
+        self.frame_cycle += 1;
+        if self.frame_cycle == FRAME_PERIOD {
+            self.frame_cycle = 0;
+            for pulse in self.pulses.iter_mut() {
+                pulse.clock_quarter_frame();
+                pulse.clock_half_frame();
+            }
+        }
+    }
+
+    // Three reads of the same nametable address in a row only happen at the start of a
+    // rendered scanline: two dummy fetches at dots 337/339 and the first fetch at dot 1.
+    fn on_ppu_address(&mut self, addr: Address) {
+        self.idle_cycles = 0;
+        self.fetch_count = self.fetch_count.saturating_add(1);
+        let is_nametable = (0x2000..0x3F00).contains(&addr);
+        if is_nametable && self.last_nametable_address == Some(addr) {
+            self.nametable_matches += 1;
+            if self.nametable_matches == 2 {
+                self.detect_scanline();
+            }
+        } else {
+            self.nametable_matches = 0;
+        }
+        self.last_nametable_address = if is_nametable { Some(addr) } else { None };
+
+        match self.background_tile() {
+            Some(tile) if self.fetch_count.is_multiple_of(4) => {
+                self.in_split = self.is_in_split(tile);
+                self.extended_attribute = self.extended_ram[(addr & 0x03FF) as usize];
+            }
+            Some(_) => (),
+            None => self.in_split = false,
+        }
+    }
+
+    fn on_ppu_register_write(&mut self, addr: Address, data: Byte) {
+        match addr & 0x2007 {
+            0x2000 => self.sprite_size_16 = data & 0x20 == 0x20,
+            0x2001 if data & 0x18 == 0 => self.leave_frame(),
+            _ => (),
+        }
+    }
+
+    fn ciram_page(&self, table: usize) -> usize {
+        ((self.nametable_mapping >> (table * 2)) & 0x01) as usize
+    }
+
+    fn read_nametable(&mut self, addr: Address) -> Option<Byte> {
+        let offset = (addr & 0x03FF) as usize;
+        let is_attribute = self.fetch_count % 4 == 1;
+        if self.in_split {
+            let (y, tile) = (self.split_y(), self.background_tile().unwrap_or(0) & 0x1F);
+            return Some(if is_attribute {
+                let attribute = self.extended_ram[0x3C0 + (y / 32) * 8 + tile / 4];
+                let shift = ((y / 16) & 0x01) * 4 + ((tile / 2) & 0x01) * 2;
+                ((attribute >> shift) & 0x03) * 0x55
+            } else {
+                self.extended_ram[(y / 8) * 32 + tile]
+            });
+        }
+        if self.extended_ram_mode == 1 && is_attribute && self.background_tile().is_some() {
+            return Some((self.extended_attribute >> 6) * 0x55);
+        }
+        let table = ((addr >> 10) & 0x03) as usize;
+        match (self.nametable_mapping >> (table * 2)) & 0x03 {
+            2 if self.extended_ram_mode <= 1 => Some(self.extended_ram[offset]),
+            2 => Some(0),
+            3 if offset >= 0x3C0 => Some(self.fill_attribute * 0x55),
+            3 => Some(self.fill_tile),
+            _ => None,
+        }
+    }
+
+    fn write_nametable(&mut self, addr: Address, data: Byte) -> bool {
+        let table = ((addr >> 10) & 0x03) as usize;
+        match (self.nametable_mapping >> (table * 2)) & 0x03 {
+            2 => {
+                if self.extended_ram_mode <= 1 {
+                    self.extended_ram[(addr & 0x03FF) as usize] = data;
+                }
+                true
+            }
+            3 => true,
+            _ => false,
+        }
+    }
+
+    fn audio_output(&self) -> f32 {
+        let pulse = (self.pulses[0].output() + self.pulses[1].output()) as f32;
+        let pulse = if pulse == 0.0 {
+            0.0
+        } else {
+            95.88 / (8128.0 / pulse + 100.0)
+        };
+        // The 8-bit PCM level is mixed like the APU DMC, which is 7 bits wide.
+        let pcm = (self.pcm_output >> 1) as f32;
+        let pcm = if pcm == 0.0 {
+            0.0
+        } else {
+            159.79 / (1.0 / (pcm / 22638.0) + 100.0)
+        };
+        pulse + pcm
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    // Performs one PPU fetch the way the PPU does: announce the address, then read.
+    fn fetch(mapper: &mut Mmc5, addr: Address) -> Byte {
+        mapper.on_ppu_address(addr);
+        if addr >= 0x2000 {
+            mapper.read_nametable(addr).unwrap_or(0xEE)
+        } else {
+            mapper.ppu_read(addr)
+        }
+    }
+
+    fn background_tile(mapper: &mut Mmc5, tile: Address) -> [Byte; 4] {
+        [
+            fetch(mapper, 0x2000 + tile),
+            fetch(mapper, 0x23C0 + tile / 4),
+            fetch(mapper, 0x0000),
+            fetch(mapper, 0x0008),
+        ]
+    }
+
+    // Feeds the fetches of one rendered scanline and returns the first background tile
+    // fetch and the first sprite pattern byte.
+    fn scanline(mapper: &mut Mmc5) -> ([Byte; 4], Byte) {
+        let background = background_tile(mapper, 2);
+        for tile in 3..34 {
+            background_tile(mapper, tile);
+        }
+        let mut sprite = 0;
+        for i in 0..8 {
+            fetch(mapper, 0x2000);
+            fetch(mapper, 0x2000);
+            let data = fetch(mapper, 0x1000);
+            fetch(mapper, 0x1008);
+            if i == 0 {
+                sprite = data;
+            }
+        }
+        background_tile(mapper, 0);
+        background_tile(mapper, 1);
+        fetch(mapper, 0x2002);
+        fetch(mapper, 0x2002);
+        (background, sprite)
+    }
+
+    fn start_frame(mapper: &mut Mmc5) {
+        for _ in 0..IDLE_CYCLES {
+            mapper.on_cpu_cycle();
+        }
+        // The pre-render line ends with the fetches that detect scanline 0.
+        scanline(mapper);
+    }
+
+    #[test]
+    fn test_program_banking_modes() {
+        let mut mapper = Mmc5::new(cartridge(5, 8, 1));
+        assert_eq!(prg_index(&mut mapper, 0xE000), 15 * 8);
+
+        mapper.cpu_write(0x5100, 0);
+        mapper.cpu_write(0x5117, 0x85);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 4 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 7 * 8);
+
+        mapper.cpu_write(0x5100, 2);
+        mapper.cpu_write(0x5115, 0x83);
+        mapper.cpu_write(0x5116, 0x89);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 2 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xA000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 9 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 5 * 8);
+    }
+
+    #[test]
+    fn test_program_ram_banks_and_protect() {
+        let mut mapper = Mmc5::new(cartridge(5, 2, 1));
+        mapper.cartridge_mut().program_ram = vec![0; 0x10000];
+        mapper.cpu_write(0x6000, 0x11);
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+
+        mapper.cpu_write(0x5102, 0x02);
+        mapper.cpu_write(0x5103, 0x01);
+        mapper.cpu_write(0x5113, 0x03);
+        mapper.cpu_write(0x6000, 0x11);
+        assert_eq!(mapper.cartridge().program_ram[0x6000], 0x11);
+
+        // RAM banked into $8000 in 8 KiB mode.
+        mapper.cpu_write(0x5114, 0x03);
+        assert_eq!(mapper.cpu_read(0x8000), 0x11);
+        mapper.cpu_write(0x8001, 0x22);
+        assert_eq!(mapper.cpu_read(0x6001), 0x22);
+    }
+
+    #[test]
+    fn test_character_sets_with_8x16_sprites() {
+        let mut mapper = Mmc5::new(cartridge(5, 2, 4));
+        mapper.cpu_write(0x5101, 3);
+        mapper.cpu_write(0x5124, 10);
+        mapper.cpu_write(0x5128, 20);
+        assert_eq!(chr_index(&mut mapper, 0x1000), 10);
+
+        mapper.on_ppu_register_write(0x2000, 0x20);
+        // Outside rendering the last written set is used.
+        assert_eq!(chr_index(&mut mapper, 0x1000), 20);
+
+        start_frame(&mut mapper);
+        let (background, sprite) = scanline(&mut mapper);
+        assert_eq!(background[2], 20);
+        assert_eq!(sprite, 10);
+    }
+
+    #[test]
+    fn test_scanline_irq_and_in_frame() {
+        let mut mapper = Mmc5::new(cartridge(5, 2, 1));
+        mapper.cpu_write(0x5203, 3);
+        mapper.cpu_write(0x5204, 0x80);
+        start_frame(&mut mapper);
+        assert_eq!(mapper.cpu_read(0x5204), 0x00);
+        scanline(&mut mapper);
+        assert_eq!(mapper.cpu_read(0x5204), 0x40);
+        scanline(&mut mapper);
+        scanline(&mut mapper);
+        assert!(!mapper.irq());
+        scanline(&mut mapper);
+        assert!(mapper.irq());
+        assert_eq!(mapper.cpu_read(0x5204), 0xC0);
+        assert!(!mapper.irq());
+
+        for _ in 0..IDLE_CYCLES {
+            mapper.on_cpu_cycle();
+        }
+        assert_eq!(mapper.cpu_read(0x5204), 0x00);
+    }
+
+    #[test]
+    fn test_nametable_sources() {
+        let mut mapper = Mmc5::new(cartridge(5, 2, 1));
+        // Table 0: CIRAM A, 1: CIRAM B, 2: ExRAM, 3: fill.
+        mapper.cpu_write(0x5105, 0xE4);
+        mapper.cpu_write(0x5106, 0x42);
+        mapper.cpu_write(0x5107, 0x02);
+        assert_eq!(mapper.ciram_page(0), 0);
+        assert_eq!(mapper.ciram_page(1), 1);
+        assert_eq!(mapper.read_nametable(0x2000), None);
+
+        assert!(mapper.write_nametable(0x2805, 0x99));
+        assert_eq!(mapper.read_nametable(0x2805), Some(0x99));
+        assert_eq!(mapper.read_nametable(0x2C05), Some(0x42));
+        assert_eq!(mapper.read_nametable(0x2FC0), Some(0xAA));
+
+        // ExRAM as CPU RAM reads back as 0 through the nametables.
+        mapper.cpu_write(0x5104, 2);
+        mapper.cpu_write(0x5C05, 0x77);
+        assert_eq!(mapper.cpu_read(0x5C05), 0x77);
+        assert_eq!(mapper.read_nametable(0x2805), Some(0));
+    }
+
+    #[test]
+    fn test_extended_attributes() {
+        let mut mapper = Mmc5::new(cartridge(5, 2, 4));
+        mapper.cpu_write(0x5104, 1);
+        mapper.cpu_write(0x5C02, 0xC5);
+        assert_eq!(mapper.extended_ram[2], 0);
+        start_frame(&mut mapper);
+        scanline(&mut mapper);
+        // ExRAM writes only land while rendering in this mode.
+        mapper.cpu_write(0x5C02, 0xC5);
+        let (background, _) = scanline(&mut mapper);
+        assert_eq!(background[1], 0xFF);
+        assert_eq!(background[2], 5 * 4);
+    }
+
+    #[test]
+    fn test_vertical_split() {
+        let mut mapper = Mmc5::new(cartridge(5, 2, 4));
+        mapper.cpu_write(0x5104, 2);
+        mapper.cpu_write(0x5C00 + 10 * 32 + 2, 0x33);
+        mapper.cpu_write(0x5200, 0x84);
+        mapper.cpu_write(0x5201, 80);
+        mapper.cpu_write(0x5202, 3);
+        start_frame(&mut mapper);
+        let (background, _) = scanline(&mut mapper);
+        assert_eq!(background[0], 0x33);
+        assert_eq!(background[2], 3 * 4);
+    }
+
+    #[test]
+    fn test_multiplier() {
+        let mut mapper = Mmc5::new(cartridge(5, 2, 1));
+        mapper.cpu_write(0x5205, 200);
+        mapper.cpu_write(0x5206, 100);
+        assert_eq!(mapper.cpu_read(0x5205), (20000 & 0xFF) as Byte);
+        assert_eq!(mapper.cpu_read(0x5206), (20000 >> 8) as Byte);
+    }
+
+    #[test]
+    fn test_expansion_audio() {
+        let mut mapper = Mmc5::new(cartridge(5, 2, 1));
+        mapper.cpu_write(0x5015, 0x01);
+        mapper.cpu_write(0x5000, 0xBF);
+        mapper.cpu_write(0x5002, 0x10);
+        mapper.cpu_write(0x5003, 0x08);
+        assert_eq!(mapper.cpu_read(0x5015), 0x01);
+        let mut heard = false;
+        for _ in 0..0x100 {
+            mapper.on_cpu_cycle();
+            heard |= mapper.audio_output() > 0.0;
+        }
+        assert!(heard);
+
+        mapper.cpu_write(0x5015, 0x00);
+        mapper.cpu_write(0x5011, 0x80);
+        assert!(mapper.audio_output() > 0.0);
+        mapper.cpu_write(0x5010, 0x81);
+        mapper.cartridge_mut().program_rom[0] = 0;
+        mapper.cpu_write(0x5117, 0x80);
+        mapper.cpu_write(0x5100, 0);
+        mapper.cpu_read(0x8000);
+        assert!(mapper.irq());
+    }
+}
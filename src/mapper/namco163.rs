@@ -0,0 +1,315 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::Cartridge;
+use crate::types::{Address, Byte};
+
+// One wavetable channel is updated every 15 CPU cycles, in turn.
+const CHANNEL_PERIOD: u8 = 15;
+
+// Output level per unit of sample times volume, on the APU mixer scale.
+const CHANNEL_LEVEL: f32 = 0.00125;
+
+// Mapper 19: Namco 129/163. Nametables can come from CHR ROM, and the 163 has 128 bytes of
+// internal RAM shared between the wavetable audio and the CPU. CHR bank values $E0-$FF in
+// the pattern tables are meant to select CIRAM, which is not supported; they read CHR ROM.
+pub struct Namco163 {
+    cartridge: Cartridge,
+    character_banks: [Byte; 8],
+    nametable_banks: [Byte; 4],
+    program_banks: [Byte; 3],
+    write_protect: Byte,
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+    sound_ram: [Byte; 0x80],
+    sound_address: Byte,
+    sound_auto_increment: bool,
+    sound_disabled: bool,
+    sound_cycle: u8,
+    current_channel: usize,
+    channel_outputs: [f32; 8],
+}
+
+impl Namco163 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Namco163 {
+            cartridge,
+            character_banks: [0; 8],
+            nametable_banks: [0xE0, 0xE1, 0xE0, 0xE1],
+            program_banks: [0; 3],
+            write_protect: 0,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            sound_ram: [0; 0x80],
+            sound_address: 0,
+            sound_auto_increment: false,
+            sound_disabled: false,
+            sound_cycle: 0,
+            current_channel: 0,
+            channel_outputs: [0.0; 8],
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Namco163::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = match addr {
+            0x8000..=0xDFFF => {
+                (self.program_banks[((addr - 0x8000) >> 13) as usize] & 0x3F) as usize
+            }
+            _ => (self.cartridge.program_rom.len() / 0x2000).max(1) - 1,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        let bank = self.character_banks[(addr >> 10) as usize & 0x07];
+        bank_address(bank as usize, 0x400, addr)
+    }
+
+    // $F800 must hold $4x to write PRG RAM; bits 0-3 protect each 2 KiB quarter.
+    fn is_program_ram_writable(&self, addr: Address) -> bool {
+        self.write_protect & 0xF0 == 0x40
+            && self.write_protect & (0x01 << ((addr - 0x6000) >> 11)) == 0
+    }
+
+    fn read_sound_data(&mut self) -> Byte {
+        let data = self.sound_ram[self.sound_address as usize];
+        self.advance_sound_address();
+        data
+    }
+
+    fn write_sound_data(&mut self, data: Byte) {
+        self.sound_ram[self.sound_address as usize] = data;
+        self.advance_sound_address();
+    }
+
+    fn advance_sound_address(&mut self) {
+        if self.sound_auto_increment {
+            self.sound_address = (self.sound_address + 1) & 0x7F;
+        }
+    }
+
+    fn enabled_channels(&self) -> usize {
+        ((self.sound_ram[0x7F] >> 4) & 0x07) as usize + 1
+    }
+
+    // Channels live at $78 (channel 7) downwards, 8 bytes each: frequency and phase are
+    // 18 and 24 bits spread over the even and odd bytes.
+    fn update_channel(&mut self, channel: usize) {
+        let base = 0x40 + channel * 8;
+        let registers = &self.sound_ram[base..base + 8];
+        let frequency =
+            registers[0] as u32 | (registers[2] as u32) << 8 | ((registers[4] & 0x03) as u32) << 16;
+        let mut phase =
+            registers[1] as u32 | (registers[3] as u32) << 8 | (registers[5] as u32) << 16;
+        let length = (256 - (registers[4] & 0xFC) as u32) << 16;
+        let offset = registers[6] as u32;
+        let volume = (registers[7] & 0x0F) as f32;
+
+        phase = (phase + frequency) % length;
+        let sample_address = ((offset + (phase >> 16)) & 0xFF) as usize;
+        let sample =
+            (self.sound_ram[(sample_address >> 1) & 0x7F] >> ((sample_address & 0x01) * 4)) & 0x0F;
+        self.channel_outputs[channel] = (sample as f32 - 8.0) * volume;
+
+        self.sound_ram[base + 1] = phase as Byte;
+        self.sound_ram[base + 3] = (phase >> 8) as Byte;
+        self.sound_ram[base + 5] = (phase >> 16) as Byte;
+    }
+}
+
+impl Mapper for Namco163 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x4800..=0x4FFF => self.read_sound_data(),
+            0x5000..=0x57FF => self.irq_counter as Byte,
+            0x5800..=0x5FFF => (self.irq_counter >> 8) as Byte | (self.irq_enabled as Byte) << 7,
+            0x6000..=0x7FFF => self.cartridge.read_program_ram((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x4800..=0x4FFF => self.write_sound_data(data),
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | data as u16;
+                self.irq_pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | ((data & 0x7F) as u16) << 8;
+                self.irq_enabled = data & 0x80 == 0x80;
+                self.irq_pending = false;
+            }
+            0x6000..=0x7FFF if self.is_program_ram_writable(addr) => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000..=0xBFFF => self.character_banks[((addr - 0x8000) >> 11) as usize] = data,
+            0xC000..=0xDFFF => self.nametable_banks[((addr - 0xC000) >> 11) as usize] = data,
+            0xE000..=0xE7FF => {
+                self.program_banks[0] = data;
+                self.sound_disabled = data & 0x40 == 0x40;
+            }
+            0xE800..=0xEFFF => self.program_banks[1] = data,
+            0xF000..=0xF7FF => self.program_banks[2] = data,
+            0xF800..=0xFFFF => {
+                self.write_protect = data;
+                self.sound_address = data & 0x7F;
+                self.sound_auto_increment = data & 0x80 == 0x80;
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    // The 15-bit counter counts up while enabled and stops at $7FFF with an IRQ.
+    fn on_cpu_cycle(&mut self) {
+        if self.irq_enabled && self.irq_counter < 0x7FFF {
+            self.irq_counter += 1;
+            if self.irq_counter == 0x7FFF {
+                self.irq_pending = true;
+            }
+        }
+        if self.sound_disabled {
+            return;
+        }
+        self.sound_cycle += 1;
+        if self.sound_cycle == CHANNEL_PERIOD {
+            self.sound_cycle = 0;
+            let count = self.enabled_channels();
+            self.current_channel = (self.current_channel + 1) % count;
+            self.update_channel(7 - self.current_channel);
+        }
+    }
+
+    fn ciram_page(&self, table: usize) -> usize {
+        (self.nametable_banks[table] & 0x01) as usize
+    }
+
+    // Nametable banks below $E0 map 1 KiB pages of CHR ROM.
+    fn read_nametable(&mut self, addr: Address) -> Option<Byte> {
+        let bank = self.nametable_banks[((addr >> 10) & 0x03) as usize];
+        if bank < 0xE0 {
+            Some(
+                self.cartridge
+                    .read_character(bank_address(bank as usize, 0x400, addr)),
+            )
+        } else {
+            None
+        }
+    }
+
+    fn write_nametable(&mut self, addr: Address, _data: Byte) -> bool {
+        self.nametable_banks[((addr >> 10) & 0x03) as usize] < 0xE0
+    }
+
+    // The hardware multiplexes the channels, so the mix is their average.
+    fn audio_output(&self) -> f32 {
+        if self.sound_disabled {
+            return 0.0;
+        }
+        let count = self.enabled_channels();
+        let sum: f32 = self.channel_outputs[8 - count..].iter().sum();
+        sum / count as f32 * CHANNEL_LEVEL
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_banking_and_nametables() {
+        let mut mapper = Namco163::new(cartridge(19, 8, 8));
+        mapper.cpu_write(0xE000, 3);
+        mapper.cpu_write(0xF000, 5);
+        mapper.cpu_write(0x8800, 21);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 5 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 15 * 8);
+        assert_eq!(chr_index(&mut mapper, 0x0400), 21);
+
+        mapper.cpu_write(0xC000, 0xE1);
+        mapper.cpu_write(0xC800, 0x09);
+        assert_eq!(mapper.ciram_page(0), 1);
+        assert_eq!(mapper.read_nametable(0x2000), None);
+        assert_eq!(mapper.read_nametable(0x2400), Some(9));
+    }
+
+    #[test]
+    fn test_program_ram_protect() {
+        let mut mapper = Namco163::new(cartridge(19, 2, 1));
+        mapper.cpu_write(0x6000, 0x11);
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+        mapper.cpu_write(0xF800, 0x42);
+        mapper.cpu_write(0x6000, 0x11);
+        mapper.cpu_write(0x6800, 0x22);
+        assert_eq!(mapper.cpu_read(0x6000), 0x11);
+        assert_eq!(mapper.cpu_read(0x6800), 0);
+    }
+
+    #[test]
+    fn test_irq_counter() {
+        let mut mapper = Namco163::new(cartridge(19, 2, 1));
+        mapper.cpu_write(0x5000, 0xFD);
+        mapper.cpu_write(0x5800, 0xFF);
+        mapper.on_cpu_cycle();
+        assert!(!mapper.irq());
+        mapper.on_cpu_cycle();
+        assert!(mapper.irq());
+        assert_eq!(mapper.cpu_read(0x5800), 0xFF);
+        mapper.cpu_write(0x5800, 0x00);
+        assert!(!mapper.irq());
+    }
+
+    #[test]
+    fn test_wavetable_channel() {
+        let mut mapper = Namco163::new(cartridge(19, 2, 1));
+        // A 4-sample wave of $F at $00, channel 7 with full volume and a fast frequency.
+        mapper.cpu_write(0xF800, 0x80);
+        for _ in 0..2 {
+            mapper.cpu_write(0x4800, 0xFF);
+        }
+        mapper.cpu_write(0xF800, 0xF8);
+        for &data in [0x00, 0x00, 0x00, 0x00, 0xFC, 0x00, 0x00, 0x0F].iter() {
+            mapper.cpu_write(0x4800, data);
+        }
+        // Read back through the auto-incrementing port.
+        mapper.cpu_write(0xF800, 0xFC);
+        assert_eq!(mapper.cpu_read(0x4800), 0xFC);
+
+        for _ in 0..CHANNEL_PERIOD {
+            mapper.on_cpu_cycle();
+        }
+        assert!(mapper.audio_output() > 0.0);
+        mapper.cpu_write(0xE000, 0x40);
+        assert_eq!(mapper.audio_output(), 0.0);
+    }
+}
@@ -0,0 +1,129 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mappers 79, 113 and 146: AVE NINA-03/NINA-06 and compatible boards, with the register
+// at $4100-$5FFF wherever A8 is set. On NINA-03/06 bit 3 selects the 32 KiB PRG bank
+// and bits 0-2 the 8 KiB CHR bank; the HES multicarts (113) widen both and add
+// mirroring control in bit 7.
+pub struct Nina06 {
+    cartridge: Cartridge,
+    is_hes: bool,
+    program_bank: usize,
+    character_bank: usize,
+    mirroring: Mirroring,
+}
+
+impl Nina06 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let is_hes = cartridge.mapper == 113;
+        let mirroring = cartridge.mirroring;
+        Nina06 {
+            cartridge,
+            is_hes,
+            program_bank: 0,
+            character_bank: 0,
+            mirroring,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Nina06::new(cartridge))
+    }
+
+    fn write_register(&mut self, data: Byte) {
+        if self.is_hes {
+            self.program_bank = ((data >> 3) & 0x07) as usize;
+            self.character_bank = ((data & 0x07) | (data >> 3) & 0x08) as usize;
+            self.mirroring = if data & 0x80 == 0x80 {
+                Mirroring::Vertical
+            } else {
+                Mirroring::Horizontal
+            };
+        } else {
+            self.program_bank = ((data >> 3) & 0x01) as usize;
+            self.character_bank = (data & 0x07) as usize;
+        }
+    }
+}
+
+impl Mapper for Nina06 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => {
+                let addr = bank_address(self.program_bank, 0x8000, addr);
+                self.cartridge.read_program_rom(addr)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        if addr & 0xE100 == 0x4100 {
+            self.write_register(data);
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.read_character(addr)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = bank_address(self.character_bank, 0x2000, addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_nina06_through_bus() {
+        let mut mapper = Nina06::new(cartridge(79, 4, 8));
+        // A8 clear: not the register.
+        bus_write(&mut mapper, 0x4200, 0x0D);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0);
+        bus_write(&mut mapper, 0x5F00, 0x0D);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 32);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 5 * 8);
+    }
+
+    #[test]
+    fn test_sachen_sa016_through_bus() {
+        let mut mapper = Nina06::new(cartridge(146, 4, 8));
+        // $5E00 has A8 clear, so it reaches the mapper but not the register.
+        bus_write(&mut mapper, 0x5E00, 0x0B);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 0);
+        bus_write(&mut mapper, 0x4100, 0x0B);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 32 + 16);
+        assert_eq!(chr_index(&mut mapper, 0x0400), 3 * 8 + 1);
+        bus_write(&mut mapper, 0x5D00, 0x06);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 6 * 8);
+    }
+
+    #[test]
+    fn test_hes_through_bus() {
+        let mut mapper = Nina06::new(cartridge(113, 16, 16));
+        bus_write(&mut mapper, 0x4100, 0xE9);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 5 * 32);
+        assert_eq!(chr_index(&mut mapper, 0x0000), 9 * 8);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+    }
+}
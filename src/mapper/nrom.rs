@@ -0,0 +1,51 @@
+use super::Mapper;
+use crate::cartridge::Cartridge;
+use crate::types::{Address, Byte};
+
+// Mapper 0: no bank switching. A 16 KiB PRG ROM is mirrored into $C000-$FFFF.
+pub struct Nrom {
+    cartridge: Cartridge,
+}
+
+impl Nrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Nrom { cartridge }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Nrom::new(cartridge))
+    }
+}
+
+impl Mapper for Nrom {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.read_program_ram((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => self.cartridge.read_program_rom((addr - 0x8000) as usize),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        if let 0x6000..=0x7FFF = addr {
+            self.cartridge
+                .write_program_ram((addr - 0x6000) as usize, data)
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        self.cartridge.write_character(addr as usize, data);
+    }
+}
@@ -0,0 +1,204 @@
+use super::mmc3::A12_LOW_FILTER;
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mappers 33 and 48: Taito TC0190 and TC0690. The TC0690 moves mirroring to $E000 and
+// adds a scanline IRQ counter clocked by PPU A12 like the MMC3's.
+pub struct TaitoTc0190 {
+    cartridge: Cartridge,
+    has_irq: bool,
+    program_banks: [Byte; 2],
+    // Two 2 KiB banks at $0000-$0FFF, then four 1 KiB banks at $1000-$1FFF.
+    character_banks: [Byte; 6],
+    mirroring: Mirroring,
+    irq_latch: Byte,
+    irq_counter: Byte,
+    irq_enabled: bool,
+    irq_pending: bool,
+    a12_low_count: u8,
+}
+
+impl TaitoTc0190 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let has_irq = cartridge.mapper == 48;
+        let mirroring = cartridge.mirroring;
+        TaitoTc0190 {
+            cartridge,
+            has_irq,
+            program_banks: [0, 1],
+            character_banks: [0; 6],
+            mirroring,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            a12_low_count: 0,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(TaitoTc0190::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let last = (self.cartridge.program_rom.len() / 0x2000).max(2) - 1;
+        let bank = match addr {
+            0x8000..=0x9FFF => self.program_banks[0] as usize,
+            0xA000..=0xBFFF => self.program_banks[1] as usize,
+            0xC000..=0xDFFF => last - 1,
+            _ => last,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        match addr {
+            0x0000..=0x0FFF => {
+                let bank = self.character_banks[(addr >> 11) as usize];
+                bank_address(bank as usize, 0x800, addr)
+            }
+            _ => {
+                let bank = self.character_banks[2 + ((addr >> 10) & 0x03) as usize];
+                bank_address(bank as usize, 0x400, addr)
+            }
+        }
+    }
+
+    fn set_mirroring(&mut self, data: Byte) {
+        self.mirroring = if data & 0x40 == 0x40 {
+            Mirroring::Horizontal
+        } else {
+            Mirroring::Vertical
+        };
+    }
+}
+
+impl Mapper for TaitoTc0190 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr & 0xE003 {
+            0x8000 => {
+                self.program_banks[0] = data & 0x3F;
+                if !self.has_irq {
+                    self.set_mirroring(data);
+                }
+            }
+            0x8001 => self.program_banks[1] = data & 0x3F,
+            0x8002 | 0x8003 => self.character_banks[(addr & 0x01) as usize] = data,
+            0xA000..=0xA003 => self.character_banks[2 + (addr & 0x03) as usize] = data,
+            0xC000 if self.has_irq => self.irq_latch = data,
+            0xC001 if self.has_irq => self.irq_counter = self.irq_latch,
+            0xC002 if self.has_irq => self.irq_enabled = true,
+            0xC003 if self.has_irq => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            0xE000 if self.has_irq => self.set_mirroring(data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    // The TC0690 counter counts up once per scanline and raises the IRQ when it
+    // overflows past $FF.
+    fn on_ppu_address(&mut self, addr: Address) {
+        if !self.has_irq {
+            return;
+        }
+        if addr & 0x1000 == 0 {
+            self.a12_low_count = self.a12_low_count.saturating_add(1);
+            return;
+        }
+        if self.a12_low_count >= A12_LOW_FILTER {
+            self.irq_counter = self.irq_counter.wrapping_add(1);
+            if self.irq_counter == 0 && self.irq_enabled {
+                self.irq_pending = true;
+            }
+        }
+        self.a12_low_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    fn scanline(mapper: &mut TaitoTc0190) {
+        for _ in 0..(34 * 4) {
+            mapper.on_ppu_address(0x0000);
+        }
+        for _ in 0..8 {
+            mapper.on_ppu_address(0x1000);
+        }
+    }
+
+    #[test]
+    fn test_banking_and_mirroring() {
+        let mut mapper = TaitoTc0190::new(cartridge(33, 8, 8));
+        mapper.cpu_write(0x8000, 0x43);
+        mapper.cpu_write(0x8001, 5);
+        mapper.cpu_write(0x8003, 6);
+        mapper.cpu_write(0xA002, 45);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xA000), 5 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 14 * 8);
+        assert_eq!(chr_index(&mut mapper, 0x0800), 12);
+        assert_eq!(chr_index(&mut mapper, 0x0C00), 13);
+        assert_eq!(chr_index(&mut mapper, 0x1800), 45);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn test_tc0690_mirroring_and_irq() {
+        let mut mapper = TaitoTc0190::new(cartridge(48, 8, 8));
+        mapper.cpu_write(0xE000, 0x00);
+        mapper.cpu_write(0x8000, 0x43);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+        mapper.cpu_write(0xE000, 0x40);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+
+        mapper.cpu_write(0xC000, 0xFE);
+        mapper.cpu_write(0xC001, 0);
+        mapper.cpu_write(0xC002, 0);
+        scanline(&mut mapper);
+        assert!(!mapper.irq());
+        scanline(&mut mapper);
+        assert!(mapper.irq());
+        mapper.cpu_write(0xC003, 0);
+        assert!(!mapper.irq());
+    }
+}
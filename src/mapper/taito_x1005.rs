@@ -0,0 +1,147 @@
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Value that must be written to $7EF8/$7EF9 to unlock the internal RAM.
+const RAM_UNLOCK: Byte = 0xA3;
+
+// Mapper 80: Taito X1-005, with registers at $7EF0-$7EFF and 128 bytes of internal RAM
+// at $7F00-$7FFF (mirrored once), kept in the cartridge PRG RAM for battery saves.
+pub struct TaitoX1005 {
+    cartridge: Cartridge,
+    program_banks: [Byte; 3],
+    // Two 2 KiB banks at $0000-$0FFF, then four 1 KiB banks at $1000-$1FFF.
+    character_banks: [Byte; 6],
+    mirroring: Mirroring,
+    ram_enabled: bool,
+}
+
+impl TaitoX1005 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring;
+        TaitoX1005 {
+            cartridge,
+            program_banks: [0; 3],
+            character_banks: [0; 6],
+            mirroring,
+            ram_enabled: false,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(TaitoX1005::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = match addr {
+            0x8000..=0xDFFF => self.program_banks[((addr - 0x8000) >> 13) as usize] as usize,
+            _ => (self.cartridge.program_rom.len() / 0x2000).max(1) - 1,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        match addr {
+            0x0000..=0x0FFF => {
+                let bank = self.character_banks[(addr >> 11) as usize] >> 1;
+                bank_address(bank as usize, 0x800, addr)
+            }
+            _ => {
+                let bank = self.character_banks[2 + ((addr >> 10) & 0x03) as usize];
+                bank_address(bank as usize, 0x400, addr)
+            }
+        }
+    }
+}
+
+impl Mapper for TaitoX1005 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x7F00..=0x7FFF if self.ram_enabled => {
+                self.cartridge.read_program_ram((addr & 0x7F) as usize)
+            }
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x7EF0 | 0x7EF1 => self.character_banks[(addr & 0x01) as usize] = data,
+            0x7EF2..=0x7EF5 => self.character_banks[(addr - 0x7EF0) as usize] = data,
+            0x7EF6 | 0x7EF7 => {
+                self.mirroring = if data & 0x01 == 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+            }
+            0x7EF8 | 0x7EF9 => self.ram_enabled = data == RAM_UNLOCK,
+            0x7EFA | 0x7EFB => self.program_banks[0] = data,
+            0x7EFC | 0x7EFD => self.program_banks[1] = data,
+            0x7EFE | 0x7EFF => self.program_banks[2] = data,
+            0x7F00..=0x7FFF if self.ram_enabled => self
+                .cartridge
+                .write_program_ram((addr & 0x7F) as usize, data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_banking_and_mirroring() {
+        let mut mapper = TaitoX1005::new(cartridge(80, 8, 8));
+        mapper.cpu_write(0x7EFA, 3);
+        mapper.cpu_write(0x7EFF, 5);
+        mapper.cpu_write(0x7EF1, 6);
+        mapper.cpu_write(0x7EF5, 45);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 5 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 15 * 8);
+        assert_eq!(chr_index(&mut mapper, 0x0800), 6);
+        assert_eq!(chr_index(&mut mapper, 0x0C00), 7);
+        assert_eq!(chr_index(&mut mapper, 0x1C00), 45);
+        mapper.cpu_write(0x7EF6, 1);
+        assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn test_internal_ram_unlock_and_mirror() {
+        let mut mapper = TaitoX1005::new(cartridge(80, 2, 1));
+        mapper.cpu_write(0x7F00, 0x55);
+        assert_eq!(mapper.cpu_read(0x7F00), 0);
+        mapper.cpu_write(0x7EF8, RAM_UNLOCK);
+        mapper.cpu_write(0x7F00, 0x55);
+        assert_eq!(mapper.cpu_read(0x7F80), 0x55);
+        mapper.cpu_write(0x7EF9, 0);
+        assert_eq!(mapper.cpu_read(0x7F00), 0);
+    }
+}
@@ -0,0 +1,104 @@
+use super::{bank_address, bus_conflict, Mapper};
+use crate::cartridge::Cartridge;
+use crate::types::{Address, Byte};
+
+// Mapper 2: a switchable 16 KiB PRG bank at $8000 with the last bank fixed at $C000.
+pub struct Uxrom {
+    cartridge: Cartridge,
+    program_bank: usize,
+    has_bus_conflicts: bool,
+}
+
+impl Uxrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        // NES 2.0 submapper 1 marks boards without bus conflicts.
+        let has_bus_conflicts = cartridge.submapper != 1;
+        Uxrom {
+            cartridge,
+            program_bank: 0,
+            has_bus_conflicts,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Uxrom::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let last = (self.cartridge.program_rom.len() / 0x4000).max(1) - 1;
+        if addr < 0xC000 {
+            bank_address(self.program_bank, 0x4000, addr)
+        } else {
+            bank_address(last, 0x4000, addr)
+        }
+    }
+}
+
+impl Mapper for Uxrom {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF => self.cartridge.read_program_ram((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000..=0xFFFF => {
+                let data = if self.has_bus_conflicts {
+                    bus_conflict(&self.cartridge, self.program_rom_address(addr), data)
+                } else {
+                    data
+                };
+                self.program_bank = data as usize;
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        self.cartridge.write_character(addr as usize, data);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_program_bank_switch() {
+        let mut mapper = Uxrom::new(cartridge(2, 8, 0));
+        // Write where the ROM byte has all bits set so the bus conflict does not interfere.
+        mapper.cartridge_mut().program_rom[0x3FFF] = 0xFF;
+        mapper.cpu_write(0xBFFF, 5);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 5 * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 7 * 16);
+    }
+
+    #[test]
+    fn test_bus_conflict() {
+        let mut mapper = Uxrom::new(cartridge(2, 8, 0));
+        // The first byte of bank 0 holds 0x00, which wins the bus conflict.
+        mapper.cpu_write(0x8000, 5);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 0);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::cartridge::Mirroring;
+use crate::types::{Address, Byte};
+
+// Konami boards wire different CPU address lines to the two register-select inputs of
+// the VRC. Each pair names the lines that drive bit 0 and bit 1 of the register number;
+// when the exact board isn't known several pairs are ORed together.
+pub fn register_index(addr: Address, lines: &[(u8, u8)]) -> usize {
+    lines.iter().fold(0, |index, &(low, high)| {
+        index | ((addr >> low) & 0x01) as usize | (((addr >> high) & 0x01) as usize) << 1
+    })
+}
+
+// The two-bit mirroring field shared by VRC4, VRC6 and VRC7.
+pub fn mirroring(data: Byte) -> Mirroring {
+    match data & 0x03 {
+        0 => Mirroring::Vertical,
+        1 => Mirroring::Horizontal,
+        2 => Mirroring::SingleScreenLower,
+        _ => Mirroring::SingleScreenUpper,
+    }
+}
+
+// One scanline is 341 PPU dots, and the prescaler counts 3 of them per CPU cycle.
+const PRESCALER_PERIOD: i16 = 341;
+
+// The IRQ counter of VRC4, VRC6 and VRC7: an 8-bit up-counter that reloads from the latch
+// and raises an IRQ when it overflows. It is clocked every CPU cycle in cycle mode and
+// every 113 2/3 CPU cycles in scanline mode.
+#[derive(Default)]
+pub struct VrcIrq {
+    latch: Byte,
+    counter: Byte,
+    prescaler: i16,
+    enabled: bool,
+    enable_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl VrcIrq {
+    pub fn set_latch(&mut self, data: Byte) {
+        self.latch = data;
+    }
+
+    // VRC4 writes the latch a nibble at a time.
+    pub fn set_latch_low(&mut self, data: Byte) {
+        self.latch = (self.latch & 0xF0) | (data & 0x0F);
+    }
+
+    pub fn set_latch_high(&mut self, data: Byte) {
+        self.latch = (self.latch & 0x0F) | (data & 0x0F) << 4;
+    }
+
+    pub fn set_control(&mut self, data: Byte) {
+        self.enable_after_ack = data & 0x01 == 0x01;
+        self.enabled = data & 0x02 == 0x02;
+        self.cycle_mode = data & 0x04 == 0x04;
+        self.pending = false;
+        self.prescaler = PRESCALER_PERIOD;
+        if self.enabled {
+            self.counter = self.latch;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enable_after_ack;
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    pub fn clock(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        if self.cycle_mode {
+            self.clock_counter();
+            return;
+        }
+        self.prescaler -= 3;
+        if self.prescaler <= 0 {
+            self.prescaler += PRESCALER_PERIOD;
+            self.clock_counter();
+        }
+    }
+
+    fn clock_counter(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cycles_until_irq(irq: &mut VrcIrq) -> usize {
+        (1..100_000)
+            .find(|_| {
+                irq.clock();
+                irq.is_pending()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_register_index() {
+        assert_eq!(register_index(0x8002, &[(1, 2)]), 1);
+        assert_eq!(register_index(0x8002, &[(1, 0)]), 1);
+        assert_eq!(register_index(0x8001, &[(1, 0)]), 2);
+        assert_eq!(register_index(0x80C0, &[(1, 2), (6, 7)]), 3);
+    }
+
+    #[test]
+    fn test_cycle_mode() {
+        let mut irq = VrcIrq::default();
+        irq.set_latch(0xF0);
+        irq.set_control(0x07);
+        assert_eq!(cycles_until_irq(&mut irq), 16);
+        // Acknowledging restores the enable-after-ack bit, so the counter keeps running.
+        irq.acknowledge();
+        assert_eq!(cycles_until_irq(&mut irq), 16);
+        irq.set_control(0x06);
+        irq.acknowledge();
+        irq.clock();
+        for _ in 0..16 {
+            irq.clock();
+        }
+        assert!(!irq.is_pending());
+    }
+
+    #[test]
+    fn test_scanline_mode() {
+        let mut irq = VrcIrq::default();
+        irq.set_latch_low(0x0D);
+        irq.set_latch_high(0x0F);
+        irq.set_control(0x02);
+        // Three scanlines of 113 2/3 cycles each.
+        assert_eq!(cycles_until_irq(&mut irq), 341);
+    }
+}
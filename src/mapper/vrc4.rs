@@ -0,0 +1,240 @@
+use super::vrc::{self, VrcIrq};
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Mappers 21, 22, 23 and 25: Konami VRC2 and VRC4. The boards differ in which address
+// lines select the registers, told apart by the NES 2.0 submapper.
+pub struct Vrc4 {
+    cartridge: Cartridge,
+    lines: &'static [(u8, u8)],
+    is_vrc2: bool,
+    // VRC2a connects CHR A10 to bank bit 1, so its banks are in 2 KiB steps.
+    character_shift: u8,
+    program_banks: [Byte; 2],
+    program_swap: bool,
+    character_banks: [u16; 8],
+    mirroring: Mirroring,
+    irq: VrcIrq,
+    // VRC2 boards without PRG RAM have a one-bit latch at $6000-$7FFF.
+    latch: Byte,
+}
+
+impl Vrc4 {
+    pub fn new(
+        cartridge: Cartridge,
+        lines: &'static [(u8, u8)],
+        is_vrc2: bool,
+        character_shift: u8,
+    ) -> Self {
+        let mirroring = cartridge.mirroring;
+        Vrc4 {
+            cartridge,
+            lines,
+            is_vrc2,
+            character_shift,
+            program_banks: [0, 0],
+            program_swap: false,
+            character_banks: [0; 8],
+            mirroring,
+            irq: VrcIrq::default(),
+            latch: 0,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        let (lines, is_vrc2, character_shift): (&'static [(u8, u8)], bool, u8) =
+            match (cartridge.mapper, cartridge.submapper) {
+                (21, 1) => (&[(1, 2)], false, 0),
+                (21, 2) => (&[(6, 7)], false, 0),
+                (21, _) => (&[(1, 2), (6, 7)], false, 0),
+                (22, _) => (&[(1, 0)], true, 1),
+                (23, 1) => (&[(0, 1)], false, 0),
+                (23, 2) => (&[(2, 3)], false, 0),
+                (23, 3) => (&[(0, 1)], true, 0),
+                (23, _) => (&[(0, 1), (2, 3)], false, 0),
+                (_, 1) => (&[(1, 0)], false, 0),
+                (_, 2) => (&[(3, 2)], false, 0),
+                (_, 3) => (&[(1, 0)], true, 0),
+                (_, _) => (&[(1, 0), (3, 2)], false, 0),
+            };
+        Box::new(Vrc4::new(cartridge, lines, is_vrc2, character_shift))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let second_last = (self.cartridge.program_rom.len() / 0x2000).max(2) - 2;
+        let bank = match (addr, self.program_swap) {
+            (0x8000..=0x9FFF, false) | (0xC000..=0xDFFF, true) => self.program_banks[0] as usize,
+            (0xA000..=0xBFFF, _) => self.program_banks[1] as usize,
+            (0xE000..=0xFFFF, _) => second_last + 1,
+            _ => second_last,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        let bank = self.character_banks[(addr >> 10) as usize & 0x07] >> self.character_shift;
+        bank_address(bank as usize, 0x400, addr)
+    }
+
+    fn write_register(&mut self, addr: Address, data: Byte) {
+        let register = vrc::register_index(addr, self.lines);
+        match (addr & 0xF000, register) {
+            (0x8000, _) => self.program_banks[0] = data & 0x1F,
+            (0x9000, _) if self.is_vrc2 => {
+                self.mirroring = vrc::mirroring(data & 0x01);
+            }
+            (0x9000, 0) | (0x9000, 1) => self.mirroring = vrc::mirroring(data),
+            (0x9000, _) => self.program_swap = data & 0x02 == 0x02,
+            (0xA000, _) => self.program_banks[1] = data & 0x1F,
+            (0xF000, _) if self.is_vrc2 => (),
+            (0xF000, 0) => self.irq.set_latch_low(data),
+            (0xF000, 1) => self.irq.set_latch_high(data),
+            (0xF000, 2) => self.irq.set_control(data),
+            (0xF000, _) => self.irq.acknowledge(),
+            (base, _) => {
+                let bank =
+                    &mut self.character_banks[((base - 0xB000) >> 11) as usize | register >> 1];
+                *bank = if register & 0x01 == 0 {
+                    (*bank & 0x1F0) | (data & 0x0F) as u16
+                } else {
+                    let mask = if self.is_vrc2 { 0x0F } else { 0x1F };
+                    (*bank & 0x0F) | ((data & mask) as u16) << 4
+                };
+            }
+        }
+    }
+}
+
+impl Mapper for Vrc4 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.cartridge.program_ram.is_empty() && self.is_vrc2 => {
+                // Only bit 0 is driven; the rest is open bus.
+                self.latch | ((addr >> 8) as Byte & 0xFE)
+            }
+            0x6000..=0x7FFF => self.cartridge.read_program_ram((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.cartridge.program_ram.is_empty() && self.is_vrc2 => {
+                self.latch = data & 0x01;
+            }
+            0x6000..=0x7FFF => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.is_pending()
+    }
+
+    fn on_cpu_cycle(&mut self) {
+        self.irq.clock();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    fn vrc(mapper: u16, submapper: u8) -> Box<dyn Mapper> {
+        let mut cartridge = cartridge(mapper, 8, 32);
+        cartridge.submapper = submapper;
+        Vrc4::boxed(cartridge)
+    }
+
+    #[test]
+    fn test_program_banking_and_swap() {
+        let mut mapper = vrc(21, 1);
+        mapper.cpu_write(0x8000, 3);
+        mapper.cpu_write(0xA000, 5);
+        assert_eq!(prg_index(&mut *mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut *mapper, 0xA000), 5 * 8);
+        assert_eq!(prg_index(&mut *mapper, 0xC000), 14 * 8);
+        assert_eq!(prg_index(&mut *mapper, 0xE000), 15 * 8);
+
+        // $9004 is register 2 with A2 as the high select line.
+        mapper.cpu_write(0x9004, 0x02);
+        assert_eq!(prg_index(&mut *mapper, 0x8000), 14 * 8);
+        assert_eq!(prg_index(&mut *mapper, 0xC000), 3 * 8);
+    }
+
+    #[test]
+    fn test_character_nibbles_by_submapper() {
+        // VRC4b (A1, A0), VRC4d (A3, A2) and VRC4e (A2, A3) address bank 1's high nibble
+        // through different addresses.
+        for &(mapper, submapper, high_nibble) in
+            [(25, 1, 0xB003), (25, 2, 0xB00C), (23, 2, 0xB00C)].iter()
+        {
+            let mut mapper = vrc(mapper, submapper);
+            mapper.cpu_write(0xB000, 0x0A);
+            mapper.cpu_write(high_nibble, 0x01);
+            assert_eq!(chr_index(&mut *mapper, 0x0400), 0x10, "high nibble");
+        }
+    }
+
+    #[test]
+    fn test_vrc2a_character_shift_and_mirroring() {
+        let mut mapper = vrc(22, 0);
+        mapper.cpu_write(0xB000, 0x06);
+        assert_eq!(chr_index(&mut *mapper, 0x0000), 3);
+        mapper.cpu_write(0x9000, 0x03);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn test_vrc2_latch_without_program_ram() {
+        let mut mapper = vrc(23, 3);
+        mapper.cartridge_mut().program_ram.clear();
+        mapper.cpu_write(0x6000, 0xFF);
+        assert_eq!(mapper.cpu_read(0x6000) & 0x01, 0x01);
+        mapper.cpu_write(0x6000, 0xFE);
+        assert_eq!(mapper.cpu_read(0x6000) & 0x01, 0x00);
+    }
+
+    #[test]
+    fn test_cycle_irq() {
+        let mut mapper = vrc(23, 1);
+        mapper.cpu_write(0xF000, 0x0E);
+        mapper.cpu_write(0xF001, 0x0F);
+        mapper.cpu_write(0xF002, 0x06);
+        mapper.on_cpu_cycle();
+        assert!(!mapper.irq());
+        mapper.on_cpu_cycle();
+        assert!(mapper.irq());
+        mapper.cpu_write(0xF003, 0);
+        assert!(!mapper.irq());
+    }
+}
@@ -0,0 +1,343 @@
+use super::vrc::{self, VrcIrq};
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Linear level of one VRC6 output step, matched to the APU pulse channels.
+const LEVEL: f32 = 0.00752;
+
+#[derive(Default)]
+struct Vrc6Pulse {
+    // Ignores the duty cycle and outputs the volume constantly.
+    digitized: bool,
+    duty: Byte,
+    volume: Byte,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: Byte,
+}
+
+impl Vrc6Pulse {
+    fn write(&mut self, register: usize, data: Byte) {
+        match register {
+            0 => {
+                self.digitized = data & 0x80 == 0x80;
+                self.duty = (data >> 4) & 0x07;
+                self.volume = data & 0x0F;
+            }
+            1 => self.period = (self.period & 0x0F00) | data as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((data & 0x0F) as u16) << 8;
+                self.enabled = data & 0x80 == 0x80;
+                if !self.enabled {
+                    self.step = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> shift;
+            self.step = (self.step + 1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> Byte {
+        if self.enabled && (self.digitized || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Default)]
+struct Sawtooth {
+    rate: Byte,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+    accumulator: Byte,
+}
+
+impl Sawtooth {
+    fn write(&mut self, register: usize, data: Byte) {
+        match register {
+            0 => self.rate = data & 0x3F,
+            1 => self.period = (self.period & 0x0F00) | data as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((data & 0x0F) as u16) << 8;
+                self.enabled = data & 0x80 == 0x80;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    // The accumulator grows by the rate on every other step and resets after 14 steps.
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period >> shift;
+        self.step += 1;
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step & 0x01 == 0 {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    fn output(&self) -> Byte {
+        self.accumulator >> 3
+    }
+}
+
+// Mappers 24 (VRC6a) and 26 (VRC6b, with A0 and A1 swapped), used by Akumajou Densetsu
+// and the Madara/Esper Dream 2 boards. CHR ROM nametables ($B003 bit 4) are not
+// supported; the mirroring bits are always applied to CIRAM.
+pub struct Vrc6 {
+    cartridge: Cartridge,
+    lines: &'static [(u8, u8)],
+    program_bank_16: Byte,
+    program_bank_8: Byte,
+    character_banks: [Byte; 8],
+    banking_control: Byte,
+    irq: VrcIrq,
+    pulses: [Vrc6Pulse; 2],
+    sawtooth: Sawtooth,
+    frequency_control: Byte,
+}
+
+impl Vrc6 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let lines: &'static [(u8, u8)] = if cartridge.mapper == 26 {
+            &[(1, 0)]
+        } else {
+            &[(0, 1)]
+        };
+        Vrc6 {
+            cartridge,
+            lines,
+            program_bank_16: 0,
+            program_bank_8: 0,
+            character_banks: [0; 8],
+            banking_control: 0,
+            irq: VrcIrq::default(),
+            pulses: [Vrc6Pulse::default(), Vrc6Pulse::default()],
+            sawtooth: Sawtooth::default(),
+            frequency_control: 0,
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Vrc6::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        match addr {
+            0x8000..=0xBFFF => bank_address(self.program_bank_16 as usize, 0x4000, addr),
+            0xC000..=0xDFFF => bank_address(self.program_bank_8 as usize, 0x2000, addr),
+            _ => bank_address(
+                (self.cartridge.program_rom.len() / 0x2000).max(1) - 1,
+                0x2000,
+                addr,
+            ),
+        }
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        let page = (addr >> 10) as usize & 0x07;
+        match (self.banking_control & 0x03, page) {
+            (0, _) => bank_address(self.character_banks[page] as usize, 0x400, addr),
+            (1, _) => bank_address(self.character_banks[page >> 1] as usize, 0x800, addr),
+            (_, 0..=3) => bank_address(self.character_banks[page] as usize, 0x400, addr),
+            (_, _) => bank_address(self.character_banks[2 + (page >> 1)] as usize, 0x800, addr),
+        }
+    }
+
+    // $9003 can halt the channels or speed them up by 16 or 256 for testing.
+    fn frequency_shift(&self) -> Option<u8> {
+        match self.frequency_control {
+            f if f & 0x01 == 0x01 => None,
+            f if f & 0x04 == 0x04 => Some(8),
+            f if f & 0x02 == 0x02 => Some(4),
+            _ => Some(0),
+        }
+    }
+
+    fn write_register(&mut self, addr: Address, data: Byte) {
+        let register = vrc::register_index(addr, self.lines);
+        match (addr & 0xF000, register) {
+            (0x8000, _) => self.program_bank_16 = data & 0x0F,
+            (0x9000, 3) => self.frequency_control = data,
+            (0x9000, _) => self.pulses[0].write(register, data),
+            (0xA000, 3) => (),
+            (0xA000, _) => self.pulses[1].write(register, data),
+            (0xB000, 3) => self.banking_control = data,
+            (0xB000, _) => self.sawtooth.write(register, data),
+            (0xC000, _) => self.program_bank_8 = data & 0x1F,
+            (0xD000, _) => self.character_banks[register] = data,
+            (0xE000, _) => self.character_banks[4 + register] = data,
+            (_, 0) => self.irq.set_latch(data),
+            (_, 1) => self.irq.set_control(data),
+            (_, 2) => self.irq.acknowledge(),
+            _ => (),
+        }
+    }
+
+    fn is_program_ram_enabled(&self) -> bool {
+        self.banking_control & 0x80 == 0x80
+    }
+}
+
+impl Mapper for Vrc6 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enabled() => {
+                self.cartridge.read_program_ram((addr - 0x6000) as usize)
+            }
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enabled() => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        vrc::mirroring(self.banking_control >> 2)
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.is_pending()
+    }
+
+    fn on_cpu_cycle(&mut self) {
+        self.irq.clock();
+        if let Some(shift) = self.frequency_shift() {
+            self.pulses.iter_mut().for_each(|pulse| pulse.clock(shift));
+            self.sawtooth.clock(shift);
+        }
+    }
+
+    fn audio_output(&self) -> f32 {
+        let output = self.pulses[0].output() + self.pulses[1].output() + self.sawtooth.output();
+        output as f32 * LEVEL
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn test_banking_with_swapped_lines() {
+        let mut mapper = Vrc6::new(cartridge(26, 8, 8));
+        mapper.cpu_write(0x8000, 2);
+        mapper.cpu_write(0xC000, 9);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 2 * 16);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 9 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 15 * 8);
+
+        // On VRC6b $D001 is R2 and $D002 is R1.
+        mapper.cpu_write(0xD001, 20);
+        mapper.cpu_write(0xD002, 21);
+        assert_eq!(chr_index(&mut mapper, 0x0400), 21);
+        assert_eq!(chr_index(&mut mapper, 0x0800), 20);
+
+        mapper.cpu_write(0xB003, 0x84);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+        mapper.cpu_write(0x6000, 0x5A);
+        assert_eq!(mapper.cpu_read(0x6000), 0x5A);
+    }
+
+    #[test]
+    fn test_pulse_duty() {
+        let mut mapper = Vrc6::new(cartridge(24, 2, 1));
+        // Duty 3 (4/16), volume 10, period 0 so every cycle advances a step.
+        mapper.cpu_write(0x9000, 0x3A);
+        mapper.cpu_write(0x9001, 0x00);
+        mapper.cpu_write(0x9002, 0x80);
+        let mut high = 0;
+        for _ in 0..16 {
+            mapper.on_cpu_cycle();
+            if mapper.audio_output() > 0.0 {
+                high += 1;
+            }
+        }
+        assert_eq!(high, 4);
+
+        mapper.cpu_write(0x9003, 0x01);
+        let before = mapper.audio_output();
+        mapper.on_cpu_cycle();
+        assert_eq!(mapper.audio_output(), before);
+    }
+
+    #[test]
+    fn test_sawtooth() {
+        let mut mapper = Vrc6::new(cartridge(24, 2, 1));
+        mapper.cpu_write(0xB000, 0x08);
+        mapper.cpu_write(0xB002, 0x80);
+        let levels: Vec<Byte> = (0..14)
+            .map(|_| {
+                mapper.on_cpu_cycle();
+                mapper.sawtooth.output()
+            })
+            .collect();
+        assert_eq!(levels, [0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 0]);
+    }
+
+    #[test]
+    fn test_irq() {
+        let mut mapper = Vrc6::new(cartridge(24, 2, 1));
+        mapper.cpu_write(0xF000, 0xFF);
+        mapper.cpu_write(0xF001, 0x06);
+        mapper.on_cpu_cycle();
+        assert!(mapper.irq());
+        mapper.cpu_write(0xF002, 0);
+        assert!(!mapper.irq());
+    }
+}
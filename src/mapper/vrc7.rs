@@ -0,0 +1,535 @@
+use std::f32::consts::TAU;
+
+use super::vrc::{self, VrcIrq};
+use super::{bank_address, Mapper};
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::types::{Address, Byte};
+
+// Built-in instruments 1-15 of the VRC7's OPLL core. Instrument 0 is the custom patch
+// written to registers $00-$07.
+const PATCHES: [[Byte; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+const MULTIPLIERS: [f32; 16] = [
+    0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 10.0, 12.0, 12.0, 15.0, 15.0,
+];
+
+// The OPLL produces one sample every 36 CPU cycles.
+const SAMPLE_PERIOD: u8 = 36;
+const SAMPLE_RATE: f32 = 1_789_773.0 / SAMPLE_PERIOD as f32;
+
+// Envelope attenuation at which an operator is considered silent.
+const SILENT_DB: f32 = 48.0;
+
+// Tremolo (3.7 Hz, 4.8 dB) and vibrato (6.4 Hz, 7 cents) of the AM/VIB patch bits.
+const TREMOLO_RATE: f32 = 3.7;
+const TREMOLO_DB: f32 = 4.8;
+const VIBRATO_RATE: f32 = 6.4;
+const VIBRATO_CENTS: f32 = 7.0;
+
+// Carrier phase offset, in waveform cycles, of a full-scale modulator.
+const MODULATION_DEPTH: f32 = 2.0;
+
+// Output level of one channel at full volume, on the APU mixer scale.
+const CHANNEL_LEVEL: f32 = 0.06;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum EnvelopeState {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    #[default]
+    Off,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Operator {
+    // Position in the waveform, in cycles.
+    phase: f32,
+    // Envelope attenuation in dB.
+    envelope: f32,
+    state: EnvelopeState,
+}
+
+// The parameters of one operator decoded from a patch.
+struct OperatorPatch {
+    tremolo: bool,
+    vibrato: bool,
+    sustained: bool,
+    key_scale_rate: bool,
+    multiplier: f32,
+    attack: u8,
+    decay: u8,
+    sustain_level: f32,
+    release: u8,
+    rectified: bool,
+}
+
+impl OperatorPatch {
+    fn new(patch: &[Byte; 8], index: usize) -> Self {
+        OperatorPatch {
+            tremolo: patch[index] & 0x80 == 0x80,
+            vibrato: patch[index] & 0x40 == 0x40,
+            sustained: patch[index] & 0x20 == 0x20,
+            key_scale_rate: patch[index] & 0x10 == 0x10,
+            multiplier: MULTIPLIERS[(patch[index] & 0x0F) as usize],
+            attack: patch[4 + index] >> 4,
+            decay: patch[4 + index] & 0x0F,
+            sustain_level: (patch[6 + index] >> 4) as f32 * 3.0,
+            release: patch[6 + index] & 0x0F,
+            rectified: patch[3] & (0x08 << index) != 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Channel {
+    fnum: u16,
+    block: u8,
+    key_on: bool,
+    sustain: bool,
+    instrument: u8,
+    volume: u8,
+    operators: [Operator; 2],
+    // The last two modulator outputs, averaged for self-feedback.
+    feedback: [f32; 2],
+}
+
+impl Channel {
+    fn key_scale(&self, patch: &OperatorPatch) -> u8 {
+        if patch.key_scale_rate {
+            self.block * 2 + (self.fnum >> 8) as u8
+        } else {
+            self.block >> 1
+        }
+    }
+
+    fn set_key(&mut self, key_on: bool) {
+        if key_on && !self.key_on {
+            for operator in self.operators.iter_mut() {
+                operator.phase = 0.0;
+                operator.state = EnvelopeState::Attack;
+            }
+        } else if !key_on && self.key_on {
+            for operator in self.operators.iter_mut() {
+                if operator.state != EnvelopeState::Off {
+                    operator.state = EnvelopeState::Release;
+                }
+            }
+        }
+        self.key_on = key_on;
+    }
+
+    fn update_envelope(&mut self, index: usize, patch: &OperatorPatch) {
+        let key_scale = self.key_scale(patch);
+        let release = if self.sustain {
+            5
+        } else if patch.sustained {
+            patch.release
+        } else {
+            7
+        };
+        let operator = &mut self.operators[index];
+        match operator.state {
+            EnvelopeState::Attack if patch.attack == 15 => {
+                operator.envelope = 0.0;
+                operator.state = EnvelopeState::Decay;
+            }
+            EnvelopeState::Attack => {
+                operator.envelope -= rate_step(patch.attack, key_scale) * 8.0;
+                if operator.envelope <= 0.0 {
+                    operator.envelope = 0.0;
+                    operator.state = EnvelopeState::Decay;
+                }
+            }
+            EnvelopeState::Decay => {
+                operator.envelope += rate_step(patch.decay, key_scale);
+                if operator.envelope >= patch.sustain_level {
+                    operator.envelope = patch.sustain_level;
+                    operator.state = EnvelopeState::Sustain;
+                }
+            }
+            // Percussive patches keep decaying with the release rate while the key is held.
+            EnvelopeState::Sustain if !patch.sustained => {
+                operator.envelope += rate_step(patch.release, key_scale);
+            }
+            EnvelopeState::Sustain => (),
+            EnvelopeState::Release => operator.envelope += rate_step(release, key_scale),
+            EnvelopeState::Off => operator.envelope = SILENT_DB,
+        }
+        if operator.envelope >= SILENT_DB {
+            operator.envelope = SILENT_DB;
+            if operator.state != EnvelopeState::Attack {
+                operator.state = EnvelopeState::Off;
+            }
+        }
+    }
+}
+
+// Envelope change per sample, in dB, at a 4-bit rate. A full decay takes about 10 seconds
+// at rate 1 and halves with every step of the key-scaled rate.
+fn rate_step(rate: u8, key_scale: u8) -> f32 {
+    if rate == 0 {
+        return 0.0;
+    }
+    let scaled = (rate * 4 + key_scale).min(63) as f32;
+    SILENT_DB / (SAMPLE_RATE * 10.0) * 2f32.powf((scaled - 4.0) / 4.0)
+}
+
+fn operator_output(phase: f32, attenuation: f32, rectified: bool) -> f32 {
+    if attenuation >= SILENT_DB {
+        return 0.0;
+    }
+    let wave = (phase * TAU).sin();
+    let wave = if rectified && wave < 0.0 { 0.0 } else { wave };
+    wave * 10f32.powf(-attenuation / 20.0)
+}
+
+// The six-channel, two-operator FM synthesizer in the VRC7 (a reduced YM2413). Key scale
+// level is not modeled and the envelope curves are approximated in dB.
+#[derive(Default)]
+struct Opll {
+    address: Byte,
+    custom: [Byte; 8],
+    channels: [Channel; 6],
+    cycle: u8,
+    tremolo_phase: f32,
+    vibrato_phase: f32,
+    output: f32,
+}
+
+impl Opll {
+    fn write(&mut self, data: Byte) {
+        let channel = (self.address & 0x0F) as usize;
+        match self.address {
+            0x00..=0x07 => self.custom[self.address as usize] = data,
+            0x10..=0x15 => {
+                self.channels[channel].fnum = (self.channels[channel].fnum & 0x100) | data as u16
+            }
+            0x20..=0x25 => {
+                let channel = &mut self.channels[channel];
+                channel.fnum = (channel.fnum & 0xFF) | ((data & 0x01) as u16) << 8;
+                channel.block = (data >> 1) & 0x07;
+                channel.sustain = data & 0x20 == 0x20;
+                channel.set_key(data & 0x10 == 0x10);
+            }
+            0x30..=0x35 => {
+                self.channels[channel].instrument = data >> 4;
+                self.channels[channel].volume = data & 0x0F;
+            }
+            _ => (),
+        }
+    }
+
+    fn clock(&mut self) {
+        self.cycle += 1;
+        if self.cycle == SAMPLE_PERIOD {
+            self.cycle = 0;
+            self.output = self.sample();
+        }
+    }
+
+    fn sample(&mut self) -> f32 {
+        self.tremolo_phase = (self.tremolo_phase + TREMOLO_RATE / SAMPLE_RATE).fract();
+        self.vibrato_phase = (self.vibrato_phase + VIBRATO_RATE / SAMPLE_RATE).fract();
+        let tremolo = (1.0 - (self.tremolo_phase * TAU).cos()) * 0.5 * TREMOLO_DB;
+        let vibrato = 2f32.powf((self.vibrato_phase * TAU).sin() * VIBRATO_CENTS / 1200.0);
+
+        let custom = self.custom;
+        let mut output = 0.0;
+        for channel in self.channels.iter_mut() {
+            let patch = if channel.instrument == 0 {
+                &custom
+            } else {
+                &PATCHES[channel.instrument as usize - 1]
+            };
+            let operators = [OperatorPatch::new(patch, 0), OperatorPatch::new(patch, 1)];
+            let base = channel.fnum as f32 * 2f32.powi(channel.block as i32) / (1 << 19) as f32;
+
+            for (index, operator) in operators.iter().enumerate() {
+                channel.update_envelope(index, operator);
+                let step =
+                    base * operator.multiplier * if operator.vibrato { vibrato } else { 1.0 };
+                let phase = &mut channel.operators[index].phase;
+                *phase = (*phase + step).fract();
+            }
+
+            let [modulator, carrier] = operators;
+            let feedback = match patch[3] & 0x07 {
+                0 => 0.0,
+                level => {
+                    (channel.feedback[0] + channel.feedback[1]) * 0.5 * 2f32.powi(level as i32 - 7)
+                }
+            };
+            let attenuation = channel.operators[0].envelope
+                + (patch[2] & 0x3F) as f32 * 0.75
+                + if modulator.tremolo { tremolo } else { 0.0 };
+            let modulation = operator_output(
+                channel.operators[0].phase + feedback,
+                attenuation,
+                modulator.rectified,
+            );
+            channel.feedback = [channel.feedback[1], modulation];
+
+            let attenuation = channel.operators[1].envelope
+                + channel.volume as f32 * 3.0
+                + if carrier.tremolo { tremolo } else { 0.0 };
+            output += operator_output(
+                channel.operators[1].phase + modulation * MODULATION_DEPTH,
+                attenuation,
+                carrier.rectified,
+            );
+        }
+        output * CHANNEL_LEVEL
+    }
+}
+
+// Mapper 85: Konami VRC7 (Lagrange Point, Tiny Toon Adventures 2). VRC7b (submapper 1)
+// selects registers with A3 and VRC7a (submapper 2) with A4.
+pub struct Vrc7 {
+    cartridge: Cartridge,
+    select_lines: &'static [u8],
+    program_banks: [Byte; 3],
+    character_banks: [Byte; 8],
+    control: Byte,
+    irq: VrcIrq,
+    opll: Opll,
+}
+
+impl Vrc7 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let select_lines: &'static [u8] = match cartridge.submapper {
+            1 => &[3],
+            2 => &[4],
+            _ => &[3, 4],
+        };
+        Vrc7 {
+            cartridge,
+            select_lines,
+            program_banks: [0; 3],
+            character_banks: [0; 8],
+            control: 0,
+            irq: VrcIrq::default(),
+            opll: Opll::default(),
+        }
+    }
+
+    pub fn boxed(cartridge: Cartridge) -> Box<dyn Mapper> {
+        Box::new(Vrc7::new(cartridge))
+    }
+
+    fn program_rom_address(&self, addr: Address) -> usize {
+        let bank = match addr {
+            0x8000..=0xDFFF => self.program_banks[((addr - 0x8000) >> 13) as usize] as usize,
+            _ => (self.cartridge.program_rom.len() / 0x2000).max(1) - 1,
+        };
+        bank_address(bank, 0x2000, addr)
+    }
+
+    fn character_address(&self, addr: Address) -> usize {
+        let bank = self.character_banks[(addr >> 10) as usize & 0x07];
+        bank_address(bank as usize, 0x400, addr)
+    }
+
+    fn is_program_ram_enabled(&self) -> bool {
+        self.control & 0x80 == 0x80
+    }
+
+    fn is_audio_silenced(&self) -> bool {
+        self.control & 0x40 == 0x40
+    }
+
+    fn write_register(&mut self, addr: Address, data: Byte) {
+        // The audio ports are decoded the same way on both board revisions.
+        match addr & 0xF030 {
+            0x9010 => self.opll.address = data,
+            0x9030 => self.opll.write(data),
+            _ => self.write_bank_register(addr, data),
+        }
+    }
+
+    fn write_bank_register(&mut self, addr: Address, data: Byte) {
+        let register = self
+            .select_lines
+            .iter()
+            .any(|&line| (addr >> line) & 0x01 == 0x01) as usize;
+        match (addr & 0xF000, register) {
+            (0x8000, _) => self.program_banks[register] = data & 0x3F,
+            (0x9000, 0) => self.program_banks[2] = data & 0x3F,
+            (0x9000, _) => (),
+            (0xE000, 0) => {
+                self.control = data;
+                if self.is_audio_silenced() {
+                    self.opll = Opll::default();
+                }
+            }
+            (0xE000, _) => self.irq.set_latch(data),
+            (0xF000, 0) => self.irq.set_control(data),
+            (0xF000, _) => self.irq.acknowledge(),
+            (base, _) => self.character_banks[((base - 0xA000) >> 11) as usize | register] = data,
+        }
+    }
+}
+
+impl Mapper for Vrc7 {
+    fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    fn cpu_read(&mut self, addr: Address) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enabled() => {
+                self.cartridge.read_program_ram((addr - 0x6000) as usize)
+            }
+            0x8000..=0xFFFF => self
+                .cartridge
+                .read_program_rom(self.program_rom_address(addr)),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: Address, data: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enabled() => self
+                .cartridge
+                .write_program_ram((addr - 0x6000) as usize, data),
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: Address) -> Byte {
+        self.cartridge.read_character(self.character_address(addr))
+    }
+
+    fn ppu_write(&mut self, addr: Address, data: Byte) {
+        let addr = self.character_address(addr);
+        self.cartridge.write_character(addr, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        vrc::mirroring(self.control)
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.is_pending()
+    }
+
+    fn on_cpu_cycle(&mut self) {
+        self.irq.clock();
+        if !self.is_audio_silenced() {
+            self.opll.clock();
+        }
+    }
+
+    fn audio_output(&self) -> f32 {
+        if self.is_audio_silenced() {
+            0.0
+        } else {
+            self.opll.output
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    fn write_audio(mapper: &mut Vrc7, register: Byte, data: Byte) {
+        mapper.cpu_write(0x9010, register);
+        mapper.cpu_write(0x9030, data);
+    }
+
+    fn peak(mapper: &mut Vrc7, cycles: usize) -> f32 {
+        (0..cycles).fold(0.0, |peak, _| {
+            mapper.on_cpu_cycle();
+            peak.max(mapper.audio_output().abs())
+        })
+    }
+
+    #[test]
+    fn test_banking_by_submapper() {
+        let mut cartridge = cartridge(85, 8, 8);
+        cartridge.submapper = 2;
+        let mut mapper = Vrc7::new(cartridge);
+        mapper.cpu_write(0x8000, 3);
+        mapper.cpu_write(0x8010, 4);
+        mapper.cpu_write(0x9000, 5);
+        // A3 is not a select line on VRC7a.
+        mapper.cpu_write(0x9008, 6);
+        assert_eq!(prg_index(&mut mapper, 0x8000), 3 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xA000), 4 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xC000), 6 * 8);
+        assert_eq!(prg_index(&mut mapper, 0xE000), 15 * 8);
+
+        mapper.cpu_write(0xA010, 11);
+        mapper.cpu_write(0xD000, 12);
+        assert_eq!(chr_index(&mut mapper, 0x0400), 11);
+        assert_eq!(chr_index(&mut mapper, 0x1800), 12);
+
+        mapper.cpu_write(0xE000, 0x81);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+        mapper.cpu_write(0x6000, 0x77);
+        assert_eq!(mapper.cpu_read(0x6000), 0x77);
+    }
+
+    #[test]
+    fn test_irq() {
+        let mut mapper = Vrc7::new(cartridge(85, 2, 1));
+        mapper.cpu_write(0xE008, 0xFE);
+        mapper.cpu_write(0xF000, 0x06);
+        mapper.on_cpu_cycle();
+        assert!(!mapper.irq());
+        mapper.on_cpu_cycle();
+        assert!(mapper.irq());
+        mapper.cpu_write(0xF010, 0);
+        assert!(!mapper.irq());
+    }
+
+    #[test]
+    fn test_fm_key_on_and_off() {
+        let mut mapper = Vrc7::new(cartridge(85, 2, 1));
+        assert_eq!(peak(&mut mapper, 0x1000), 0.0);
+
+        // Flute on channel 0 at full volume, A4 (fnum 0x120, block 4).
+        write_audio(&mut mapper, 0x30, 0x40);
+        write_audio(&mut mapper, 0x10, 0x20);
+        write_audio(&mut mapper, 0x20, 0x19);
+        assert!(peak(&mut mapper, 0x20000) > 0.01);
+
+        write_audio(&mut mapper, 0x20, 0x09);
+        peak(&mut mapper, 1_789_773);
+        assert_eq!(peak(&mut mapper, 0x1000), 0.0);
+    }
+
+    #[test]
+    fn test_silence_bit() {
+        let mut mapper = Vrc7::new(cartridge(85, 2, 1));
+        write_audio(&mut mapper, 0x30, 0x30);
+        write_audio(&mut mapper, 0x20, 0x19);
+        assert!(peak(&mut mapper, 0x4000) > 0.0);
+        mapper.cpu_write(0xE000, 0x40);
+        assert_eq!(peak(&mut mapper, 0x1000), 0.0);
+    }
+}
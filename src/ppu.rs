@@ -0,0 +1,1009 @@
+pub mod background;
+pub mod sprites;
+
+use self::background::Background;
+use self::sprites::Sprites;
+use crate::mapper::Mapper;
+use crate::types::{Address, Byte, Word};
+
+// PPUCTRL ($2000) bits.
+const CONTROL_INCREMENT_32: Byte = 0x04;
+const CONTROL_SPRITE_TABLE: Byte = 0x08;
+const CONTROL_BACKGROUND_TABLE: Byte = 0x10;
+const CONTROL_SPRITE_SIZE_16: Byte = 0x20;
+const CONTROL_NMI_ENABLE: Byte = 0x80;
+
+// PPUMASK ($2001) bits.
+const MASK_GREYSCALE: Byte = 0x01;
+const MASK_SHOW_BACKGROUND_LEFT: Byte = 0x02;
+const MASK_SHOW_SPRITES_LEFT: Byte = 0x04;
+const MASK_SHOW_BACKGROUND: Byte = 0x08;
+const MASK_SHOW_SPRITES: Byte = 0x10;
+const MASK_EMPHASIS: Byte = 0xE0;
+
+// PPUSTATUS ($2002) bits.
+const STATUS_SPRITE_OVERFLOW: Byte = 0x20;
+const STATUS_SPRITE_ZERO_HIT: Byte = 0x40;
+const STATUS_VBLANK: Byte = 0x80;
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+pub const DOTS_PER_SCANLINE: u16 = 341;
+pub const SCANLINES_PER_FRAME: u16 = 262;
+const VISIBLE_SCANLINES: u16 = 240;
+const VBLANK_SCANLINE: u16 = 241;
+const PRE_RENDER_SCANLINE: u16 = 261;
+
+pub struct Ppu {
+    control: Byte,
+    mask: Byte,
+    status: Byte,
+    oam_address: Byte,
+    oam: [Byte; 0x100],
+    // The loopy registers: v is the current VRAM address, t the temporary address the
+    // scroll and address writes go to, x the fine X scroll and w the write toggle
+    // shared by $2005 and $2006.
+    v: Address,
+    t: Address,
+    x: Byte,
+    w: bool,
+    // PPUDATA reads below the palette return the previous read's byte.
+    read_buffer: Byte,
+    // The data bus latch that write-only registers read back as.
+    io_latch: Byte,
+    // 2 KiB of CIRAM in pages 0-1, followed by the 2 KiB four-screen boards add.
+    nametable_ram: [Byte; 0x1000],
+    palette_ram: [Byte; 0x20],
+    background: Background,
+    sprites: Sprites,
+    // The low sprite pattern byte between its fetch and the high byte's.
+    sprite_pattern_low: Byte,
+    // One NES color (0-63) per pixel, with the PPUMASK emphasis bits in bits 6-8.
+    framebuffer: Vec<Word>,
+    is_frame_ready: bool,
+    scanline: u16,
+    dot: u16,
+    is_odd_frame: bool,
+    nmi_line: bool,
+    nmi_pending: bool,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Ppu {
+            control: 0,
+            mask: 0,
+            status: 0,
+            oam_address: 0,
+            oam: [0; 0x100],
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+            read_buffer: 0,
+            io_latch: 0,
+            nametable_ram: [0; 0x1000],
+            palette_ram: [0; 0x20],
+            background: Background::default(),
+            sprites: Sprites::default(),
+            sprite_pattern_low: 0,
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            is_frame_ready: false,
+            scanline: 0,
+            dot: 0,
+            is_odd_frame: false,
+            nmi_line: false,
+            nmi_pending: false,
+        }
+    }
+
+    pub fn get_scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn get_dot(&self) -> u16 {
+        self.dot
+    }
+
+    pub fn is_rendering_enabled(&self) -> bool {
+        self.mask & (MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES) != 0
+    }
+
+    pub fn get_framebuffer(&self) -> &[Word] {
+        &self.framebuffer
+    }
+
+    // Returns true once per frame, when the PPU enters VBlank with a finished picture.
+    pub fn take_frame(&mut self) -> bool {
+        std::mem::replace(&mut self.is_frame_ready, false)
+    }
+
+    fn is_rendering_scanline(&self) -> bool {
+        self.scanline < VISIBLE_SCANLINES || self.scanline == PRE_RENDER_SCANLINE
+    }
+
+    // Returns true once for every rising edge of the NMI output, which is the
+    // VBlank flag ANDed with the PPUCTRL NMI enable.
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::replace(&mut self.nmi_pending, false)
+    }
+
+    fn update_nmi(&mut self) {
+        let line = self.status & STATUS_VBLANK != 0 && self.control & CONTROL_NMI_ENABLE != 0;
+        if line && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = line;
+    }
+
+    // CPU reads of $2000-$3FFF; the eight registers repeat every 8 bytes.
+    pub fn read_register(&mut self, addr: Address, mapper: &mut dyn Mapper) -> Byte {
+        let data = match addr & 0x0007 {
+            0x0002 => {
+                let data = (self.status & 0xE0) | (self.io_latch & 0x1F);
+                self.status &= !STATUS_VBLANK;
+                self.w = false;
+                self.update_nmi();
+                data
+            }
+            // Secondary OAM clearing makes OAMDATA read $FF during dots 1-64.
+            0x0004
+                if self.is_rendering_enabled()
+                    && self.scanline < VISIBLE_SCANLINES
+                    && (1..=64).contains(&self.dot) =>
+            {
+                0xFF
+            }
+            // Bits 2-4 of the attribute byte do not exist.
+            0x0004 if self.oam_address & 0x03 == 0x02 => self.oam[self.oam_address as usize] & 0xE3,
+            0x0004 => self.oam[self.oam_address as usize],
+            0x0007 => {
+                let addr = self.v & 0x3FFF;
+                let data = if addr >= 0x3F00 {
+                    // Palette reads are immediate; the buffer gets the nametable byte below.
+                    self.read_buffer = self.read_memory(addr - 0x1000, mapper);
+                    let color = self.read_memory(addr, mapper);
+                    self.apply_greyscale(color) | (self.io_latch & 0xC0)
+                } else {
+                    let data = self.read_buffer;
+                    self.read_buffer = self.read_memory(addr, mapper);
+                    data
+                };
+                self.increment_v();
+                data
+            }
+            _ => self.io_latch,
+        };
+        self.io_latch = data;
+        data
+    }
+
+    // CPU writes to $2000-$3FFF.
+    pub fn write_register(&mut self, addr: Address, data: Byte, mapper: &mut dyn Mapper) {
+        self.io_latch = data;
+        match addr & 0x0007 {
+            0x0000 => {
+                self.control = data;
+                self.t = (self.t & 0xF3FF) | ((data & 0x03) as Address) << 10;
+                self.update_nmi();
+            }
+            0x0001 => self.mask = data,
+            0x0003 => self.oam_address = data,
+            0x0004 => {
+                self.oam[self.oam_address as usize] = data;
+                self.oam_address = self.oam_address.wrapping_add(1);
+            }
+            0x0005 if !self.w => {
+                self.t = (self.t & 0xFFE0) | (data >> 3) as Address;
+                self.x = data & 0x07;
+                self.w = true;
+            }
+            0x0005 => {
+                self.t = (self.t & 0x8C1F)
+                    | ((data & 0x07) as Address) << 12
+                    | ((data & 0xF8) as Address) << 2;
+                self.w = false;
+            }
+            0x0006 if !self.w => {
+                self.t = (self.t & 0x00FF) | ((data & 0x3F) as Address) << 8;
+                self.w = true;
+            }
+            0x0006 => {
+                self.t = (self.t & 0xFF00) | data as Address;
+                self.v = self.t;
+                self.w = false;
+            }
+            0x0007 => {
+                self.write_memory(self.v & 0x3FFF, data, mapper);
+                self.increment_v();
+            }
+            _ => (),
+        }
+    }
+
+    // PPUDATA accesses while rendering bump v through the rendering increments instead.
+    fn increment_v(&mut self) {
+        if self.is_rendering_enabled() && self.is_rendering_scanline() {
+            self.increment_coarse_x();
+            self.increment_y();
+            return;
+        }
+        let step = if self.control & CONTROL_INCREMENT_32 != 0 {
+            32
+        } else {
+            1
+        };
+        self.v = self.v.wrapping_add(step) & 0x7FFF;
+    }
+
+    // Coarse X wraps at 32 tiles into the horizontally adjacent nametable.
+    fn increment_coarse_x(&mut self) {
+        if self.v & 0x001F == 0x001F {
+            self.v = (self.v & !0x001F) ^ 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    // Fine Y carries into coarse Y, which wraps at row 30 into the vertically adjacent
+    // nametable; rows 30 and 31 (attribute memory) wrap without switching.
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let coarse_y = match (self.v & 0x03E0) >> 5 {
+            29 => {
+                self.v ^= 0x0800;
+                0
+            }
+            31 => 0,
+            y => y + 1,
+        };
+        self.v = (self.v & !0x03E0) | coarse_y << 5;
+    }
+
+    fn copy_horizontal(&mut self) {
+        self.v = (self.v & !0x041F) | (self.t & 0x041F);
+    }
+
+    fn copy_vertical(&mut self) {
+        self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+    }
+
+    fn nametable_index(&self, addr: Address, mapper: &dyn Mapper) -> usize {
+        let table = ((addr >> 10) & 0x03) as usize;
+        (mapper.ciram_page(table) & 0x03) << 10 | (addr & 0x03FF) as usize
+    }
+
+    // Entries $10/$14/$18/$1C are the backdrop entries $00/$04/$08/$0C.
+    fn palette_index(addr: Address) -> usize {
+        let index = (addr & 0x1F) as usize;
+        if index & 0x13 == 0x10 {
+            index & 0x0F
+        } else {
+            index
+        }
+    }
+
+    fn apply_greyscale(&self, color: Byte) -> Byte {
+        if self.mask & MASK_GREYSCALE != 0 {
+            color & 0x30
+        } else {
+            color
+        }
+    }
+
+    // The PPU address space: pattern tables on the cartridge, nametables in CIRAM unless
+    // the board answers them itself, and the palette.
+    fn read_memory(&mut self, addr: Address, mapper: &mut dyn Mapper) -> Byte {
+        mapper.on_ppu_address(addr);
+        match addr {
+            0x0000..=0x1FFF => mapper.ppu_read(addr),
+            0x2000..=0x3EFF => {
+                let addr = 0x2000 | (addr & 0x0FFF);
+                match mapper.read_nametable(addr) {
+                    Some(data) => data,
+                    None => self.nametable_ram[self.nametable_index(addr, mapper)],
+                }
+            }
+            _ => self.palette_ram[Self::palette_index(addr)],
+        }
+    }
+
+    fn write_memory(&mut self, addr: Address, data: Byte, mapper: &mut dyn Mapper) {
+        mapper.on_ppu_address(addr);
+        match addr {
+            0x0000..=0x1FFF => mapper.ppu_write(addr, data),
+            0x2000..=0x3EFF => {
+                let addr = 0x2000 | (addr & 0x0FFF);
+                if !mapper.write_nametable(addr, data) {
+                    let index = self.nametable_index(addr, mapper);
+                    self.nametable_ram[index] = data;
+                }
+            }
+            // Palette entries are 6 bits wide.
+            _ => self.palette_ram[Self::palette_index(addr)] = data & 0x3F,
+        }
+    }
+
+    fn nametable_address(&self) -> Address {
+        0x2000 | (self.v & 0x0FFF)
+    }
+
+    fn fetch_background(&mut self, mapper: &mut dyn Mapper) {
+        let table = if self.control & CONTROL_BACKGROUND_TABLE != 0 {
+            0x1000
+        } else {
+            0x0000
+        };
+        let pattern = table | (self.background.get_nametable() as Address) << 4 | self.v >> 12;
+        match self.dot % 8 {
+            1 => {
+                let data = self.read_memory(self.nametable_address(), mapper);
+                self.background.set_nametable(data);
+            }
+            3 => {
+                let addr =
+                    0x23C0 | (self.v & 0x0C00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07);
+                let data = self.read_memory(addr, mapper);
+                self.background
+                    .set_attribute(data, ((self.v >> 4) & 0x04) | (self.v & 0x02));
+            }
+            5 => {
+                let data = self.read_memory(pattern, mapper);
+                self.background.set_pattern_low(data);
+            }
+            7 => {
+                let data = self.read_memory(pattern + 8, mapper);
+                self.background.set_pattern_high(data);
+            }
+            0 => self.increment_coarse_x(),
+            _ => (),
+        }
+    }
+
+    fn sprite_height(&self) -> u16 {
+        if self.control & CONTROL_SPRITE_SIZE_16 != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    // Dots 257-320 fetch the patterns of the sprites found for the next scanline, each
+    // slot preceded by two garbage nametable reads.
+    fn fetch_sprites(&mut self, mapper: &mut dyn Mapper) {
+        let slot = ((self.dot - 257) / 8) as usize;
+        let table = if self.control & CONTROL_SPRITE_TABLE != 0 {
+            0x1000
+        } else {
+            0x0000
+        };
+        let pattern =
+            self.sprites
+                .pattern_address(slot, self.scanline, table, self.sprite_height());
+        match (self.dot - 257) % 8 {
+            0 | 2 => {
+                self.read_memory(self.nametable_address(), mapper);
+            }
+            4 => self.sprite_pattern_low = self.read_memory(pattern, mapper),
+            6 => {
+                let pattern_high = self.read_memory(pattern + 8, mapper);
+                self.sprites
+                    .load_slot(slot, self.sprite_pattern_low, pattern_high);
+            }
+            _ => (),
+        }
+    }
+
+    fn evaluate_sprites(&mut self) {
+        match self.dot {
+            1 => self.sprites.clear_secondary_oam(),
+            65 if self.scanline < VISIBLE_SCANLINES => self.sprites.start_evaluation(),
+            66..=256 if self.scanline < VISIBLE_SCANLINES && self.dot.is_multiple_of(2) => {
+                let height = self.sprite_height();
+                if self.sprites.evaluate(&self.oam, self.scanline, height) {
+                    self.status |= STATUS_SPRITE_OVERFLOW;
+                }
+            }
+            257 => self.sprites.start_loading(),
+            _ => (),
+        }
+    }
+
+    // One dot of a visible or pre-render scanline with rendering enabled.
+    fn render_dot(&mut self, mapper: &mut dyn Mapper) {
+        let dot = self.dot;
+        if (2..=257).contains(&dot) || (322..=337).contains(&dot) {
+            self.background.shift();
+        }
+        if (9..=257).contains(&dot) && dot % 8 == 1 || dot == 329 || dot == 337 {
+            self.background.reload();
+        }
+        self.evaluate_sprites();
+        match dot {
+            1..=256 | 321..=336 => self.fetch_background(mapper),
+            257..=320 => {
+                self.oam_address = 0;
+                self.fetch_sprites(mapper);
+            }
+            // Two unused nametable fetches end the line.
+            337 | 339 => {
+                self.read_memory(self.nametable_address(), mapper);
+            }
+            _ => (),
+        }
+        match dot {
+            256 => self.increment_y(),
+            257 => self.copy_horizontal(),
+            280..=304 if self.scanline == PRE_RENDER_SCANLINE => self.copy_vertical(),
+            _ => (),
+        }
+    }
+
+    fn render_pixel(&mut self) {
+        let x = self.dot - 1;
+        let color = if self.is_rendering_enabled() {
+            let show_background = self.mask & MASK_SHOW_BACKGROUND != 0
+                && (x >= 8 || self.mask & MASK_SHOW_BACKGROUND_LEFT != 0);
+            let show_sprites = self.mask & MASK_SHOW_SPRITES != 0
+                && (x >= 8 || self.mask & MASK_SHOW_SPRITES_LEFT != 0);
+            let background = if show_background {
+                self.background.get_pixel(self.x)
+            } else {
+                0
+            };
+            let sprite = if show_sprites {
+                self.sprites.get_pixel(x)
+            } else {
+                None
+            };
+            let is_background_opaque = background & 0x03 != 0;
+            // Transparent pixels show the universal background color.
+            let index = match sprite {
+                Some(sprite) => {
+                    if sprite.is_sprite_zero && is_background_opaque && x != 255 {
+                        self.status |= STATUS_SPRITE_ZERO_HIT;
+                    }
+                    if sprite.is_behind_background && is_background_opaque {
+                        background
+                    } else {
+                        sprite.index
+                    }
+                }
+                None if is_background_opaque => background,
+                None => 0,
+            };
+            self.palette_ram[Self::palette_index(index as Address)]
+        } else if self.v & 0x3F00 == 0x3F00 {
+            // With rendering off, pointing v into the palette displays that entry.
+            self.palette_ram[Self::palette_index(self.v)]
+        } else {
+            self.palette_ram[0]
+        };
+        let emphasis = ((self.mask & MASK_EMPHASIS) as Word) << 1;
+        self.framebuffer[self.scanline as usize * SCREEN_WIDTH + x as usize] =
+            emphasis | self.apply_greyscale(color) as Word;
+    }
+
+    // Advances one dot. The frame is 262 scanlines of 341 dots; VBlank starts at dot 1
+    // of scanline 241 and ends at dot 1 of the pre-render scanline 261.
+    pub fn step(&mut self, mapper: &mut dyn Mapper) {
+        if self.is_rendering_enabled() && self.is_rendering_scanline() {
+            self.render_dot(mapper);
+        }
+        if self.scanline < VISIBLE_SCANLINES && (1..=256).contains(&self.dot) {
+            self.render_pixel();
+        }
+        match (self.scanline, self.dot) {
+            (VBLANK_SCANLINE, 1) => {
+                self.status |= STATUS_VBLANK;
+                self.is_frame_ready = true;
+                self.update_nmi();
+            }
+            (PRE_RENDER_SCANLINE, 1) => {
+                self.status &= !(STATUS_VBLANK | STATUS_SPRITE_ZERO_HIT | STATUS_SPRITE_OVERFLOW);
+                self.update_nmi();
+            }
+            _ => (),
+        }
+        self.dot += 1;
+        // With rendering on, odd frames skip the last dot of the pre-render scanline.
+        let skip = self.scanline == PRE_RENDER_SCANLINE
+            && self.dot == DOTS_PER_SCANLINE - 1
+            && self.is_odd_frame
+            && self.is_rendering_enabled();
+        if self.dot == DOTS_PER_SCANLINE || skip {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline == SCANLINES_PER_FRAME {
+                self.scanline = 0;
+                self.is_odd_frame = !self.is_odd_frame;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Mirroring;
+    use crate::mapper::namco163::Namco163;
+    use crate::mapper::nrom::Nrom;
+    use crate::mapper::test::cartridge;
+
+    fn run_to(ppu: &mut Ppu, mapper: &mut dyn Mapper, scanline: u16, dot: u16) {
+        while (ppu.get_scanline(), ppu.get_dot()) != (scanline, dot) {
+            ppu.step(mapper);
+        }
+    }
+
+    fn write_vram(ppu: &mut Ppu, mapper: &mut dyn Mapper, addr: Address, data: &[Byte]) {
+        ppu.write_register(0x2006, (addr >> 8) as Byte, mapper);
+        ppu.write_register(0x2006, addr as Byte, mapper);
+        for &byte in data {
+            ppu.write_register(0x2007, byte, mapper);
+        }
+    }
+
+    fn read_vram(ppu: &mut Ppu, mapper: &mut dyn Mapper, addr: Address) -> Byte {
+        ppu.write_register(0x2006, (addr >> 8) as Byte, mapper);
+        ppu.write_register(0x2006, addr as Byte, mapper);
+        if addr < 0x3F00 {
+            ppu.read_register(0x2007, mapper);
+        }
+        ppu.read_register(0x2007, mapper)
+    }
+
+    fn set_scroll(ppu: &mut Ppu, mapper: &mut dyn Mapper, x: Byte, y: Byte) {
+        ppu.write_register(0x2000, 0, mapper);
+        ppu.write_register(0x2005, x, mapper);
+        ppu.write_register(0x2005, y, mapper);
+    }
+
+    // A CHR RAM board whose tile 1 has color 1 in its left half and color 2 in its
+    // right half, placed down nametable column 0 with palette 1 everywhere.
+    fn striped_column() -> (Ppu, Nrom) {
+        let mut mapper = Nrom::new(cartridge(0, 1, 0));
+        let mut ppu = Ppu::new();
+        write_vram(&mut ppu, &mut mapper, 0x0010, &[0xF0; 8]);
+        write_vram(&mut ppu, &mut mapper, 0x0018, &[0x0F; 8]);
+        write_vram(&mut ppu, &mut mapper, 0x23C0, &[0x55; 0x40]);
+        write_vram(
+            &mut ppu,
+            &mut mapper,
+            0x3F00,
+            &[0x0F, 0, 0, 0, 0, 0x16, 0x27],
+        );
+        ppu.write_register(0x2000, CONTROL_INCREMENT_32, &mut mapper);
+        write_vram(&mut ppu, &mut mapper, 0x2000, &[0x01; 30]);
+        set_scroll(&mut ppu, &mut mapper, 0, 0);
+        (ppu, mapper)
+    }
+
+    // Fills OAM with the given sprites and parks the rest below the screen. Sprite
+    // palette 0 is $30/$21/$2A.
+    fn load_sprites(ppu: &mut Ppu, mapper: &mut dyn Mapper, sprites: &[[Byte; 4]]) {
+        let mut oam = [0xF0; 0x100];
+        for (i, sprite) in sprites.iter().enumerate() {
+            oam[i * 4..i * 4 + 4].copy_from_slice(sprite);
+        }
+        ppu.write_register(0x2003, 0x00, mapper);
+        for &byte in oam.iter() {
+            ppu.write_register(0x2004, byte, mapper);
+        }
+        ppu.write_register(0x2000, 0, mapper);
+        write_vram(ppu, mapper, 0x3F11, &[0x30, 0x21, 0x2A]);
+        set_scroll(ppu, mapper, 0, 0);
+    }
+
+    const SHOW_ALL: Byte = MASK_SHOW_BACKGROUND
+        | MASK_SHOW_BACKGROUND_LEFT
+        | MASK_SHOW_SPRITES
+        | MASK_SHOW_SPRITES_LEFT;
+
+    fn render_frame(ppu: &mut Ppu, mapper: &mut dyn Mapper) {
+        run_to(ppu, mapper, PRE_RENDER_SCANLINE, 0);
+        run_to(ppu, mapper, VBLANK_SCANLINE, 0);
+    }
+
+    fn pixel(ppu: &Ppu, x: usize, y: usize) -> Word {
+        ppu.get_framebuffer()[y * SCREEN_WIDTH + x]
+    }
+
+    #[test]
+    fn test_scroll_and_address_registers() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2000, 0x03, &mut mapper);
+        assert_eq!(ppu.t, 0x0C00);
+        ppu.write_register(0x2005, 0x7D, &mut mapper);
+        assert_eq!((ppu.t, ppu.x, ppu.w), (0x0C0F, 0x05, true));
+        ppu.write_register(0x2005, 0x5E, &mut mapper);
+        assert_eq!((ppu.t, ppu.w), (0x6D6F, false));
+        ppu.write_register(0x2006, 0x3D, &mut mapper);
+        assert_eq!(ppu.t, 0x3D6F);
+        ppu.write_register(0x2006, 0xF0, &mut mapper);
+        assert_eq!((ppu.t, ppu.v), (0x3DF0, 0x3DF0));
+
+        // Reading PPUSTATUS resets the shared write toggle.
+        ppu.write_register(0x2006, 0x21, &mut mapper);
+        ppu.read_register(0x2002, &mut mapper);
+        ppu.write_register(0x2006, 0x22, &mut mapper);
+        ppu.write_register(0x2006, 0x00, &mut mapper);
+        assert_eq!(ppu.v, 0x2200);
+    }
+
+    #[test]
+    fn test_data_read_buffer_and_increment() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2006, 0x20, &mut mapper);
+        ppu.write_register(0x2006, 0x00, &mut mapper);
+        for data in [0x11, 0x22, 0x33].iter() {
+            ppu.write_register(0x2007, *data, &mut mapper);
+        }
+        ppu.write_register(0x2006, 0x20, &mut mapper);
+        ppu.write_register(0x2006, 0x00, &mut mapper);
+        assert_eq!(ppu.read_register(0x2007, &mut mapper), 0x00);
+        assert_eq!(ppu.read_register(0x2007, &mut mapper), 0x11);
+        assert_eq!(ppu.read_register(0x2007, &mut mapper), 0x22);
+
+        // Increment by 32 walks down a nametable column; $3FFF mirrors $2007.
+        ppu.write_register(0x2000, CONTROL_INCREMENT_32, &mut mapper);
+        ppu.write_register(0x2006, 0x20, &mut mapper);
+        ppu.write_register(0x2006, 0x00, &mut mapper);
+        ppu.write_register(0x3FFF, 0x44, &mut mapper);
+        assert_eq!(ppu.v, 0x2020);
+        assert_eq!(ppu.nametable_ram[0x0000], 0x44);
+    }
+
+    #[test]
+    fn test_palette_reads_bypass_the_buffer() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2006, 0x2F, &mut mapper);
+        ppu.write_register(0x2006, 0x01, &mut mapper);
+        ppu.write_register(0x2007, 0x99, &mut mapper);
+        ppu.write_register(0x2006, 0x3F, &mut mapper);
+        ppu.write_register(0x2006, 0x01, &mut mapper);
+        ppu.write_register(0x2007, 0x2A, &mut mapper);
+        ppu.write_register(0x2006, 0x3F, &mut mapper);
+        ppu.write_register(0x2006, 0x01, &mut mapper);
+        assert_eq!(ppu.read_register(0x2007, &mut mapper), 0x2A);
+        assert_eq!(ppu.read_buffer, 0x99);
+    }
+
+    #[test]
+    fn test_pattern_table_reads_go_to_the_cartridge() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2006, 0x1C, &mut mapper);
+        ppu.write_register(0x2006, 0x00, &mut mapper);
+        ppu.read_register(0x2007, &mut mapper);
+        assert_eq!(ppu.read_register(0x2007, &mut mapper), 7);
+    }
+
+    #[test]
+    fn test_oam_data() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2003, 0x01, &mut mapper);
+        ppu.write_register(0x2004, 0xAA, &mut mapper);
+        ppu.write_register(0x2004, 0xFF, &mut mapper);
+        ppu.write_register(0x2003, 0x01, &mut mapper);
+        assert_eq!(ppu.read_register(0x2004, &mut mapper), 0xAA);
+        ppu.write_register(0x2003, 0x02, &mut mapper);
+        assert_eq!(ppu.read_register(0x2004, &mut mapper), 0xE3);
+    }
+
+    #[test]
+    fn test_vblank_and_nmi() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2000, CONTROL_NMI_ENABLE, &mut mapper);
+        run_to(&mut ppu, &mut mapper, VBLANK_SCANLINE, 1);
+        assert!(!ppu.take_nmi());
+        ppu.step(&mut mapper);
+        assert!(ppu.take_nmi());
+        assert!(!ppu.take_nmi());
+
+        // Reading PPUSTATUS reports and clears VBlank, and write-only bits come from the latch.
+        ppu.write_register(0x2000, CONTROL_NMI_ENABLE | 0x1F, &mut mapper);
+        assert_eq!(ppu.read_register(0x2002, &mut mapper), 0x9F);
+        assert_eq!(ppu.read_register(0x2002, &mut mapper) & STATUS_VBLANK, 0);
+    }
+
+    #[test]
+    fn test_enabling_nmi_during_vblank_triggers_it() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut ppu = Ppu::new();
+        run_to(&mut ppu, &mut mapper, VBLANK_SCANLINE + 1, 0);
+        assert!(!ppu.take_nmi());
+        ppu.write_register(0x2000, CONTROL_NMI_ENABLE, &mut mapper);
+        assert!(ppu.take_nmi());
+        // Toggling the enable while still in VBlank fires again.
+        ppu.write_register(0x2000, 0, &mut mapper);
+        ppu.write_register(0x2000, CONTROL_NMI_ENABLE, &mut mapper);
+        assert!(ppu.take_nmi());
+
+        run_to(&mut ppu, &mut mapper, PRE_RENDER_SCANLINE, 2);
+        assert_eq!(ppu.status & STATUS_VBLANK, 0);
+    }
+
+    #[test]
+    fn test_odd_frames_are_one_dot_shorter_when_rendering() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2001, MASK_SHOW_BACKGROUND, &mut mapper);
+        let mut frame_dots = |ppu: &mut Ppu| {
+            let mut dots = 0;
+            loop {
+                ppu.step(&mut mapper);
+                dots += 1;
+                if (ppu.get_scanline(), ppu.get_dot()) == (0, 0) {
+                    return dots;
+                }
+            }
+        };
+        let first = frame_dots(&mut ppu);
+        let second = frame_dots(&mut ppu);
+        assert_eq!(first.max(second), 341 * 262);
+        assert_eq!(first.min(second), 341 * 262 - 1);
+    }
+    #[test]
+    fn test_background_rendering() {
+        let (mut ppu, mut mapper) = striped_column();
+        ppu.write_register(
+            0x2001,
+            MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT,
+            &mut mapper,
+        );
+        render_frame(&mut ppu, &mut mapper);
+        for y in [0, 7, 8, 239].iter() {
+            let row: Vec<Word> = (0..9).map(|x| pixel(&ppu, x, *y)).collect();
+            assert_eq!(row, [0x16, 0x16, 0x16, 0x16, 0x27, 0x27, 0x27, 0x27, 0x0F]);
+        }
+        assert!(ppu.take_frame());
+    }
+
+    #[test]
+    fn test_fine_and_coarse_scroll() {
+        let (mut ppu, mut mapper) = striped_column();
+        ppu.write_register(
+            0x2001,
+            MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT,
+            &mut mapper,
+        );
+        set_scroll(&mut ppu, &mut mapper, 3, 0);
+        render_frame(&mut ppu, &mut mapper);
+        assert_eq!(pixel(&ppu, 0, 0), 0x16);
+        assert_eq!(pixel(&ppu, 1, 0), 0x27);
+        assert_eq!(pixel(&ppu, 5, 0), 0x0F);
+
+        // Scrolling 250 pixels shows column 0 of the next nametable, which mirrors
+        // nametable 0 here, from screen X 6.
+        set_scroll(&mut ppu, &mut mapper, 250, 0);
+        render_frame(&mut ppu, &mut mapper);
+        assert_eq!(pixel(&ppu, 5, 0), 0x0F);
+        assert_eq!(pixel(&ppu, 6, 0), 0x16);
+        assert_eq!(pixel(&ppu, 10, 0), 0x27);
+    }
+
+    #[test]
+    fn test_left_column_mask() {
+        let (mut ppu, mut mapper) = striped_column();
+        ppu.write_register(0x2001, MASK_SHOW_BACKGROUND, &mut mapper);
+        set_scroll(&mut ppu, &mut mapper, 252, 0);
+        render_frame(&mut ppu, &mut mapper);
+        assert_eq!(pixel(&ppu, 7, 10), 0x0F);
+        assert_eq!(pixel(&ppu, 8, 10), 0x27);
+    }
+
+    #[test]
+    fn test_mid_frame_scroll_split() {
+        let (mut ppu, mut mapper) = striped_column();
+        ppu.write_register(
+            0x2001,
+            MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT,
+            &mut mapper,
+        );
+        render_frame(&mut ppu, &mut mapper);
+        // A write during scanline 100 takes effect at dot 257, so from scanline 101.
+        run_to(&mut ppu, &mut mapper, 100, 200);
+        set_scroll(&mut ppu, &mut mapper, 8, 0);
+        run_to(&mut ppu, &mut mapper, VBLANK_SCANLINE, 0);
+        assert_eq!(pixel(&ppu, 0, 0), 0x16);
+        assert_eq!(pixel(&ppu, 0, 100), 0x16);
+        assert_eq!(pixel(&ppu, 0, 101), 0x0F);
+        assert_eq!(pixel(&ppu, 0, 239), 0x0F);
+    }
+
+    #[test]
+    fn test_data_access_while_rendering_bumps_scroll() {
+        let (mut ppu, mut mapper) = striped_column();
+        ppu.write_register(0x2001, MASK_SHOW_BACKGROUND, &mut mapper);
+        run_to(&mut ppu, &mut mapper, 10, 100);
+        ppu.write_register(0x2006, 0x20, &mut mapper);
+        ppu.write_register(0x2006, 0x00, &mut mapper);
+        ppu.read_register(0x2007, &mut mapper);
+        assert_eq!(ppu.v, 0x3001);
+    }
+
+    #[test]
+    fn test_backdrop_when_rendering_is_off() {
+        let (mut ppu, mut mapper) = striped_column();
+        render_frame(&mut ppu, &mut mapper);
+        assert_eq!(pixel(&ppu, 0, 0), 0x0F);
+        assert_eq!(pixel(&ppu, 255, 239), 0x0F);
+    }
+
+    #[test]
+    fn test_sprite_rendering_flip_and_priority() {
+        let (mut ppu, mut mapper) = striped_column();
+        load_sprites(
+            &mut ppu,
+            &mut mapper,
+            &[[20, 1, 0x00, 16], [20, 1, 0x40, 32], [20, 1, 0x20, 0]],
+        );
+        ppu.write_register(0x2001, SHOW_ALL, &mut mapper);
+        render_frame(&mut ppu, &mut mapper);
+        // Sprites appear one scanline below their Y coordinate.
+        assert_eq!(pixel(&ppu, 16, 20), 0x0F);
+        assert_eq!(pixel(&ppu, 16, 21), 0x30);
+        assert_eq!(pixel(&ppu, 20, 21), 0x21);
+        assert_eq!(pixel(&ppu, 16, 28), 0x30);
+        assert_eq!(pixel(&ppu, 16, 29), 0x0F);
+        assert_eq!(pixel(&ppu, 32, 21), 0x21);
+        assert_eq!(pixel(&ppu, 36, 21), 0x30);
+        // The sprite behind the background only shows through transparent pixels.
+        assert_eq!(pixel(&ppu, 0, 21), 0x16);
+    }
+
+    #[test]
+    fn test_8x16_sprites_with_vertical_flip() {
+        let (mut ppu, mut mapper) = striped_column();
+        // Even tile 0 selects tiles 0 and 1 of the left pattern table; tile 0 is blank.
+        load_sprites(
+            &mut ppu,
+            &mut mapper,
+            &[[50, 0x00, 0x00, 16], [50, 0x00, 0x80, 32]],
+        );
+        ppu.write_register(0x2000, CONTROL_SPRITE_SIZE_16, &mut mapper);
+        ppu.write_register(0x2001, SHOW_ALL, &mut mapper);
+        render_frame(&mut ppu, &mut mapper);
+        assert_eq!(pixel(&ppu, 16, 58), 0x0F);
+        assert_eq!(pixel(&ppu, 16, 59), 0x30);
+        assert_eq!(pixel(&ppu, 16, 66), 0x30);
+        assert_eq!(pixel(&ppu, 32, 51), 0x30);
+        assert_eq!(pixel(&ppu, 32, 58), 0x30);
+        assert_eq!(pixel(&ppu, 32, 59), 0x0F);
+    }
+
+    #[test]
+    fn test_sprite_zero_hit_timing() {
+        let (mut ppu, mut mapper) = striped_column();
+        load_sprites(&mut ppu, &mut mapper, &[[20, 1, 0x00, 0]]);
+        ppu.write_register(0x2001, SHOW_ALL, &mut mapper);
+        // The first frame starts from the v left by the VRAM writes.
+        render_frame(&mut ppu, &mut mapper);
+        run_to(&mut ppu, &mut mapper, 21, 1);
+        assert_eq!(ppu.status & STATUS_SPRITE_ZERO_HIT, 0);
+        ppu.step(&mut mapper);
+        assert_ne!(ppu.status & STATUS_SPRITE_ZERO_HIT, 0);
+        run_to(&mut ppu, &mut mapper, PRE_RENDER_SCANLINE, 2);
+        assert_eq!(ppu.status & STATUS_SPRITE_ZERO_HIT, 0);
+
+        // Clipping the sprite out of the left column prevents the hit.
+        ppu.write_register(0x2001, SHOW_ALL & !MASK_SHOW_SPRITES_LEFT, &mut mapper);
+        render_frame(&mut ppu, &mut mapper);
+        assert_eq!(ppu.status & STATUS_SPRITE_ZERO_HIT, 0);
+    }
+
+    #[test]
+    fn test_sprite_overflow_flag() {
+        let (mut ppu, mut mapper) = striped_column();
+        load_sprites(&mut ppu, &mut mapper, &[[100, 1, 0x00, 64]; 9]);
+        ppu.write_register(0x2001, SHOW_ALL, &mut mapper);
+        run_to(&mut ppu, &mut mapper, 100, 0);
+        assert_eq!(ppu.status & STATUS_SPRITE_OVERFLOW, 0);
+        // Secondary OAM clearing shows through OAMDATA.
+        run_to(&mut ppu, &mut mapper, 100, 10);
+        assert_eq!(ppu.read_register(0x2004, &mut mapper), 0xFF);
+        run_to(&mut ppu, &mut mapper, 101, 0);
+        assert_ne!(ppu.status & STATUS_SPRITE_OVERFLOW, 0);
+        run_to(&mut ppu, &mut mapper, PRE_RENDER_SCANLINE, 2);
+        assert_eq!(ppu.status & STATUS_SPRITE_OVERFLOW, 0);
+
+        load_sprites(&mut ppu, &mut mapper, &[[100, 1, 0x00, 64]; 8]);
+        render_frame(&mut ppu, &mut mapper);
+        assert_eq!(ppu.status & STATUS_SPRITE_OVERFLOW, 0);
+    }
+
+    #[test]
+    fn test_nametable_mirroring() {
+        let cases = [
+            (Mirroring::Horizontal, [2, 2, 4, 4]),
+            (Mirroring::Vertical, [3, 4, 3, 4]),
+            (Mirroring::SingleScreenLower, [4, 4, 4, 4]),
+            (Mirroring::SingleScreenUpper, [4, 4, 4, 4]),
+            (Mirroring::FourScreen, [1, 2, 3, 4]),
+        ];
+        for (mirroring, expected) in cases.iter() {
+            let mut mapper = Nrom::new(cartridge(0, 1, 1));
+            mapper.cartridge_mut().mirroring = *mirroring;
+            let mut ppu = Ppu::new();
+            // Later writes overwrite the tables that share a page.
+            for (table, data) in [1, 2, 3, 4].iter().enumerate() {
+                write_vram(
+                    &mut ppu,
+                    &mut mapper,
+                    0x2000 + table as Address * 0x400,
+                    &[*data],
+                );
+            }
+            let tables: Vec<Byte> = (0..4)
+                .map(|table| read_vram(&mut ppu, &mut mapper, 0x2000 + table * 0x400))
+                .collect();
+            assert_eq!(tables, expected, "{:?}", mirroring);
+            // $3000-$3EFF mirrors $2000-$2EFF.
+            assert_eq!(read_vram(&mut ppu, &mut mapper, 0x3C00), expected[3]);
+        }
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        mapper.cartridge_mut().mirroring = Mirroring::SingleScreenUpper;
+        let mut ppu = Ppu::new();
+        write_vram(&mut ppu, &mut mapper, 0x2000, &[0x99]);
+        assert_eq!(ppu.nametable_ram[0x400], 0x99);
+    }
+
+    #[test]
+    fn test_mapper_supplied_nametables() {
+        let mut mapper = Namco163::new(cartridge(19, 8, 8));
+        mapper.cpu_write(0xC000, 3);
+        mapper.cpu_write(0xC800, 0xE1);
+        let mut ppu = Ppu::new();
+        // Table 0 reads CHR ROM page 3 and ignores writes; table 1 is CIRAM page 1.
+        write_vram(&mut ppu, &mut mapper, 0x2000, &[0x55]);
+        write_vram(&mut ppu, &mut mapper, 0x2400, &[0x66]);
+        assert_eq!(read_vram(&mut ppu, &mut mapper, 0x2000), 3);
+        assert_eq!(read_vram(&mut ppu, &mut mapper, 0x2400), 0x66);
+        assert_eq!(ppu.nametable_ram[0x000], 0x00);
+        assert_eq!(ppu.nametable_ram[0x400], 0x66);
+    }
+
+    #[test]
+    fn test_palette_mirrors_and_greyscale() {
+        let mut mapper = Nrom::new(cartridge(0, 1, 1));
+        let mut ppu = Ppu::new();
+        write_vram(&mut ppu, &mut mapper, 0x3F10, &[0x21, 0x22, 0, 0, 0x24]);
+        assert_eq!(read_vram(&mut ppu, &mut mapper, 0x3F00), 0x21);
+        assert_eq!(read_vram(&mut ppu, &mut mapper, 0x3F01), 0x00);
+        assert_eq!(read_vram(&mut ppu, &mut mapper, 0x3F11), 0x22);
+        assert_eq!(read_vram(&mut ppu, &mut mapper, 0x3F04), 0x24);
+        // The whole $3F20-$3FFF range mirrors the 32 entries.
+        assert_eq!(read_vram(&mut ppu, &mut mapper, 0x3F30), 0x21);
+
+        ppu.write_register(0x2001, MASK_GREYSCALE, &mut mapper);
+        assert_eq!(read_vram(&mut ppu, &mut mapper, 0x3F00), 0x20);
+    }
+
+    #[test]
+    fn test_greyscale_and_emphasis_in_the_framebuffer() {
+        let (mut ppu, mut mapper) = striped_column();
+        ppu.write_register(0x2001, SHOW_ALL | MASK_GREYSCALE | 0xA0, &mut mapper);
+        render_frame(&mut ppu, &mut mapper);
+        render_frame(&mut ppu, &mut mapper);
+        assert_eq!(pixel(&ppu, 0, 0), 0x140 | 0x10);
+        assert_eq!(pixel(&ppu, 4, 0), 0x140 | 0x20);
+        assert_eq!(pixel(&ppu, 8, 0), 0x140);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::types::Byte;
+
+// The background half of the rendering pipeline: latches filled by the four fetches of
+// each tile, and the shift registers that feed one pixel per dot. The upper 8 bits of
+// each shifter hold the tile being drawn and the lower 8 bits the next one.
+#[derive(Default)]
+pub struct Background {
+    nametable: Byte,
+    attribute: Byte,
+    pattern_low: Byte,
+    pattern_high: Byte,
+    pattern_shifters: [u16; 2],
+    attribute_shifters: [u16; 2],
+}
+
+impl Background {
+    pub fn get_nametable(&self) -> Byte {
+        self.nametable
+    }
+
+    pub fn set_nametable(&mut self, data: Byte) {
+        self.nametable = data;
+    }
+
+    // Keeps the 2-bit palette of the current tile's quadrant.
+    pub fn set_attribute(&mut self, data: Byte, shift: u16) {
+        self.attribute = (data >> shift) & 0x03;
+    }
+
+    pub fn set_pattern_low(&mut self, data: Byte) {
+        self.pattern_low = data;
+    }
+
+    pub fn set_pattern_high(&mut self, data: Byte) {
+        self.pattern_high = data;
+    }
+
+    // Moves the fetched tile into the low half of the shifters.
+    pub fn reload(&mut self) {
+        self.pattern_shifters[0] = (self.pattern_shifters[0] & 0xFF00) | self.pattern_low as u16;
+        self.pattern_shifters[1] = (self.pattern_shifters[1] & 0xFF00) | self.pattern_high as u16;
+        for (bit, shifter) in self.attribute_shifters.iter_mut().enumerate() {
+            let fill = if (self.attribute >> bit) & 0x01 == 0x01 {
+                0xFF
+            } else {
+                0x00
+            };
+            *shifter = (*shifter & 0xFF00) | fill;
+        }
+    }
+
+    pub fn shift(&mut self) {
+        for shifter in self
+            .pattern_shifters
+            .iter_mut()
+            .chain(self.attribute_shifters.iter_mut())
+        {
+            *shifter <<= 1;
+        }
+    }
+
+    // The 4-bit palette index (attribute << 2 | pattern) at fine X scroll `fine_x`.
+    pub fn get_pixel(&self, fine_x: Byte) -> Byte {
+        let bit = 15 - fine_x as u16;
+        let pick = |shifter: u16| ((shifter >> bit) & 0x01) as Byte;
+        pick(self.attribute_shifters[1]) << 3
+            | pick(self.attribute_shifters[0]) << 2
+            | pick(self.pattern_shifters[1]) << 1
+            | pick(self.pattern_shifters[0])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reload_and_shift() {
+        let mut background = Background::default();
+        background.set_pattern_low(0x80);
+        background.set_pattern_high(0x40);
+        background.set_attribute(0xE4, 2);
+        background.reload();
+        assert_eq!(background.get_pixel(0), 0);
+        for _ in 0..8 {
+            background.shift();
+        }
+        assert_eq!(background.get_pixel(0), 0x05);
+        assert_eq!(background.get_pixel(1), 0x06);
+        assert_eq!(background.get_pixel(2), 0x04);
+    }
+}
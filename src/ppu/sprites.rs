@@ -0,0 +1,311 @@
+use crate::types::{Address, Byte};
+
+const MAX_SPRITES_PER_LINE: usize = 8;
+
+// Sprite attribute byte bits.
+const ATTRIBUTE_PALETTE: Byte = 0x03;
+const ATTRIBUTE_BEHIND_BACKGROUND: Byte = 0x20;
+const ATTRIBUTE_FLIP_HORIZONTAL: Byte = 0x40;
+const ATTRIBUTE_FLIP_VERTICAL: Byte = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Evaluation {
+    // Checking the Y coordinate of sprite n.
+    Scan,
+    // Copying the remaining bytes of an in-range sprite into secondary OAM.
+    Copy,
+    // Eight sprites were found; looking for a ninth with the hardware's bug.
+    Overflow,
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    x: Byte,
+    attributes: Byte,
+    pattern_low: Byte,
+    pattern_high: Byte,
+}
+
+pub struct SpritePixel {
+    // Palette RAM index, $10-$1F.
+    pub index: Byte,
+    pub is_behind_background: bool,
+    pub is_sprite_zero: bool,
+}
+
+// The sprite half of the rendering pipeline. Each visible scanline evaluates primary
+// OAM into secondary OAM for the next line (dots 65-256), and dots 257-320 load the
+// eight output slots that draw it.
+pub struct Sprites {
+    secondary_oam: [Byte; 32],
+    evaluation: Evaluation,
+    // The primary OAM sprite and byte being examined.
+    n: usize,
+    m: usize,
+    found: usize,
+    is_sprite_zero_found: bool,
+    slots: [Slot; MAX_SPRITES_PER_LINE],
+    has_sprite_zero: bool,
+}
+
+impl Default for Sprites {
+    fn default() -> Self {
+        Sprites {
+            secondary_oam: [0xFF; 32],
+            evaluation: Evaluation::Done,
+            n: 0,
+            m: 0,
+            found: 0,
+            is_sprite_zero_found: false,
+            slots: [Slot::default(); MAX_SPRITES_PER_LINE],
+            has_sprite_zero: false,
+        }
+    }
+}
+
+impl Sprites {
+    // Dots 1-64 fill secondary OAM with $FF.
+    pub fn clear_secondary_oam(&mut self) {
+        self.secondary_oam = [0xFF; 32];
+        self.found = 0;
+        self.is_sprite_zero_found = false;
+        self.evaluation = Evaluation::Done;
+    }
+
+    pub fn start_evaluation(&mut self) {
+        self.evaluation = Evaluation::Scan;
+        self.n = 0;
+        self.m = 0;
+    }
+
+    fn is_in_range(y: Byte, scanline: u16, height: u16) -> bool {
+        scanline.wrapping_sub(y as u16) < height
+    }
+
+    fn next_sprite(&mut self) {
+        self.n += 1;
+        if self.n == 64 {
+            self.evaluation = Evaluation::Done;
+        }
+    }
+
+    // One read/write pair of the evaluation, two dots long. Returns true when the
+    // sprite overflow flag should be set.
+    pub fn evaluate(&mut self, oam: &[Byte; 0x100], scanline: u16, height: u16) -> bool {
+        match self.evaluation {
+            Evaluation::Scan => {
+                let y = oam[self.n * 4];
+                self.secondary_oam[self.found * 4] = y;
+                if Self::is_in_range(y, scanline, height) {
+                    self.is_sprite_zero_found |= self.n == 0;
+                    self.m = 1;
+                    self.evaluation = Evaluation::Copy;
+                } else {
+                    self.next_sprite();
+                }
+            }
+            Evaluation::Copy => {
+                self.secondary_oam[self.found * 4 + self.m] = oam[self.n * 4 + self.m];
+                self.m += 1;
+                if self.m == 4 {
+                    self.m = 0;
+                    self.found += 1;
+                    self.evaluation = if self.found == MAX_SPRITES_PER_LINE {
+                        Evaluation::Overflow
+                    } else {
+                        Evaluation::Scan
+                    };
+                    self.next_sprite();
+                }
+            }
+            Evaluation::Overflow => {
+                // The hardware treats byte m of sprite n as a Y coordinate and, on a
+                // miss, increments m along with n, so it checks tiles, attributes and
+                // X positions as well: this is the overflow bug.
+                if Self::is_in_range(oam[self.n * 4 + self.m], scanline, height) {
+                    self.evaluation = Evaluation::Done;
+                    return true;
+                }
+                self.m = (self.m + 1) & 0x03;
+                self.next_sprite();
+            }
+            Evaluation::Done => (),
+        }
+        false
+    }
+
+    pub fn get_secondary_oam(&self) -> &[Byte; 32] {
+        &self.secondary_oam
+    }
+
+    // Pattern address of the low plane for output slot `slot` on the line after
+    // `scanline`. Empty slots fetch tile $FF.
+    pub fn pattern_address(
+        &self,
+        slot: usize,
+        scanline: u16,
+        table: Address,
+        height: u16,
+    ) -> Address {
+        let entry = &self.secondary_oam[slot * 4..slot * 4 + 4];
+        let (tile, attributes) = (entry[1], entry[2]);
+        let mut row = if slot < self.found {
+            scanline.wrapping_sub(entry[0] as u16) & (height - 1)
+        } else {
+            0
+        };
+        if slot < self.found && attributes & ATTRIBUTE_FLIP_VERTICAL != 0 {
+            row = height - 1 - row;
+        }
+        if height == 16 {
+            let table = (tile as Address & 0x01) << 12;
+            let tile = (tile & 0xFE) as Address + (row >> 3);
+            table | tile << 4 | (row & 0x07)
+        } else {
+            table | (tile as Address) << 4 | row
+        }
+    }
+
+    // Called at dot 257, before the slots are loaded.
+    pub fn start_loading(&mut self) {
+        self.has_sprite_zero = self.is_sprite_zero_found;
+    }
+
+    pub fn load_slot(&mut self, slot: usize, pattern_low: Byte, pattern_high: Byte) {
+        if slot >= self.found {
+            self.slots[slot] = Slot::default();
+            return;
+        }
+        let attributes = self.secondary_oam[slot * 4 + 2];
+        let (pattern_low, pattern_high) = if attributes & ATTRIBUTE_FLIP_HORIZONTAL != 0 {
+            (pattern_low.reverse_bits(), pattern_high.reverse_bits())
+        } else {
+            (pattern_low, pattern_high)
+        };
+        self.slots[slot] = Slot {
+            x: self.secondary_oam[slot * 4 + 3],
+            attributes,
+            pattern_low,
+            pattern_high,
+        };
+    }
+
+    // The first opaque sprite pixel at screen X `x`; lower slots win.
+    pub fn get_pixel(&self, x: u16) -> Option<SpritePixel> {
+        self.slots.iter().enumerate().find_map(|(i, slot)| {
+            let offset = x.wrapping_sub(slot.x as u16);
+            if offset >= 8 {
+                return None;
+            }
+            let bit = 7 - offset;
+            let pattern =
+                ((slot.pattern_high >> bit) & 0x01) << 1 | ((slot.pattern_low >> bit) & 0x01);
+            if pattern == 0 {
+                return None;
+            }
+            Some(SpritePixel {
+                index: 0x10 | (slot.attributes & ATTRIBUTE_PALETTE) << 2 | pattern,
+                is_behind_background: slot.attributes & ATTRIBUTE_BEHIND_BACKGROUND != 0,
+                is_sprite_zero: i == 0 && self.has_sprite_zero,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Runs a full evaluation and returns whether the overflow flag was set.
+    fn evaluate_line(sprites: &mut Sprites, oam: &[Byte; 0x100], scanline: u16) -> bool {
+        sprites.clear_secondary_oam();
+        sprites.start_evaluation();
+        let mut overflow = false;
+        for _ in 0..96 {
+            overflow |= sprites.evaluate(oam, scanline, 8);
+        }
+        overflow
+    }
+
+    fn oam_with(sprites: &[[Byte; 4]]) -> [Byte; 0x100] {
+        let mut oam = [0xF0; 0x100];
+        for (i, sprite) in sprites.iter().enumerate() {
+            oam[i * 4..i * 4 + 4].copy_from_slice(sprite);
+        }
+        oam
+    }
+
+    #[test]
+    fn test_eight_sprite_limit_and_overflow() {
+        let mut sprites = Sprites::default();
+        let oam = oam_with(&[[10, 1, 0, 0]; 9]);
+        assert!(evaluate_line(&mut sprites, &oam, 12));
+        assert_eq!(sprites.found, 8);
+        assert_eq!(sprites.get_secondary_oam()[28..], [10, 1, 0, 0]);
+
+        let oam = oam_with(&[[10, 1, 0, 0]; 8]);
+        assert!(!evaluate_line(&mut sprites, &oam, 12));
+        assert!(sprites.is_sprite_zero_found);
+    }
+
+    #[test]
+    fn test_overflow_false_positive() {
+        // Sprite 8 is out of range, so the search reads sprite 9's tile as a Y
+        // coordinate, and tile 10 looks in range.
+        let mut entries = vec![[10, 1, 0, 0]; 8];
+        entries.push([100, 0, 0, 0]);
+        entries.push([200, 10, 0, 0]);
+        let oam = oam_with(&entries);
+        assert!(evaluate_line(&mut Sprites::default(), &oam, 12));
+    }
+
+    #[test]
+    fn test_overflow_false_negative() {
+        // A ninth in-range sprite is missed because its attribute byte is checked.
+        let mut entries = vec![[10, 1, 0, 0]; 8];
+        entries.push([100, 0, 0, 0]);
+        entries.push([200, 100, 0, 0]);
+        entries.push([10, 1, 0x40, 0]);
+        let oam = oam_with(&entries);
+        assert!(!evaluate_line(&mut Sprites::default(), &oam, 12));
+    }
+
+    #[test]
+    fn test_pattern_addresses() {
+        let mut sprites = Sprites::default();
+        let oam = oam_with(&[
+            [10, 0x25, 0x00, 0],
+            [10, 0x25, 0x80, 0],
+            [10, 0x25, 0x80, 0],
+        ]);
+        evaluate_line(&mut sprites, &oam, 13);
+        assert_eq!(sprites.pattern_address(0, 13, 0x1000, 8), 0x1253);
+        assert_eq!(sprites.pattern_address(1, 13, 0x1000, 8), 0x1254);
+        // 8x16 takes the table from bit 0 of the tile.
+        assert_eq!(sprites.pattern_address(0, 13, 0x0000, 16), 0x1243);
+        assert_eq!(sprites.pattern_address(2, 13, 0x0000, 16), 0x1254);
+        assert_eq!(sprites.pattern_address(3, 13, 0x0000, 8), 0x0FF0);
+    }
+
+    #[test]
+    fn test_pixels_with_flip_and_slot_priority() {
+        let mut sprites = Sprites::default();
+        let oam = oam_with(&[[10, 0, 0x41, 4], [10, 0, 0x22, 0]]);
+        evaluate_line(&mut sprites, &oam, 10);
+        sprites.start_loading();
+        sprites.load_slot(0, 0x80, 0x00);
+        sprites.load_slot(1, 0xFF, 0xFF);
+        sprites.load_slot(2, 0xFF, 0xFF);
+
+        let pixel = sprites.get_pixel(0).unwrap();
+        assert_eq!(pixel.index, 0x1B);
+        assert!(pixel.is_behind_background && !pixel.is_sprite_zero);
+        // The flipped sprite 0 has its single pixel at its right edge.
+        let pixel = sprites.get_pixel(11).unwrap();
+        assert_eq!(pixel.index, 0x15);
+        assert!(pixel.is_sprite_zero);
+        assert_eq!(sprites.get_pixel(4).unwrap().index, 0x1B);
+        assert!(sprites.get_pixel(12).is_none());
+    }
+}
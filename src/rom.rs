@@ -15,4 +15,4 @@ impl Rom {
     pub fn size(&self) -> usize {
         self.vec.len()
     }
-}
\ No newline at end of file
+}